@@ -1,56 +1,733 @@
-use actix_web::{get, post, web, Responder};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, Responder};
 use log::{debug, info, warn};
 
 use std::sync::Arc;
 
+use crate::auth::{Principal, Scope};
+use crate::catalog::{CatalogConfig, EventTypeCatalog, EventTypeDefinition};
+use crate::dedup::{DedupWindow, Observation};
+use crate::erasure::{ErasureRequest, ErasureService};
 use crate::error::AppError;
-use crate::model::{Event, EventQuery, NewEvent};
+use crate::idempotency::{
+    Claim, IdempotencyCache, IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER,
+};
+use crate::metrics::{event_type_label, Metrics};
+use crate::model::{
+    Event, EventQuery, NewEvent, StoreStatus, StreamAppend, StreamQuery, UsageQuery,
+};
+use crate::projection::{ProjectionDefinition, ProjectionRegistry};
+use crate::redaction::{RedactionRule, Redactor};
+use crate::schema::SchemaRegistry;
+use crate::skew::ClockSkewPolicy;
 use crate::storage::EventStore;
+use crate::upcast::UpcasterRegistry;
+use crate::usage::{ingested_bytes, usage_key, UsageTracker};
+use chrono::Utc;
 use uuid::Uuid;
 
 #[post("/events")]
+#[allow(clippy::too_many_arguments)]
 async fn post_event(
+    principal: Principal,
+    req: HttpRequest,
     store: web::Data<Arc<dyn EventStore>>,
     payload: web::Json<NewEvent>,
-) -> Result<impl Responder, AppError> {
-    debug!("Received event: {:#?}", payload);
-    let new_event = Event {
-        id: Uuid::new_v4(),
-        event_type: payload.event_type.clone(),
-        timestamp: payload.timestamp,
-        payload: payload.payload.clone(),
+    metrics: Option<web::Data<Metrics>>,
+    redactor: Option<web::Data<Redactor>>,
+    schemas: Option<web::Data<SchemaRegistry>>,
+    catalog: Option<web::Data<EventTypeCatalog>>,
+    skew: Option<web::Data<ClockSkewPolicy>>,
+    idempotency: Option<web::Data<IdempotencyCache>>,
+    dedup: Option<web::Data<DedupWindow>>,
+    projections: Option<web::Data<ProjectionRegistry>>,
+    usage: Option<web::Data<UsageTracker>>,
+) -> Result<HttpResponse, AppError> {
+    principal.require(Scope::EventsWrite)?;
+    let tenant = principal.tenant();
+    //The payload may still contain PII at this point, so only the type is logged
+    debug!(
+        "Received event of type {} for tenant {}",
+        payload.event_type, tenant
+    );
+    check_new_event(&payload, catalog.as_ref(), schemas.as_ref())?;
+
+    //Retries are recognised by the Idempotency-Key header, or failing that the client's event id
+    let new_event = payload.into_inner();
+    let idempotency_key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .map_err(|_| {
+                    AppError::BadRequest(format!(
+                        "{IDEMPOTENCY_KEY_HEADER} must contain only visible ASCII characters"
+                    ))
+                })?
+                .to_string(),
+        ),
+        None => new_event.id.map(|id| id.to_string()),
+    };
+    let body =
+        serde_json::to_vec(&new_event).map_err(|e| AppError::InternalError(e.to_string()))?;
+    let fingerprint = IdempotencyCache::fingerprint(&body);
+    let ingested = body.len() as u64;
+    let client_id = new_event.id;
+    //Dedup compares the timestamp the client sent, not the arrival time it defaults to
+    let client_timestamp = new_event.timestamp;
+    let new_event = prepare_event(new_event, skew.as_ref(), redactor.as_ref())?;
+
+    let claim = match (&idempotency, &idempotency_key) {
+        (Some(cache), Some(key)) => match cache.claim(tenant, key, fingerprint, new_event.id)? {
+            Claim::New => Some((cache, key)),
+            Claim::Replay(id) => {
+                return match store.get_by_id(tenant, id)? {
+                    Some(existing) => Ok(replayed(existing)),
+                    None => Err(AppError::Conflict(format!(
+                        "Event {id} for idempotency key '{key}' is still being stored or has been deleted"
+                    ))),
+                };
+            }
+        },
+        _ => None,
+    };
+    //Releases the idempotency claim of a request that ends up storing nothing
+    let release_claim = || -> Result<(), AppError> {
+        if let Some((cache, key)) = claim {
+            cache.release(tenant, key)?;
+        }
+        Ok(())
     };
 
-    store.add_event(new_event.clone())?;
+    //The content is reserved before the write, so concurrent copies cannot both be stored
+    let reservation = match &dedup {
+        Some(dedup) => match dedup.observe(tenant, &new_event, client_timestamp)? {
+            Observation::New(reservation) => Some((dedup, reservation)),
+            Observation::Duplicate(original) => match store.get_by_id(tenant, original)? {
+                Some(existing) => {
+                    release_claim()?;
+                    dedup.record_dropped(&new_event, original);
+                    if let Some(metrics) = &metrics {
+                        metrics.record_deduplicated(event_type_label(
+                            catalog.as_ref(),
+                            &new_event.event_type,
+                        ));
+                    }
+                    return Ok(replayed(existing));
+                }
+                //The original has since been deleted, so this copy is stored in its place
+                None => match dedup.take_over(tenant, &new_event, client_timestamp, original)? {
+                    Some(reservation) => Some((dedup, reservation)),
+                    None => {
+                        release_claim()?;
+                        return Err(AppError::Conflict(
+                            "An identical event is still being stored".to_string(),
+                        ));
+                    }
+                },
+            },
+            Observation::Pending(original) => {
+                release_claim()?;
+                return Err(AppError::Conflict(format!(
+                    "An identical event {original} is still being stored"
+                )));
+            }
+        },
+        None => None,
+    };
+    //Only events that are actually stored count against the daily quota
+    let usage_key = usage_key(&principal);
+    if let Some(usage) = &usage {
+        if let Err(e) = usage.charge(&usage_key, 1, ingested, Utc::now()) {
+            if let Some((dedup, reservation)) = reservation {
+                dedup.release(reservation)?;
+            }
+            if let Some((cache, key)) = claim {
+                cache.release(tenant, key)?;
+            }
+            return Err(e);
+        }
+    }
+    if let Err(e) = store.add_event(tenant, new_event.clone()) {
+        if let Some((dedup, reservation)) = reservation {
+            dedup.release(reservation)?;
+        }
+        if let Some(usage) = &usage {
+            usage.refund(&usage_key, 1, ingested, Utc::now())?;
+        }
+        //Event ids are write-once: a client id that is already stored is a replay, never an
+        //overwrite.  The claim, if any, already points at the stored event.
+        if let (Some(id), AppError::Conflict(_)) = (client_id, &e) {
+            if let Some(existing) = store.get_by_id(tenant, id)? {
+                return Ok(replayed(existing));
+            }
+        }
+        if let Some((cache, key)) = claim {
+            cache.release(tenant, key)?;
+        }
+        return Err(e);
+    }
+    if let Some((dedup, reservation)) = reservation {
+        dedup.confirm(reservation)?;
+    }
+    if let Some(projections) = &projections {
+        projections.apply(tenant, &new_event)?;
+    }
+    if let Some(metrics) = metrics {
+        metrics.record_ingested(event_type_label(catalog.as_ref(), &new_event.event_type));
+    }
+
+    info!(
+        "Stored event {} of type {}",
+        new_event.id, new_event.event_type
+    );
 
-    info!("Stored event: {:#?}", new_event);
+    Ok(HttpResponse::Ok().json(new_event))
+}
 
-    Ok(web::Json(new_event))
+//Ingest checks on the event as the producer sent it, shared by POST /events and stream appends
+fn check_new_event(
+    new_event: &NewEvent,
+    catalog: Option<&web::Data<EventTypeCatalog>>,
+    schemas: Option<&web::Data<SchemaRegistry>>,
+) -> Result<(), AppError> {
+    if let Some(catalog) = catalog {
+        catalog.check(&new_event.event_type)?;
+    }
+    //Validate the payload as the producer sent it, before redaction rewrites any values
+    if let Some(schemas) = schemas {
+        schemas.validate(&new_event.event_type, &new_event.payload)?;
+    }
+    Ok(())
+}
+
+//Builds the event to store: applies the clock-skew policy, then redacts the payload and metadata
+fn prepare_event(
+    new_event: NewEvent,
+    skew: Option<&web::Data<ClockSkewPolicy>>,
+    redactor: Option<&web::Data<Redactor>>,
+) -> Result<Event, AppError> {
+    let mut event = new_event.into_event();
+    if let Some(skew) = skew {
+        skew.apply(&mut event)?;
+    }
+    if let Some(redactor) = redactor {
+        let redacted = redactor.redact_event(&mut event)?;
+        debug!("Redacted {} value(s) from event {}", redacted, event.id);
+    }
+    Ok(event)
+}
+
+//Response for a retried POST /events: the originally stored event, marked as a replay
+fn replayed(event: Event) -> HttpResponse {
+    info!("Replayed stored event {}", event.id);
+    HttpResponse::Ok()
+        .insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"))
+        .json(event)
+}
+
+#[post("/streams/{stream_id}/events")]
+#[allow(clippy::too_many_arguments)]
+async fn append_stream_events(
+    principal: Principal,
+    store: web::Data<Arc<dyn EventStore>>,
+    path: web::Path<String>,
+    body: web::Json<StreamAppend>,
+    metrics: Option<web::Data<Metrics>>,
+    redactor: Option<web::Data<Redactor>>,
+    schemas: Option<web::Data<SchemaRegistry>>,
+    catalog: Option<web::Data<EventTypeCatalog>>,
+    skew: Option<web::Data<ClockSkewPolicy>>,
+    projections: Option<web::Data<ProjectionRegistry>>,
+    usage: Option<web::Data<UsageTracker>>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::EventsWrite)?;
+    let tenant = principal.tenant();
+    let stream_id = path.into_inner();
+    let append = body.into_inner();
+    debug!(
+        "Received {} event(s) for stream {}, expected version {:?}",
+        append.events.len(),
+        stream_id,
+        append.expected_version
+    );
+    if append.events.is_empty() {
+        return Err(AppError::BadRequest(
+            "At least one event is required".to_string(),
+        ));
+    }
+
+    //Every event is checked before any is appended so a batch is all-or-nothing
+    let mut ingested = 0;
+    for new_event in &append.events {
+        check_new_event(new_event, catalog.as_ref(), schemas.as_ref())?;
+        ingested += ingested_bytes(new_event)?;
+    }
+    let count = append.events.len() as u64;
+    let events = append
+        .events
+        .into_iter()
+        .map(|new_event| prepare_event(new_event, skew.as_ref(), redactor.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some(usage) = &usage {
+        usage.charge(&usage_key(&principal), count, ingested, Utc::now())?;
+    }
+    let appended = match store.append_to_stream(tenant, &stream_id, append.expected_version, events)
+    {
+        Ok(appended) => appended,
+        Err(e) => {
+            if let Some(usage) = &usage {
+                usage.refund(&usage_key(&principal), count, ingested, Utc::now())?;
+            }
+            return Err(e);
+        }
+    };
+    if let Some(projections) = &projections {
+        for event in &appended.events {
+            projections.apply(tenant, event)?;
+        }
+    }
+    if let Some(metrics) = metrics {
+        for event in &appended.events {
+            metrics.record_ingested(event_type_label(catalog.as_ref(), &event.event_type));
+        }
+    }
+    info!(
+        "Stream {} now at version {}",
+        appended.stream_id, appended.version
+    );
+    Ok(web::Json(appended))
+}
+
+#[get("/streams/{stream_id}")]
+async fn get_stream(
+    principal: Principal,
+    store: web::Data<Arc<dyn EventStore>>,
+    path: web::Path<String>,
+    query: web::Query<StreamQuery>,
+    upcasters: Option<web::Data<UpcasterRegistry>>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::EventsRead)?;
+    let stream_id = path.into_inner();
+    match store.read_stream(principal.tenant(), &stream_id, query.from_version)? {
+        Some(mut stream) => {
+            if let Some(upcasters) = upcasters {
+                stream.events = upcasters.upcast_all(stream.events)?;
+            }
+            debug!(
+                "Read {} event(s) from stream {}",
+                stream.events.len(),
+                stream_id
+            );
+            Ok(web::Json(stream))
+        }
+        None => {
+            warn!("Stream {} not found", stream_id);
+            Err(AppError::NotFound(format!("Stream {stream_id} not found")))
+        }
+    }
 }
 
 #[get("/events")]
 async fn get_events(
+    principal: Principal,
     store: web::Data<Arc<dyn EventStore>>,
     query: web::Query<EventQuery>,
+    upcasters: Option<web::Data<UpcasterRegistry>>,
 ) -> Result<impl Responder, AppError> {
+    principal.require(Scope::EventsRead)?;
     debug!("Received query: {:#?}", query);
-    let results = store.query_events(query.into_inner())?;
-    info!("Query results: {:#?}", results);
+    let mut results = store.query_events(principal.tenant(), query.into_inner())?;
+    if let Some(upcasters) = upcasters {
+        results = upcasters.upcast_all(results)?;
+    }
+    info!("Query returned {} event(s)", results.len());
     Ok(web::Json(results))
 }
 
 #[get("/events/{id}")]
 async fn get_event_by_id(
+    principal: Principal,
     store: web::Data<Arc<dyn EventStore>>,
     path: web::Path<Uuid>,
+    upcasters: Option<web::Data<UpcasterRegistry>>,
 ) -> Result<impl Responder, AppError> {
+    principal.require(Scope::EventsRead)?;
     debug!("Received id: {:#?}", path);
     let id = path.into_inner();
-    if let Some(event) = store.get_by_id(id)? {
-        info!("Found event: {:#?}", event);
+    if let Some(mut event) = store.get_by_id(principal.tenant(), id)? {
+        if let Some(upcasters) = upcasters {
+            event = upcasters.upcast(event)?;
+        }
+        info!("Found event {} of type {}", event.id, event.event_type);
         Ok(web::Json(event))
     } else {
         warn!("Event {} not found", id);
         Err(AppError::NotFound(format!("Event {id} not found")))
     }
 }
+
+#[delete("/events/{id}")]
+async fn delete_event(
+    principal: Principal,
+    store: web::Data<Arc<dyn EventStore>>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    let id = path.into_inner();
+    if store.delete(principal.tenant(), id)? == 0 {
+        warn!("Event {} not found", id);
+        return Err(AppError::NotFound(format!("Event {id} not found")));
+    }
+    info!("Deleted event {}", id);
+    Ok(web::Json(serde_json::json!({ "deleted": 1 })))
+}
+
+#[delete("/events")]
+async fn delete_events(
+    principal: Principal,
+    store: web::Data<Arc<dyn EventStore>>,
+    query: web::Query<EventQuery>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    debug!("Received delete query: {:#?}", query);
+    //Refuse to purge the whole store from a request with no filters
+    if query.is_unfiltered() {
+        return Err(AppError::BadRequest(
+            "At least one filter (event_type, start, end or a metadata field) is required"
+                .to_string(),
+        ));
+    }
+    let deleted = store.delete_matching(principal.tenant(), query.into_inner())?;
+    info!("Deleted {} event(s)", deleted);
+    Ok(web::Json(serde_json::json!({ "deleted": deleted })))
+}
+
+#[get("/stats")]
+async fn get_stats(
+    principal: Principal,
+    store: web::Data<Arc<dyn EventStore>>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::EventsRead)?;
+    let stats = store.stats(Some(principal.tenant()))?;
+    debug!("Store stats: {:#?}", stats);
+    Ok(web::Json(stats))
+}
+
+//Today's ingestion by the caller's key against its daily quota.  Admins can look up any key.
+#[get("/usage")]
+async fn get_usage(
+    principal: Principal,
+    query: web::Query<UsageQuery>,
+    usage: web::Data<UsageTracker>,
+) -> Result<impl Responder, AppError> {
+    let own_key = usage_key(&principal);
+    let key = match &query.key {
+        Some(key) if *key != own_key => {
+            principal.require(Scope::Admin)?;
+            key.as_str()
+        }
+        _ => own_key.as_str(),
+    };
+    Ok(web::Json(usage.report(key, Utc::now())?))
+}
+
+#[get("/metrics")]
+async fn get_metrics(
+    principal: Principal,
+    store: web::Data<Arc<dyn EventStore>>,
+    metrics: web::Data<Metrics>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::MetricsRead)?;
+    metrics.set_store_stats(&store.stats(None)?);
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics.render()?))
+}
+
+//Liveness probe: answers as long as the process is serving requests
+#[get("/healthz")]
+async fn healthz() -> impl Responder {
+    web::Json(serde_json::json!({ "status": "ok" }))
+}
+
+//Readiness probe: the store must be reachable and within its capacity
+#[get("/readyz")]
+async fn readyz(store: web::Data<Arc<dyn EventStore>>) -> Result<impl Responder, AppError> {
+    match store.status() {
+        Ok(StoreStatus::Ready) => Ok(web::Json(serde_json::json!({ "status": "ready" }))),
+        Ok(status) => Err(AppError::ServiceUnavailable(format!("Store is {status:?}"))),
+        Err(e) => Err(AppError::ServiceUnavailable(format!(
+            "Store unreachable: {e}"
+        ))),
+    }
+}
+
+#[post("/erasures")]
+async fn post_erasure(
+    principal: Principal,
+    store: web::Data<Arc<dyn EventStore>>,
+    erasure: web::Data<ErasureService>,
+    projections: Option<web::Data<ProjectionRegistry>>,
+    request: web::Json<ErasureRequest>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    //The subject is PII, so only the mode is logged here; the receipt carries a hash of it
+    debug!("Received erasure request, mode {:?}", request.mode);
+    let receipt = erasure.erase(
+        store.get_ref().as_ref(),
+        principal.tenant(),
+        request.into_inner(),
+    )?;
+    //Projections may still hold the erased values; fold them again from what is left
+    if let Some(projections) = projections {
+        if receipt.events_affected > 0 {
+            projections.rebuild(principal.tenant(), store.get_ref().as_ref())?;
+        }
+    }
+    Ok(web::Json(receipt))
+}
+
+#[get("/erasures")]
+async fn get_erasures(
+    principal: Principal,
+    erasure: web::Data<ErasureService>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    Ok(web::Json(erasure.receipts(principal.tenant())?))
+}
+
+#[get("/erasures/{id}")]
+async fn get_erasure_by_id(
+    principal: Principal,
+    erasure: web::Data<ErasureService>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    let id = path.into_inner();
+    match erasure.receipt(principal.tenant(), id)? {
+        Some(receipt) => Ok(web::Json(receipt)),
+        None => Err(AppError::NotFound(format!(
+            "Erasure receipt {id} not found"
+        ))),
+    }
+}
+
+#[get("/admin/redaction")]
+async fn get_redaction_rules(
+    principal: Principal,
+    redactor: web::Data<Redactor>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    Ok(web::Json(redactor.rules()?))
+}
+
+#[put("/admin/redaction")]
+async fn put_redaction_rules(
+    principal: Principal,
+    redactor: web::Data<Redactor>,
+    rules: web::Json<Vec<RedactionRule>>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    let rules = rules.into_inner();
+    redactor.set_rules(rules.clone())?;
+    info!("Replaced redaction rules, {} rule(s) active", rules.len());
+    Ok(web::Json(rules))
+}
+
+#[get("/admin/schemas")]
+async fn get_schemas(
+    principal: Principal,
+    schemas: web::Data<SchemaRegistry>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    Ok(web::Json(schemas.list()?))
+}
+
+#[get("/admin/schemas/{event_type}")]
+async fn get_schema(
+    principal: Principal,
+    schemas: web::Data<SchemaRegistry>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    let event_type = path.into_inner();
+    match schemas.get(&event_type)? {
+        Some(schema) => Ok(web::Json(schema)),
+        None => Err(AppError::NotFound(format!(
+            "No schema registered for event type {event_type}"
+        ))),
+    }
+}
+
+#[put("/admin/schemas/{event_type}")]
+async fn put_schema(
+    principal: Principal,
+    schemas: web::Data<SchemaRegistry>,
+    path: web::Path<String>,
+    schema: web::Json<serde_json::Value>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    let event_type = path.into_inner();
+    let schema = schema.into_inner();
+    schemas.register(&event_type, schema.clone())?;
+    info!("Registered schema for event type {}", event_type);
+    Ok(web::Json(schema))
+}
+
+#[delete("/admin/schemas/{event_type}")]
+async fn delete_schema(
+    principal: Principal,
+    schemas: web::Data<SchemaRegistry>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    let event_type = path.into_inner();
+    if !schemas.remove(&event_type)? {
+        return Err(AppError::NotFound(format!(
+            "No schema registered for event type {event_type}"
+        )));
+    }
+    info!("Removed schema for event type {}", event_type);
+    Ok(web::Json(serde_json::json!({ "deleted": 1 })))
+}
+
+#[get("/projections")]
+async fn get_projections(
+    principal: Principal,
+    projections: web::Data<ProjectionRegistry>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::EventsRead)?;
+    Ok(web::Json(projections.list(principal.tenant())?))
+}
+
+#[get("/projections/{name}")]
+async fn get_projection(
+    principal: Principal,
+    projections: web::Data<ProjectionRegistry>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::EventsRead)?;
+    let name = path.into_inner();
+    match projections.get(principal.tenant(), &name)? {
+        Some(state) => Ok(web::Json(state)),
+        None => Err(AppError::NotFound(format!("Projection {name} not found"))),
+    }
+}
+
+#[put("/projections/{name}")]
+async fn put_projection(
+    principal: Principal,
+    store: web::Data<Arc<dyn EventStore>>,
+    projections: web::Data<ProjectionRegistry>,
+    path: web::Path<String>,
+    definition: web::Json<ProjectionDefinition>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    let name = path.into_inner();
+    let tenant = principal.tenant().to_string();
+    //The backfill scans the tenant's store, so keep it off the async workers
+    let state = {
+        let name = name.clone();
+        web::block(move || {
+            projections.register(
+                &tenant,
+                &name,
+                definition.into_inner(),
+                store.get_ref().as_ref(),
+            )
+        })
+        .await
+        .map_err(|e| AppError::InternalError(e.to_string()))??
+    };
+    info!("Registered projection {}", name);
+    Ok(web::Json(state))
+}
+
+#[delete("/projections/{name}")]
+async fn delete_projection(
+    principal: Principal,
+    projections: web::Data<ProjectionRegistry>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    let name = path.into_inner();
+    if !projections.remove(principal.tenant(), &name)? {
+        return Err(AppError::NotFound(format!("Projection {name} not found")));
+    }
+    info!("Removed projection {}", name);
+    Ok(web::Json(serde_json::json!({ "deleted": 1 })))
+}
+
+#[get("/admin/catalog")]
+async fn get_catalog(
+    principal: Principal,
+    catalog: web::Data<EventTypeCatalog>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    Ok(web::Json(catalog.snapshot()?))
+}
+
+#[put("/admin/catalog")]
+async fn put_catalog(
+    principal: Principal,
+    catalog: web::Data<EventTypeCatalog>,
+    config: web::Json<CatalogConfig>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    let config = config.into_inner();
+    info!(
+        "Replacing event type catalog: strict={}, {} event type(s)",
+        config.strict,
+        config.event_types.len()
+    );
+    catalog.replace(config.clone())?;
+    Ok(web::Json(config))
+}
+
+#[get("/admin/event-types/{event_type}")]
+async fn get_event_type(
+    principal: Principal,
+    catalog: web::Data<EventTypeCatalog>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    let event_type = path.into_inner();
+    match catalog.get(&event_type)? {
+        Some(definition) => Ok(web::Json(definition)),
+        None => Err(AppError::NotFound(format!(
+            "Event type {event_type} is not in the catalog"
+        ))),
+    }
+}
+
+#[put("/admin/event-types/{event_type}")]
+async fn put_event_type(
+    principal: Principal,
+    catalog: web::Data<EventTypeCatalog>,
+    path: web::Path<String>,
+    definition: web::Json<EventTypeDefinition>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    let event_type = path.into_inner();
+    let definition = definition.into_inner();
+    catalog.upsert(&event_type, definition.clone())?;
+    info!("Catalogued event type {}", event_type);
+    Ok(web::Json(definition))
+}
+
+#[delete("/admin/event-types/{event_type}")]
+async fn delete_event_type(
+    principal: Principal,
+    catalog: web::Data<EventTypeCatalog>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    principal.require(Scope::Admin)?;
+    let event_type = path.into_inner();
+    if !catalog.remove(&event_type)? {
+        return Err(AppError::NotFound(format!(
+            "Event type {event_type} is not in the catalog"
+        )));
+    }
+    info!("Removed event type {} from the catalog", event_type);
+    Ok(web::Json(serde_json::json!({ "deleted": 1 })))
+}
@@ -0,0 +1,370 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, FromRequest, HttpMessage, HttpRequest};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::future::{ready, Ready};
+
+use crate::error::AppError;
+use crate::jwt::{looks_like_jwt, JwtVerifier};
+use crate::tenant::{validate_tenant, DEFAULT_TENANT, TENANT_HEADER};
+
+//Alternative to "Authorization: Bearer <key>" for clients that cannot set that header
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Scope {
+    #[serde(rename = "events:write")]
+    EventsWrite,
+    #[serde(rename = "events:read")]
+    EventsRead,
+    //Read-only access to GET /metrics, for Prometheus scrapers
+    #[serde(rename = "metrics:read")]
+    MetricsRead,
+    //Satisfies every other scope
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::EventsWrite => "events:write",
+            Scope::EventsRead => "events:read",
+            Scope::MetricsRead => "metrics:read",
+            Scope::Admin => "admin",
+        }
+    }
+
+    #[must_use]
+    pub fn parse(name: &str) -> Option<Self> {
+        [
+            Scope::EventsWrite,
+            Scope::EventsRead,
+            Scope::MetricsRead,
+            Scope::Admin,
+        ]
+        .into_iter()
+        .find(|scope| scope.as_str() == name)
+    }
+}
+
+//One configured key.  Only the SHA-256 hex digest of the key is kept, never the key itself.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApiKeyEntry {
+    pub id: String,
+    pub key_sha256: String,
+    pub scopes: BTreeSet<Scope>,
+    //Binds the key to one tenant.  Keys without one act on the default tenant; admin keys may
+    //pick another with X-Tenant-Id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+}
+
+//Serializable form of the key set, used for the API_KEYS_FILE config file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ApiKeyConfig {
+    #[serde(default)]
+    pub keys: Vec<ApiKeyEntry>,
+}
+
+//How a principal proved who it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Credential {
+    ApiKey,
+    Jwt,
+}
+
+impl Credential {
+    #[must_use]
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Credential::ApiKey => "key",
+            Credential::Jwt => "jwt",
+        }
+    }
+}
+
+//The caller a request was made by.  When authentication is not configured every request gets
+//an unrestricted principal, so handlers can enforce scopes unconditionally.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principal {
+    //API key id or JWT subject
+    pub subject: Option<String>,
+    pub tenant: Option<String>,
+    credential: Option<Credential>,
+    scopes: Option<BTreeSet<Scope>>,
+}
+
+impl Principal {
+    #[must_use]
+    pub fn unrestricted() -> Self {
+        Self {
+            subject: None,
+            tenant: None,
+            credential: None,
+            scopes: None,
+        }
+    }
+
+    #[must_use]
+    pub fn new(
+        credential: Credential,
+        subject: &str,
+        tenant: Option<String>,
+        scopes: BTreeSet<Scope>,
+    ) -> Self {
+        Self {
+            subject: Some(subject.to_string()),
+            tenant,
+            credential: Some(credential),
+            scopes: Some(scopes),
+        }
+    }
+
+    //Subject prefixed by credential kind ("key:ingest", "jwt:billing-service"), so an API key
+    //and a JWT subject of the same name are never counted as one caller
+    #[must_use]
+    pub fn credential_id(&self) -> Option<String> {
+        let credential = self.credential?;
+        let subject = self.subject.as_deref()?;
+        Some(format!("{}:{}", credential.prefix(), subject))
+    }
+
+    #[must_use]
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.contains(&scope) || scopes.contains(&Scope::Admin))
+    }
+
+    //Tenant the request acts on
+    #[must_use]
+    pub fn tenant(&self) -> &str {
+        self.tenant.as_deref().unwrap_or(DEFAULT_TENANT)
+    }
+
+    pub fn require(&self, scope: Scope) -> Result<(), AppError> {
+        if self.has_scope(scope) {
+            return Ok(());
+        }
+        Err(AppError::Forbidden(format!(
+            "'{}' lacks the '{}' scope",
+            self.subject.as_deref().unwrap_or_default(),
+            scope.as_str()
+        )))
+    }
+}
+
+//Applies the X-Tenant-Id header.  Credentials bound to a tenant may only repeat their own, and
+//only admins (or every request when authentication is off) may pick one otherwise.
+fn with_requested_tenant(
+    mut principal: Principal,
+    req: &HttpRequest,
+) -> Result<Principal, AppError> {
+    let Some(value) = req.headers().get(TENANT_HEADER) else {
+        return Ok(principal);
+    };
+    let requested = value
+        .to_str()
+        .map_err(|_| AppError::BadRequest(format!("Invalid {TENANT_HEADER} header")))?;
+    validate_tenant(requested)?;
+    match &principal.tenant {
+        Some(tenant) if tenant != requested => Err(AppError::Forbidden(format!(
+            "'{}' cannot act on tenant '{}'",
+            principal.subject.as_deref().unwrap_or_default(),
+            requested
+        ))),
+        None if !principal.has_scope(Scope::Admin) => Err(AppError::Forbidden(format!(
+            "'{}' is not bound to a tenant and may not choose one",
+            principal.subject.as_deref().unwrap_or_default()
+        ))),
+        _ => {
+            principal.tenant = Some(requested.to_string());
+            Ok(principal)
+        }
+    }
+}
+
+//Resolved by the authenticate middleware.  A request without credentials only reaches a
+//handler without a principal when auth is configured, and is then rejected with a 401.
+impl FromRequest for Principal {
+    type Error = AppError;
+    type Future = Ready<Result<Self, AppError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let principal = match req.extensions().get::<Principal>() {
+            Some(principal) => Ok(principal.clone()),
+            None if !auth_configured(req) => Ok(Principal::unrestricted()),
+            None => Err(AppError::Unauthorized(
+                "An API key or bearer token is required".to_string(),
+            )),
+        };
+        ready(principal.and_then(|principal| with_requested_tenant(principal, req)))
+    }
+}
+
+pub(crate) fn auth_configured(req: &HttpRequest) -> bool {
+    req.app_data::<web::Data<ApiKeys>>().is_some()
+        || req.app_data::<web::Data<JwtVerifier>>().is_some()
+}
+
+#[must_use]
+pub fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+pub struct ApiKeys {
+    by_hash: HashMap<String, ApiKeyEntry>,
+}
+
+impl ApiKeys {
+    #[must_use]
+    pub fn from_config(config: ApiKeyConfig) -> Self {
+        Self {
+            by_hash: config
+                .keys
+                .into_iter()
+                .map(|entry| (entry.key_sha256.to_lowercase(), entry))
+                .collect(),
+        }
+    }
+
+    //Loads keys from a JSON file in the ApiKeyConfig shape
+    pub fn load(path: &str) -> Result<Self, AppError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| AppError::InternalError(format!("Failed to read {path}: {e}")))?;
+        let config: ApiKeyConfig = serde_json::from_str(&contents)
+            .map_err(|e| AppError::BadRequest(format!("Invalid API keys in {path}: {e}")))?;
+        for tenant in config
+            .keys
+            .iter()
+            .filter_map(|entry| entry.tenant.as_deref())
+        {
+            validate_tenant(tenant)?;
+        }
+        Ok(Self::from_config(config))
+    }
+
+    #[must_use]
+    pub fn authenticate(&self, key: &str) -> Option<Principal> {
+        self.by_hash.get(&hash_key(key)).map(|entry| {
+            Principal::new(
+                Credential::ApiKey,
+                &entry.id,
+                entry.tenant.clone(),
+                entry.scopes.clone(),
+            )
+        })
+    }
+}
+
+//Credentials from "Authorization: Bearer <key or JWT>" or the X-API-Key header
+fn presented_key(req: &ServiceRequest) -> Option<&str> {
+    let headers = req.headers();
+    headers
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| {
+            headers
+                .get(API_KEY_HEADER)
+                .and_then(|value| value.to_str().ok())
+        })
+        .map(str::trim)
+}
+
+//Resolves the Principal for a presented credential: bearer JWTs go to the JwtVerifier when one
+//is configured, anything else is looked up as an API key
+fn resolve(req: &ServiceRequest, credential: &str) -> Result<Principal, AppError> {
+    if looks_like_jwt(credential) {
+        if let Some(verifier) = req.app_data::<web::Data<JwtVerifier>>() {
+            return verifier.verify(credential);
+        }
+    }
+    req.app_data::<web::Data<ApiKeys>>()
+        .and_then(|keys| keys.authenticate(credential))
+        .ok_or_else(|| AppError::Unauthorized("Invalid API key".to_string()))
+}
+
+//Middleware resolving the request's Principal from its API key or JWT.  Invalid credentials are
+//rejected here; missing ones are left to the handlers so unauthenticated routes such as the
+//probes still work.
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if auth_configured(req.request()) {
+        if let Some(credential) = presented_key(&req) {
+            match resolve(&req, credential) {
+                Ok(principal) => {
+                    debug!("Authenticated request as {:?}", principal.subject);
+                    req.extensions_mut().insert(principal);
+                }
+                Err(e) => {
+                    warn!("Rejected request credentials: {}", e);
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn keys() -> ApiKeys {
+        let config: ApiKeyConfig = serde_json::from_value(json!({
+            "keys": [
+                { "id": "ingest", "key_sha256": hash_key("ingest-secret"), "scopes": ["events:write"] },
+                { "id": "ops", "key_sha256": hash_key("ops-secret").to_uppercase(), "scopes": ["admin"] },
+                { "id": "scraper", "key_sha256": hash_key("scraper-secret"), "scopes": ["metrics:read"] }
+            ]
+        }))
+        .unwrap();
+        ApiKeys::from_config(config)
+    }
+
+    #[test]
+    fn test_authenticate_by_hash() {
+        let keys = keys();
+        let ingest = keys.authenticate("ingest-secret").unwrap();
+        assert_eq!(ingest.subject.as_deref(), Some("ingest"));
+        assert!(ingest.has_scope(Scope::EventsWrite));
+        assert!(!ingest.has_scope(Scope::EventsRead));
+        assert!(keys.authenticate("ops-secret").is_some());
+        assert!(keys.authenticate("guess").is_none());
+    }
+
+    #[test]
+    fn test_require_scopes() {
+        let keys = keys();
+        let ingest = keys.authenticate("ingest-secret").unwrap();
+        assert!(matches!(
+            ingest.require(Scope::Admin),
+            Err(AppError::Forbidden(_))
+        ));
+        let ops = keys.authenticate("ops-secret").unwrap();
+        assert!(ops.require(Scope::EventsRead).is_ok());
+        assert!(Principal::unrestricted().require(Scope::Admin).is_ok());
+    }
+
+    #[test]
+    fn test_metrics_scope_grants_nothing_else() {
+        let keys = keys();
+        let scraper = keys.authenticate("scraper-secret").unwrap();
+        assert!(scraper.require(Scope::MetricsRead).is_ok());
+        assert!(scraper.require(Scope::EventsRead).is_err());
+        assert!(scraper.require(Scope::Admin).is_err());
+        let ops = keys.authenticate("ops-secret").unwrap();
+        assert!(ops.require(Scope::MetricsRead).is_ok());
+        assert_eq!(Scope::parse("metrics:read"), Some(Scope::MetricsRead));
+    }
+}
@@ -0,0 +1,206 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use crate::error::AppError;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EventTypeStatus {
+    #[default]
+    Active,
+    //Still accepted, but producers should migrate away
+    Deprecated,
+    //Rejected at ingest regardless of strict mode
+    Disabled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct EventTypeDefinition {
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub status: EventTypeStatus,
+}
+
+//Serializable form of the catalog, used for config files and the admin API
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct CatalogConfig {
+    #[serde(default)]
+    pub strict: bool,
+    #[serde(default)]
+    pub event_types: BTreeMap<String, EventTypeDefinition>,
+}
+
+//Known event types.  In strict mode, event types missing from the catalog are rejected at ingest;
+//otherwise the catalog is informational apart from Disabled entries.
+#[derive(Default)]
+pub struct EventTypeCatalog {
+    strict: AtomicBool,
+    event_types: RwLock<BTreeMap<String, EventTypeDefinition>>,
+}
+
+impl EventTypeCatalog {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn from_config(config: CatalogConfig) -> Self {
+        Self {
+            strict: AtomicBool::new(config.strict),
+            event_types: RwLock::new(config.event_types),
+        }
+    }
+
+    //Loads a catalog from a JSON file in the CatalogConfig shape
+    pub fn load(path: &str) -> Result<Self, AppError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| AppError::InternalError(format!("Failed to read {path}: {e}")))?;
+        let config: CatalogConfig = serde_json::from_str(&contents)
+            .map_err(|e| AppError::BadRequest(format!("Invalid catalog in {path}: {e}")))?;
+        Ok(Self::from_config(config))
+    }
+
+    pub fn snapshot(&self) -> Result<CatalogConfig, AppError> {
+        let event_types = self
+            .event_types
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(CatalogConfig {
+            strict: self.is_strict(),
+            event_types: event_types.clone(),
+        })
+    }
+
+    pub fn replace(&self, config: CatalogConfig) -> Result<(), AppError> {
+        let mut event_types = self
+            .event_types
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        *event_types = config.event_types;
+        self.strict.store(config.strict, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict.load(Ordering::Relaxed)
+    }
+
+    pub fn get(&self, event_type: &str) -> Result<Option<EventTypeDefinition>, AppError> {
+        let event_types = self
+            .event_types
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(event_types.get(event_type).cloned())
+    }
+
+    pub fn upsert(
+        &self,
+        event_type: &str,
+        definition: EventTypeDefinition,
+    ) -> Result<(), AppError> {
+        let mut event_types = self
+            .event_types
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        event_types.insert(event_type.to_string(), definition);
+        Ok(())
+    }
+
+    pub fn remove(&self, event_type: &str) -> Result<bool, AppError> {
+        let mut event_types = self
+            .event_types
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(event_types.remove(event_type).is_some())
+    }
+
+    //Decides whether an event of this type may be ingested
+    pub fn check(&self, event_type: &str) -> Result<(), AppError> {
+        match self.get(event_type)? {
+            Some(definition) => match definition.status {
+                EventTypeStatus::Active => Ok(()),
+                EventTypeStatus::Deprecated => {
+                    warn!("Ingesting deprecated event type {}", event_type);
+                    Ok(())
+                }
+                EventTypeStatus::Disabled => Err(AppError::BadRequest(format!(
+                    "Event type '{event_type}' is disabled"
+                ))),
+            },
+            None if self.is_strict() => Err(AppError::BadRequest(format!(
+                "Unknown event type '{event_type}'"
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn catalog(strict: bool) -> EventTypeCatalog {
+        let config: CatalogConfig = serde_json::from_value(json!({
+            "strict": strict,
+            "event_types": {
+                "login": { "description": "User signed in", "owner": "identity" },
+                "legacy_login": { "status": "deprecated" },
+                "debug": { "status": "disabled" }
+            }
+        }))
+        .unwrap();
+        EventTypeCatalog::from_config(config)
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_unknown_types() {
+        let catalog = catalog(true);
+        assert!(catalog.check("login").is_ok());
+        assert!(catalog.check("legacy_login").is_ok());
+        assert!(matches!(
+            catalog.check("signup"),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_lenient_mode_accepts_unknown_but_not_disabled() {
+        let catalog = catalog(false);
+        assert!(catalog.check("signup").is_ok());
+        assert!(matches!(
+            catalog.check("debug"),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_upsert_remove_and_snapshot() {
+        let catalog = EventTypeCatalog::new();
+        catalog
+            .upsert(
+                "login",
+                EventTypeDefinition {
+                    owner: Some("identity".into()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let snapshot = catalog.snapshot().unwrap();
+        assert!(!snapshot.strict);
+        assert_eq!(
+            snapshot.event_types["login"].owner.as_deref(),
+            Some("identity")
+        );
+
+        assert!(catalog.remove("login").unwrap());
+        assert!(catalog.get("login").unwrap().is_none());
+    }
+}
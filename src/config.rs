@@ -0,0 +1,553 @@
+use actix_web::http::Method;
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::error::AppError;
+use crate::rate_limit::{RateLimitConfig, RateLimitKey, OVERRIDE_PREFIXES};
+use crate::skew::ClockSkewAction;
+
+//Read when neither --config nor EVENT_TRACKER_CONFIG names a file, if it exists
+pub const DEFAULT_CONFIG_FILE: &str = "event-tracker.toml";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreBackend {
+    #[default]
+    Memory,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_address: String,
+    //log4rs configuration file
+    pub log_config: String,
+    //How often the config file and the files it names are checked for changes; 0 only reloads
+    //on SIGHUP
+    pub watch_interval_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: "127.0.0.1:8080".to_string(),
+            log_config: "log4rs.yml".to_string(),
+            watch_interval_secs: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoreConfig {
+    pub backend: StoreBackend,
+    //Memory budget shared by all tenants, reported by /readyz and enforced on writes with a 507;
+    //unset is unbounded
+    pub capacity_bytes: Option<usize>,
+    //Per-tenant quotas and retention (see TenantConfig)
+    pub tenants_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub api_keys_file: Option<String>,
+    pub jwks_file: Option<String>,
+    pub jwt_issuer: Option<String>,
+    pub jwt_audience: Option<String>,
+    pub jwt_tenant_claim: String,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            api_keys_file: None,
+            jwks_file: None,
+            jwt_issuer: None,
+            jwt_audience: None,
+            jwt_tenant_claim: "tenant".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct IngestConfig {
+    pub idempotency_ttl_secs: u64,
+    //Content dedup is off unless set
+    pub dedup_window_secs: Option<u64>,
+    pub clock_skew_max_future_secs: Option<i64>,
+    pub clock_skew_max_past_secs: Option<i64>,
+    pub clock_skew_action: ClockSkewAction,
+    pub event_catalog_file: Option<String>,
+    //JSON object of event_type -> JSON Schema
+    pub schemas_file: Option<String>,
+    //JSON array of redaction rules; nothing is redacted when unset
+    pub redaction_rules_file: Option<String>,
+    pub usage_quotas_file: Option<String>,
+    //Payload fields that identify a data subject for erasures
+    pub erasure_subject_paths: Vec<String>,
+    //Key for the subject hashes in erasure receipts; random per process when unset
+    pub erasure_hmac_secret: Option<String>,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            idempotency_ttl_secs: 24 * 60 * 60,
+            dedup_window_secs: None,
+            clock_skew_max_future_secs: None,
+            clock_skew_max_past_secs: None,
+            clock_skew_action: ClockSkewAction::Reject,
+            event_catalog_file: None,
+            schemas_file: None,
+            redaction_rules_file: None,
+            usage_quotas_file: None,
+            erasure_subject_paths: vec!["user_id".to_string(), "email".to_string()],
+            erasure_hmac_secret: None,
+        }
+    }
+}
+
+//Service settings.  Built from defaults, then the TOML config file, then environment variables,
+//then command-line flags, each layer overriding the ones before it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub store: StoreConfig,
+    pub auth: AuthConfig,
+    pub rate_limit: RateLimitConfig,
+    pub ingest: IngestConfig,
+}
+
+//Command-line flags; every flag overrides the config file and the environment
+#[derive(Debug, Default, Parser)]
+#[command(
+    name = "event-tracker",
+    version,
+    about = "Event ingestion and query service"
+)]
+pub struct Cli {
+    #[arg(
+        long,
+        short = 'c',
+        help = "TOML config file [default: event-tracker.toml, if present]"
+    )]
+    pub config: Option<String>,
+    #[arg(long, help = "Address to listen on, host:port")]
+    pub bind_address: Option<String>,
+    #[arg(long, help = "log4rs configuration file")]
+    pub log_config: Option<String>,
+    #[arg(long, value_enum, help = "Event store implementation")]
+    pub store: Option<StoreBackend>,
+    #[arg(long, help = "Memory budget of the event store")]
+    pub store_capacity_bytes: Option<usize>,
+    #[arg(long, value_enum, help = "What rate-limit buckets are shared by")]
+    pub rate_limit_key: Option<RateLimitKey>,
+    #[arg(long, help = "Milliseconds to replenish one read request")]
+    pub rate_limit_read_period_ms: Option<u64>,
+    #[arg(long, help = "Read requests allowed in a burst")]
+    pub rate_limit_read_burst: Option<u32>,
+    #[arg(long, help = "Milliseconds to replenish one write request")]
+    pub rate_limit_write_period_ms: Option<u64>,
+    #[arg(long, help = "Write requests allowed in a burst")]
+    pub rate_limit_write_burst: Option<u32>,
+    #[arg(long, help = "Redis-compatible server for shared rate-limit buckets")]
+    pub rate_limit_redis_url: Option<String>,
+}
+
+fn invalid_env(name: &str, value: &str, e: impl std::fmt::Display) -> AppError {
+    AppError::BadRequest(format!("Invalid {name} '{value}': {e}"))
+}
+
+fn parse_env<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, AppError>
+where
+    T::Err: std::fmt::Display,
+{
+    value.parse().map_err(|e| invalid_env(name, value, e))
+}
+
+//Parses a snake_case enum value the way the config file spells it
+fn parse_enum<T: serde::de::DeserializeOwned>(name: &str, value: &str) -> Result<T, AppError> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|e| invalid_env(name, value, e))
+}
+
+impl Config {
+    //Reads the TOML file at `path`, or returns the defaults without one
+    pub fn from_file(path: Option<&str>) -> Result<Self, AppError> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| AppError::BadRequest(format!("Failed to read config file {path}: {e}")))?;
+        toml::from_str(&contents)
+            .map_err(|e| AppError::BadRequest(format!("Invalid config file {path}: {e}")))
+    }
+
+    //The config file in effect: --config, EVENT_TRACKER_CONFIG, or DEFAULT_CONFIG_FILE if present
+    #[must_use]
+    pub fn path(cli: &Cli) -> Option<String> {
+        cli.config
+            .clone()
+            .or_else(|| std::env::var("EVENT_TRACKER_CONFIG").ok())
+            .or_else(|| {
+                Path::new(DEFAULT_CONFIG_FILE)
+                    .exists()
+                    .then(|| DEFAULT_CONFIG_FILE.to_string())
+            })
+    }
+
+    //Full configuration: file, then process environment, then flags, then validation.  Used at
+    //startup and again on every reload.
+    pub fn load(cli: &Cli) -> Result<Self, AppError> {
+        let mut config = Self::from_file(Self::path(cli).as_deref())?;
+        config.apply_env(|name| std::env::var(name).ok())?;
+        config.apply_cli(cli);
+        config.validate()?;
+        Ok(config)
+    }
+
+    //Applies the environment variables the service has always read, looked up through `var`
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), AppError> {
+        let string = |name: &str, target: &mut String| {
+            if let Some(value) = var(name) {
+                *target = value;
+            }
+        };
+        let optional = |name: &str, target: &mut Option<String>| {
+            if let Some(value) = var(name) {
+                *target = Some(value);
+            }
+        };
+        string("BIND_ADDRESS", &mut self.server.bind_address);
+        string("LOG_CONFIG", &mut self.server.log_config);
+        optional("TENANTS_FILE", &mut self.store.tenants_file);
+        optional("API_KEYS_FILE", &mut self.auth.api_keys_file);
+        optional("JWKS_FILE", &mut self.auth.jwks_file);
+        optional("JWT_ISSUER", &mut self.auth.jwt_issuer);
+        optional("JWT_AUDIENCE", &mut self.auth.jwt_audience);
+        string("JWT_TENANT_CLAIM", &mut self.auth.jwt_tenant_claim);
+        optional("RATE_LIMIT_REDIS_URL", &mut self.rate_limit.redis_url);
+        optional("EVENT_CATALOG_FILE", &mut self.ingest.event_catalog_file);
+        optional("SCHEMAS_FILE", &mut self.ingest.schemas_file);
+        optional(
+            "REDACTION_RULES_FILE",
+            &mut self.ingest.redaction_rules_file,
+        );
+        optional("USAGE_QUOTAS_FILE", &mut self.ingest.usage_quotas_file);
+
+        if let Some(value) = var("CONFIG_WATCH_INTERVAL_SECS") {
+            self.server.watch_interval_secs = parse_env("CONFIG_WATCH_INTERVAL_SECS", &value)?;
+        }
+        if let Some(value) = var("STORE_BACKEND") {
+            self.store.backend = parse_enum("STORE_BACKEND", &value)?;
+        }
+        if let Some(value) = var("STORE_CAPACITY_BYTES") {
+            self.store.capacity_bytes = Some(parse_env("STORE_CAPACITY_BYTES", &value)?);
+        }
+        if let Some(value) = var("RATE_LIMIT_KEY") {
+            self.rate_limit.key = parse_enum("RATE_LIMIT_KEY", &value)?;
+        }
+        for (prefix, limit) in [
+            ("RATE_LIMIT_READ", &mut self.rate_limit.reads),
+            ("RATE_LIMIT_WRITE", &mut self.rate_limit.writes),
+        ] {
+            let name = format!("{prefix}_PERIOD_MS");
+            if let Some(value) = var(&name) {
+                limit.period_ms = parse_env(&name, &value)?;
+            }
+            let name = format!("{prefix}_BURST");
+            if let Some(value) = var(&name) {
+                limit.burst = parse_env(&name, &value)?;
+            }
+        }
+        if let Some(value) = var("IDEMPOTENCY_TTL_SECS") {
+            self.ingest.idempotency_ttl_secs = parse_env("IDEMPOTENCY_TTL_SECS", &value)?;
+        }
+        if let Some(value) = var("DEDUP_WINDOW_SECS") {
+            self.ingest.dedup_window_secs = Some(parse_env("DEDUP_WINDOW_SECS", &value)?);
+        }
+        if let Some(value) = var("CLOCK_SKEW_MAX_FUTURE_SECS") {
+            self.ingest.clock_skew_max_future_secs =
+                Some(parse_env("CLOCK_SKEW_MAX_FUTURE_SECS", &value)?);
+        }
+        if let Some(value) = var("CLOCK_SKEW_MAX_PAST_SECS") {
+            self.ingest.clock_skew_max_past_secs =
+                Some(parse_env("CLOCK_SKEW_MAX_PAST_SECS", &value)?);
+        }
+        if let Some(value) = var("CLOCK_SKEW_ACTION") {
+            self.ingest.clock_skew_action = parse_enum("CLOCK_SKEW_ACTION", &value)?;
+        }
+        //Comma separated
+        if let Some(value) = var("ERASURE_SUBJECT_PATHS") {
+            self.ingest.erasure_subject_paths = value
+                .split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect();
+        }
+        if let Some(value) = var("ERASURE_HMAC_SECRET") {
+            self.ingest.erasure_hmac_secret = Some(value);
+        }
+        Ok(())
+    }
+
+    pub fn apply_cli(&mut self, cli: &Cli) {
+        if let Some(bind_address) = &cli.bind_address {
+            self.server.bind_address.clone_from(bind_address);
+        }
+        if let Some(log_config) = &cli.log_config {
+            self.server.log_config.clone_from(log_config);
+        }
+        if let Some(store) = cli.store {
+            self.store.backend = store;
+        }
+        if let Some(capacity) = cli.store_capacity_bytes {
+            self.store.capacity_bytes = Some(capacity);
+        }
+        if let Some(key) = cli.rate_limit_key {
+            self.rate_limit.key = key;
+        }
+        if let Some(period_ms) = cli.rate_limit_read_period_ms {
+            self.rate_limit.reads.period_ms = period_ms;
+        }
+        if let Some(burst) = cli.rate_limit_read_burst {
+            self.rate_limit.reads.burst = burst;
+        }
+        if let Some(period_ms) = cli.rate_limit_write_period_ms {
+            self.rate_limit.writes.period_ms = period_ms;
+        }
+        if let Some(burst) = cli.rate_limit_write_burst {
+            self.rate_limit.writes.burst = burst;
+        }
+        if let Some(url) = &cli.rate_limit_redis_url {
+            self.rate_limit.redis_url = Some(url.clone());
+        }
+    }
+
+    //Checks everything that can be checked before the service starts, reporting every problem
+    //at once rather than one per restart
+    pub fn validate(&self) -> Result<(), AppError> {
+        let mut problems = Vec::new();
+
+        let valid_address = self
+            .server
+            .bind_address
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+        if !valid_address {
+            problems.push(format!(
+                "server.bind_address '{}' must be host:port",
+                self.server.bind_address
+            ));
+        }
+        let mut limits = vec![
+            ("rate_limit.reads".to_string(), self.rate_limit.reads),
+            ("rate_limit.writes".to_string(), self.rate_limit.writes),
+        ];
+        for (i, rule) in self.rate_limit.routes.iter().enumerate() {
+            if !rule.path.starts_with('/') {
+                problems.push(format!("rate_limit.routes[{i}].path must start with '/'"));
+            }
+            if let Some(method) = &rule.method {
+                if Method::from_bytes(method.as_bytes()).is_err() {
+                    problems.push(format!(
+                        "rate_limit.routes[{i}].method '{method}' is not an HTTP method"
+                    ));
+                }
+            }
+            limits.push((format!("rate_limit.routes[{i}].limit"), rule.limit));
+        }
+        for (name, client) in &self.rate_limit.overrides {
+            if !OVERRIDE_PREFIXES
+                .iter()
+                .any(|prefix| name.len() > prefix.len() && name.starts_with(prefix))
+            {
+                problems.push(format!(
+                    "rate_limit.overrides: '{name}' must be key:<id>, jwt:<sub>, tenant:<id> or ip:<addr>"
+                ));
+            }
+            for (class, limit) in [("reads", client.reads), ("writes", client.writes)] {
+                if let Some(limit) = limit {
+                    limits.push((format!("rate_limit.overrides.\"{name}\".{class}"), limit));
+                }
+            }
+        }
+        for (name, limit) in limits {
+            if limit.period_ms == 0 || limit.burst == 0 {
+                problems.push(format!(
+                    "{name}: period_ms and burst must be greater than zero"
+                ));
+            }
+        }
+        if cfg!(not(feature = "redis")) && self.rate_limit.redis_url.is_some() {
+            problems.push("rate_limit.redis_url needs a build with the redis feature".to_string());
+        }
+        if self.auth.jwt_tenant_claim.is_empty() {
+            problems.push("auth.jwt_tenant_claim must not be empty".to_string());
+        }
+        if self.auth.jwks_file.is_none()
+            && (self.auth.jwt_issuer.is_some() || self.auth.jwt_audience.is_some())
+        {
+            problems.push("auth.jwt_issuer and auth.jwt_audience need auth.jwks_file".to_string());
+        }
+        if self.ingest.idempotency_ttl_secs == 0 {
+            problems.push("ingest.idempotency_ttl_secs must be greater than zero".to_string());
+        }
+        if self.ingest.dedup_window_secs == Some(0) {
+            problems.push("ingest.dedup_window_secs must be greater than zero".to_string());
+        }
+
+        let files = [
+            ("server.log_config", Some(&self.server.log_config)),
+            ("store.tenants_file", self.store.tenants_file.as_ref()),
+            ("auth.api_keys_file", self.auth.api_keys_file.as_ref()),
+            ("auth.jwks_file", self.auth.jwks_file.as_ref()),
+            (
+                "ingest.event_catalog_file",
+                self.ingest.event_catalog_file.as_ref(),
+            ),
+            ("ingest.schemas_file", self.ingest.schemas_file.as_ref()),
+            (
+                "ingest.redaction_rules_file",
+                self.ingest.redaction_rules_file.as_ref(),
+            ),
+            (
+                "ingest.usage_quotas_file",
+                self.ingest.usage_quotas_file.as_ref(),
+            ),
+        ];
+        for (name, path) in files {
+            if let Some(path) = path {
+                if !Path::new(path).is_file() {
+                    problems.push(format!("{name}: {path} does not exist"));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::BadRequest(format!(
+                "Invalid configuration: {}",
+                problems.join("; ")
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limit::{ClientLimits, RouteLimit};
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [server]
+            bind_address = "0.0.0.0:9000"
+
+            [rate_limit]
+            key = "tenant"
+            reads = { period_ms = 1000, burst = 50 }
+            writes = { burst = 5 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.rate_limit.writes.period_ms, 5000);
+
+        config
+            .apply_env(env(&[
+                ("BIND_ADDRESS", "0.0.0.0:9001"),
+                ("RATE_LIMIT_READ_BURST", "60"),
+            ]))
+            .unwrap();
+        let cli = Cli::try_parse_from(["event-tracker", "--rate-limit-read-burst", "70"]).unwrap();
+        config.apply_cli(&cli);
+
+        assert_eq!(config.server.bind_address, "0.0.0.0:9001");
+        assert_eq!(config.rate_limit.key, RateLimitKey::Tenant);
+        assert_eq!(config.rate_limit.reads.period_ms, 1000);
+        assert_eq!(config.rate_limit.reads.burst, 70);
+        assert_eq!(config.server.log_config, "log4rs.yml");
+    }
+
+    #[test]
+    fn test_invalid_settings_are_reported_together() {
+        assert!(toml::from_str::<Config>("[server]\nbind = \"x\"").is_err());
+
+        let mut config = Config::default();
+        assert!(matches!(
+            config.apply_env(env(&[("RATE_LIMIT_KEY", "user")])),
+            Err(AppError::BadRequest(_))
+        ));
+
+        config.server.bind_address = "localhost".to_string();
+        config.rate_limit.writes.burst = 0;
+        config.auth.api_keys_file = Some("/nonexistent/keys.json".to_string());
+        let Err(AppError::BadRequest(message)) = config.validate() else {
+            panic!("expected validation to fail");
+        };
+        assert!(message.contains("server.bind_address"));
+        assert!(message.contains("rate_limit.writes"));
+        assert!(message.contains("auth.api_keys_file"));
+    }
+
+    #[test]
+    fn test_rate_limit_routes_and_overrides() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [[rate_limit.routes]]
+            path = "/admin/projections/{name}"
+            method = "PUT"
+            limit = { period_ms = 60000, burst = 2 }
+
+            [rate_limit.overrides."tenant:team-a"]
+            writes = { period_ms = 100, burst = 100 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.rate_limit.routes[0].limit.burst, 2);
+        assert_eq!(
+            config.rate_limit.overrides["tenant:team-a"].writes,
+            Some(RouteLimit {
+                period_ms: 100,
+                burst: 100
+            })
+        );
+        assert!(config.validate().is_ok());
+
+        config.rate_limit.routes[0].path = "admin".to_string();
+        config.rate_limit.routes[0].method = Some("P UT".to_string());
+        config.rate_limit.overrides.insert(
+            "team-b".to_string(),
+            ClientLimits {
+                reads: Some(RouteLimit {
+                    period_ms: 0,
+                    burst: 1,
+                }),
+                writes: None,
+            },
+        );
+        let Err(AppError::BadRequest(message)) = config.validate() else {
+            panic!("expected validation to fail");
+        };
+        assert!(message.contains("rate_limit.routes[0].path"));
+        assert!(message.contains("rate_limit.routes[0].method"));
+        assert!(message.contains("'team-b' must be"));
+        assert!(message.contains(r#"rate_limit.overrides."team-b".reads"#));
+    }
+}
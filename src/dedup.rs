@@ -0,0 +1,329 @@
+use chrono::{DateTime, Utc};
+use log::debug;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::model::Event;
+
+struct Seen {
+    event_id: Uuid,
+    expires: Instant,
+    //False while the request that reserved the entry is still storing its event
+    stored: bool,
+}
+
+#[derive(Default)]
+struct SeenEvents {
+    by_hash: HashMap<[u8; 32], Seen>,
+    //Hashes in the order they were recorded; the window is fixed so this is also expiry order
+    expiries: VecDeque<(Instant, [u8; 32])>,
+}
+
+impl SeenEvents {
+    fn purge_expired(&mut self, now: Instant) {
+        while self
+            .expiries
+            .front()
+            .is_some_and(|(expires, _)| *expires <= now)
+        {
+            if let Some((expires, hash)) = self.expiries.pop_front() {
+                if self
+                    .by_hash
+                    .get(&hash)
+                    .is_some_and(|s| s.expires == expires)
+                {
+                    self.by_hash.remove(&hash);
+                }
+            }
+        }
+    }
+
+    fn reserve(&mut self, hash: [u8; 32], event_id: Uuid, expires: Instant) -> Reservation {
+        self.by_hash.insert(
+            hash,
+            Seen {
+                event_id,
+                expires,
+                stored: false,
+            },
+        );
+        self.expiries.push_back((expires, hash));
+        Reservation { hash, event_id }
+    }
+}
+
+//Outcome of observing an event against the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Observation {
+    //No identical event within the window; the caller stores the event, then confirms or
+    //releases the reservation
+    New(Reservation),
+    //An identical event was stored as this id
+    Duplicate(Uuid),
+    //An identical event is still being stored by another request
+    Pending(Uuid),
+}
+
+//Claim on a content hash held while its event is being stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reservation {
+    hash: [u8; 32],
+    event_id: Uuid,
+}
+
+//Content-based deduplication for producers that replay whole batches without idempotency keys.
+//Events with the same event_type, client timestamp and payload seen within `window` of each
+//other are treated as one; the window starts when the first copy is stored.  Events sent without
+//a timestamp are compared as such, not by the time they happened to arrive.
+pub struct DedupWindow {
+    window: Duration,
+    seen: Mutex<SeenEvents>,
+    dropped: AtomicU64,
+}
+
+impl DedupWindow {
+    #[must_use]
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: Mutex::new(SeenEvents::default()),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    fn content_hash(
+        tenant: &str,
+        event: &Event,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> Result<[u8; 32], AppError> {
+        let content = serde_json::to_vec(&(tenant, &event.event_type, timestamp, &event.payload))
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(Sha256::digest(content).into())
+    }
+
+    //Looks for an identical event from the tenant within the window and, if there is none,
+    //reserves the content for `event` in the same step so concurrent copies cannot both be
+    //stored.  `timestamp` is the one the client sent, if any.
+    pub fn observe(
+        &self,
+        tenant: &str,
+        event: &Event,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> Result<Observation, AppError> {
+        let hash = Self::content_hash(tenant, event, timestamp)?;
+        let mut seen = self
+            .seen
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let now = Instant::now();
+        seen.purge_expired(now);
+
+        Ok(match seen.by_hash.get(&hash) {
+            Some(original) if original.stored => Observation::Duplicate(original.event_id),
+            Some(original) => Observation::Pending(original.event_id),
+            None => Observation::New(seen.reserve(hash, event.id, now + self.window)),
+        })
+    }
+
+    //Reserves the content for `event` in place of `original`, a stored copy that has since been
+    //deleted.  Returns None if another request got there first.
+    pub fn take_over(
+        &self,
+        tenant: &str,
+        event: &Event,
+        timestamp: Option<DateTime<Utc>>,
+        original: Uuid,
+    ) -> Result<Option<Reservation>, AppError> {
+        let hash = Self::content_hash(tenant, event, timestamp)?;
+        let mut seen = self
+            .seen
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let now = Instant::now();
+        seen.purge_expired(now);
+
+        match seen.by_hash.get(&hash) {
+            Some(current) if current.event_id != original || !current.stored => Ok(None),
+            _ => Ok(Some(seen.reserve(hash, event.id, now + self.window))),
+        }
+    }
+
+    //Marks the reserved event as stored; the window starts now
+    pub fn confirm(&self, reservation: Reservation) -> Result<(), AppError> {
+        let mut seen = self
+            .seen
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let expires = Instant::now() + self.window;
+        let Some(entry) = seen.by_hash.get_mut(&reservation.hash) else {
+            return Ok(());
+        };
+        if entry.event_id != reservation.event_id {
+            return Ok(());
+        }
+        entry.stored = true;
+        entry.expires = expires;
+        seen.expiries.push_back((expires, reservation.hash));
+        Ok(())
+    }
+
+    //Forgets a reservation whose event could not be stored, so a later copy is stored instead
+    pub fn release(&self, reservation: Reservation) -> Result<(), AppError> {
+        let mut seen = self
+            .seen
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        if seen
+            .by_hash
+            .get(&reservation.hash)
+            .is_some_and(|s| s.event_id == reservation.event_id && !s.stored)
+        {
+            seen.by_hash.remove(&reservation.hash);
+        }
+        Ok(())
+    }
+
+    pub fn record_dropped(&self, event: &Event, original: Uuid) {
+        debug!(
+            "Dropped event {} of type {} as a duplicate of {}",
+            event.id, event.event_type, original
+        );
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    //Duplicates dropped since startup
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::NewEvent;
+    use chrono::{TimeZone, Utc};
+    use serde_json::{json, Value};
+
+    fn event(event_type: &str, payload: Value) -> Event {
+        NewEvent {
+            id: None,
+            event_type: event_type.into(),
+            timestamp: Some(Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap()),
+            payload,
+            schema_version: 1,
+            metadata: Default::default(),
+        }
+        .into_event()
+    }
+
+    //Observes `event` with its client timestamp and confirms it when it is new
+    fn store(dedup: &DedupWindow, tenant: &str, event: &Event) -> Observation {
+        let observation = dedup.observe(tenant, event, Some(event.timestamp)).unwrap();
+        if let Observation::New(reservation) = observation {
+            dedup.confirm(reservation).unwrap();
+        }
+        observation
+    }
+
+    fn is_new(observation: Observation) -> bool {
+        matches!(observation, Observation::New(_))
+    }
+
+    #[test]
+    fn test_identical_content_within_window_is_duplicate() {
+        let dedup = DedupWindow::new(Duration::from_secs(60));
+        let first = event("purchase", json!({ "order_id": 7 }));
+        let replay = event("purchase", json!({ "order_id": 7 }));
+
+        assert!(is_new(store(&dedup, "t", &first)));
+        assert_eq!(
+            store(&dedup, "t", &replay),
+            Observation::Duplicate(first.id)
+        );
+        assert!(is_new(store(
+            &dedup,
+            "t",
+            &event("purchase", json!({ "order_id": 8 }))
+        )));
+        assert!(is_new(store(
+            &dedup,
+            "t",
+            &event("refund", json!({ "order_id": 7 }))
+        )));
+        assert!(is_new(store(&dedup, "u", &replay)));
+    }
+
+    #[test]
+    fn test_concurrent_copies_wait_for_the_reservation() {
+        let dedup = DedupWindow::new(Duration::from_secs(60));
+        let first = event("purchase", json!({ "order_id": 7 }));
+        let replay = event("purchase", json!({ "order_id": 7 }));
+
+        let Observation::New(reservation) = dedup.observe("t", &first, None).unwrap() else {
+            panic!("first copy should be new");
+        };
+        assert_eq!(
+            dedup.observe("t", &replay, None).unwrap(),
+            Observation::Pending(first.id)
+        );
+
+        //The first write failed, so the next copy is stored instead
+        dedup.release(reservation).unwrap();
+        assert!(is_new(dedup.observe("t", &replay, None).unwrap()));
+    }
+
+    #[test]
+    fn test_events_without_timestamp_are_compared_by_content() {
+        let dedup = DedupWindow::new(Duration::from_secs(60));
+        let first = event("purchase", json!({ "order_id": 7 }));
+        let mut retry = event("purchase", json!({ "order_id": 7 }));
+        //The server defaults the timestamp to the arrival time, which differs on every retry
+        retry.timestamp = Utc::now();
+
+        let Observation::New(reservation) = dedup.observe("t", &first, None).unwrap() else {
+            panic!("first copy should be new");
+        };
+        dedup.confirm(reservation).unwrap();
+        assert_eq!(
+            dedup.observe("t", &retry, None).unwrap(),
+            Observation::Duplicate(first.id)
+        );
+        assert!(is_new(
+            dedup.observe("t", &retry, Some(retry.timestamp)).unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_entries_expire_and_can_be_taken_over() {
+        let dedup = DedupWindow::new(Duration::ZERO);
+        let first = event("purchase", json!({ "order_id": 7 }));
+        assert!(is_new(store(&dedup, "t", &first)));
+        assert!(is_new(store(&dedup, "t", &first)));
+
+        let dedup = DedupWindow::new(Duration::from_secs(60));
+        let second = event("purchase", json!({ "order_id": 7 }));
+        let third = event("purchase", json!({ "order_id": 7 }));
+        store(&dedup, "t", &first);
+        let reservation = dedup
+            .take_over("t", &second, Some(second.timestamp), first.id)
+            .unwrap()
+            .unwrap();
+        //Only one request may replace a deleted original
+        assert_eq!(
+            dedup
+                .take_over("t", &third, Some(third.timestamp), first.id)
+                .unwrap(),
+            None
+        );
+        dedup.confirm(reservation).unwrap();
+        assert_eq!(
+            store(&dedup, "t", &first),
+            Observation::Duplicate(second.id)
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::model::EventQuery;
+use crate::payload::{lookup, lookup_mut, value_matches};
+use crate::storage::EventStore;
+
+//Replacement written over identifying values when anonymizing
+pub const ERASED_MARKER: &str = "[erased]";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ErasureMode {
+    //Remove every event that references the subject
+    #[default]
+    Delete,
+    //Keep the events but overwrite the referencing fields with ERASED_MARKER
+    Anonymize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ErasureRequest {
+    pub subject: String,
+    #[serde(default)]
+    pub mode: ErasureMode,
+    //Overrides the configured subject paths for this request
+    pub paths: Option<Vec<String>>,
+}
+
+//Audit record of a completed erasure.  Only an HMAC-SHA256 of the subject, keyed with a
+//server-side secret, is kept so the receipt itself does not retain the identifier that was
+//erased and cannot be reversed by hashing a dictionary of likely emails or ids.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ErasureReceipt {
+    pub id: Uuid,
+    pub tenant: String,
+    pub subject_hash: String,
+    pub mode: ErasureMode,
+    pub paths: Vec<String>,
+    pub requested_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
+    pub events_affected: usize,
+    pub event_ids: Vec<Uuid>,
+}
+
+pub struct ErasureService {
+    subject_paths: Vec<String>,
+    //Key for the receipts' subject hashes
+    secret: Vec<u8>,
+    receipts: RwLock<HashMap<Uuid, ErasureReceipt>>,
+}
+
+impl ErasureService {
+    #[must_use]
+    pub fn new(subject_paths: Vec<String>, secret: &[u8]) -> Self {
+        Self {
+            subject_paths,
+            secret: secret.to_vec(),
+            receipts: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn subject_paths(&self) -> &[String] {
+        &self.subject_paths
+    }
+
+    pub fn erase(
+        &self,
+        store: &dyn EventStore,
+        tenant: &str,
+        request: ErasureRequest,
+    ) -> Result<ErasureReceipt, AppError> {
+        let requested_at = Utc::now();
+        let paths = request.paths.unwrap_or_else(|| self.subject_paths.clone());
+        if request.subject.is_empty() {
+            return Err(AppError::BadRequest(
+                "subject must not be empty".to_string(),
+            ));
+        }
+        if paths.is_empty() {
+            return Err(AppError::BadRequest(
+                "At least one subject path is required".to_string(),
+            ));
+        }
+
+        let mut event_ids = Vec::new();
+        for mut event in store.query_events(tenant, EventQuery::default())? {
+            let matched: Vec<&String> = paths
+                .iter()
+                .filter(|path| {
+                    lookup(&event.payload, path)
+                        .is_some_and(|value| value_matches(value, &request.subject))
+                })
+                .collect();
+            //The metadata envelope (actor and friends) is searched too, whatever the paths
+            let in_metadata = event
+                .metadata
+                .fields()
+                .any(|(_, value)| value == request.subject);
+            if matched.is_empty() && !in_metadata {
+                continue;
+            }
+
+            match request.mode {
+                ErasureMode::Delete => {
+                    store.delete(tenant, event.id)?;
+                }
+                ErasureMode::Anonymize => {
+                    for path in matched {
+                        if let Some(value) = lookup_mut(&mut event.payload, path) {
+                            *value = Value::String(ERASED_MARKER.to_string());
+                        }
+                    }
+                    for value in event.metadata.values_mut() {
+                        if value.as_deref() == Some(request.subject.as_str()) {
+                            *value = Some(ERASED_MARKER.to_string());
+                        }
+                    }
+                    store.rewrite_event(tenant, event.clone())?;
+                }
+            }
+            event_ids.push(event.id);
+        }
+
+        let receipt = ErasureReceipt {
+            id: Uuid::new_v4(),
+            tenant: tenant.to_string(),
+            subject_hash: hash_subject(&self.secret, &request.subject),
+            mode: request.mode,
+            paths,
+            requested_at,
+            completed_at: Utc::now(),
+            events_affected: event_ids.len(),
+            event_ids,
+        };
+        info!(
+            "Erasure {} ({:?}) affected {} event(s) of tenant {}",
+            receipt.id, receipt.mode, receipt.events_affected, receipt.tenant
+        );
+
+        self.receipts
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))?
+            .insert(receipt.id, receipt.clone());
+        Ok(receipt)
+    }
+
+    pub fn receipt(&self, tenant: &str, id: Uuid) -> Result<Option<ErasureReceipt>, AppError> {
+        let receipts = self
+            .receipts
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(receipts.get(&id).filter(|r| r.tenant == tenant).cloned())
+    }
+
+    pub fn receipts(&self, tenant: &str) -> Result<Vec<ErasureReceipt>, AppError> {
+        let receipts = self
+            .receipts
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let mut all: Vec<ErasureReceipt> = receipts
+            .values()
+            .filter(|r| r.tenant == tenant)
+            .cloned()
+            .collect();
+        all.sort_by_key(|r| r.requested_at);
+        Ok(all)
+    }
+}
+
+#[must_use]
+pub fn hash_subject(secret: &[u8], subject: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any size");
+    mac.update(subject.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Event, EventMetadata};
+    use crate::storage::InMemoryEventStore;
+    use crate::tenant::DEFAULT_TENANT;
+    use serde_json::json;
+
+    fn add(store: &InMemoryEventStore, payload: Value) -> Uuid {
+        add_with_metadata(store, payload, EventMetadata::default())
+    }
+
+    fn add_with_metadata(
+        store: &InMemoryEventStore,
+        payload: Value,
+        metadata: EventMetadata,
+    ) -> Uuid {
+        let event = Event {
+            id: Uuid::new_v4(),
+            event_type: "test".into(),
+            timestamp: Utc::now(),
+            payload,
+            schema_version: 1,
+            metadata,
+            received_at: Utc::now(),
+            clock_skewed: false,
+            stream: None,
+        };
+        let id = event.id;
+        store.add_event(DEFAULT_TENANT, event).unwrap();
+        id
+    }
+
+    const SECRET: &[u8] = b"receipt-secret";
+
+    #[test]
+    fn test_subject_hash_depends_on_secret() {
+        let hash = hash_subject(SECRET, "jo@example.com");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_subject(SECRET, "jo@example.com"));
+        assert_ne!(hash, hash_subject(b"other-secret", "jo@example.com"));
+        //Not the bare SHA-256 a dictionary attack would try
+        assert_ne!(
+            hash,
+            "f4e19df2e6c609fbd59a42b9063d0fadf44260218531ea21ad8c575f205c0453"
+        );
+    }
+
+    #[test]
+    fn test_erase_deletes_matching_events() {
+        let store = InMemoryEventStore::new();
+        let service = ErasureService::new(vec!["user_id".into(), "contact.email".into()], SECRET);
+        let by_id = add(&store, json!({ "user_id": 42 }));
+        let by_email = add(
+            &store,
+            json!({ "contact": { "email": "a@example.com" }, "user_id": 42 }),
+        );
+        let other = add(&store, json!({ "user_id": 7 }));
+
+        let receipt = service
+            .erase(
+                &store,
+                DEFAULT_TENANT,
+                ErasureRequest {
+                    subject: "42".into(),
+                    mode: ErasureMode::Delete,
+                    paths: None,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(receipt.events_affected, 2);
+        assert!(receipt.event_ids.contains(&by_id));
+        assert!(receipt.event_ids.contains(&by_email));
+        assert!(store.get_by_id(DEFAULT_TENANT, by_id).unwrap().is_none());
+        assert!(store.get_by_id(DEFAULT_TENANT, other).unwrap().is_some());
+        assert_eq!(receipt.subject_hash, hash_subject(SECRET, "42"));
+        assert_eq!(service.receipt("other", receipt.id).unwrap(), None);
+        assert_eq!(
+            service.receipt(DEFAULT_TENANT, receipt.id).unwrap(),
+            Some(receipt)
+        );
+    }
+
+    #[test]
+    fn test_erase_anonymizes_only_matching_fields() {
+        let store = InMemoryEventStore::new();
+        let service = ErasureService::new(vec!["email".into()], SECRET);
+        let id = add(&store, json!({ "email": "a@example.com", "plan": "pro" }));
+
+        let receipt = service
+            .erase(
+                &store,
+                DEFAULT_TENANT,
+                ErasureRequest {
+                    subject: "a@example.com".into(),
+                    mode: ErasureMode::Anonymize,
+                    paths: None,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(receipt.events_affected, 1);
+        let event = store.get_by_id(DEFAULT_TENANT, id).unwrap().unwrap();
+        assert_eq!(
+            event.payload,
+            json!({ "email": ERASED_MARKER, "plan": "pro" })
+        );
+        assert!(!serde_json::to_string(&receipt)
+            .unwrap()
+            .contains("a@example.com"));
+    }
+
+    #[test]
+    fn test_anonymize_is_not_blocked_by_byte_quota() {
+        let store = InMemoryEventStore::new();
+        let service = ErasureService::new(vec!["user_id".into()], SECRET);
+        let ids = [
+            add(&store, json!({ "user_id": 7 })),
+            add(&store, json!({ "user_id": 7 })),
+        ];
+        //The tenant is exactly at its quota, and "[erased]" is longer than 7
+        let used = store.stats(Some(DEFAULT_TENANT)).unwrap().memory_bytes;
+        store
+            .set_tenant_limits(
+                serde_json::from_value(json!({ "default": { "max_bytes": used } })).unwrap(),
+            )
+            .unwrap();
+
+        let receipt = service
+            .erase(
+                &store,
+                DEFAULT_TENANT,
+                ErasureRequest {
+                    subject: "7".into(),
+                    mode: ErasureMode::Anonymize,
+                    paths: None,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(receipt.events_affected, 2);
+        for id in ids {
+            let event = store.get_by_id(DEFAULT_TENANT, id).unwrap().unwrap();
+            assert_eq!(event.payload, json!({ "user_id": ERASED_MARKER }));
+        }
+    }
+
+    #[test]
+    fn test_erase_matches_metadata_fields() {
+        let store = InMemoryEventStore::new();
+        let service = ErasureService::new(vec!["user_id".into()], SECRET);
+        let actor = |value: &str| EventMetadata {
+            actor: Some(value.into()),
+            source: Some("checkout".into()),
+            ..Default::default()
+        };
+        let acted = add_with_metadata(&store, json!({ "plan": "pro" }), actor("user-42"));
+        let other = add_with_metadata(&store, json!({ "plan": "pro" }), actor("user-7"));
+
+        let receipt = service
+            .erase(
+                &store,
+                DEFAULT_TENANT,
+                ErasureRequest {
+                    subject: "user-42".into(),
+                    mode: ErasureMode::Anonymize,
+                    paths: None,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(receipt.event_ids, vec![acted]);
+        let event = store.get_by_id(DEFAULT_TENANT, acted).unwrap().unwrap();
+        assert_eq!(event.metadata.actor.as_deref(), Some(ERASED_MARKER));
+        assert_eq!(event.metadata.source.as_deref(), Some("checkout"));
+        //The metadata index no longer finds the subject
+        let query = EventQuery {
+            metadata: actor("user-42"),
+            ..Default::default()
+        };
+        assert!(store
+            .query_events(DEFAULT_TENANT, query)
+            .unwrap()
+            .is_empty());
+        assert!(store.get_by_id(DEFAULT_TENANT, other).unwrap().is_some());
+    }
+}
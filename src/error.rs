@@ -1,4 +1,6 @@
+use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
+use chrono::Utc;
 use log::{error, warn};
 use thiserror::Error;
 
@@ -12,9 +14,73 @@ pub enum AppError {
     NotFound(String),
     #[error("Unexpected error: {0}")]
     Unexpected(String),
+    #[error("Service unavailable: {0}")]
+    ServiceUnavailable(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Insufficient storage: {0}")]
+    InsufficientStorage(String),
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+}
+
+impl AppError {
+    //Variant name, used as a metrics label
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::InternalError(_) => "InternalError",
+            AppError::BadRequest(_) => "BadRequest",
+            AppError::NotFound(_) => "NotFound",
+            AppError::Unexpected(_) => "Unexpected",
+            AppError::ServiceUnavailable(_) => "ServiceUnavailable",
+            AppError::Conflict(_) => "Conflict",
+            AppError::Unauthorized(_) => "Unauthorized",
+            AppError::Forbidden(_) => "Forbidden",
+            AppError::InsufficientStorage(_) => "InsufficientStorage",
+            AppError::TooManyRequests(_) => "TooManyRequests",
+            AppError::QuotaExceeded(_) => "QuotaExceeded",
+        }
+    }
+}
+
+//Daily quotas reset at midnight UTC
+fn seconds_until_utc_midnight() -> i64 {
+    let now = Utc::now();
+    let midnight = now
+        .date_naive()
+        .succ_opt()
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .map(|midnight| midnight.and_utc());
+    midnight.map_or(0, |midnight| (midnight - now).num_seconds().max(1))
 }
 
 impl ResponseError for AppError {
+    //Errors returned from middleware are reported with this status before error_response runs
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::InsufficientStorage(_) => StatusCode::INSUFFICIENT_STORAGE,
+            AppError::TooManyRequests(_) | AppError::QuotaExceeded(_) => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            AppError::InternalError(_) | AppError::Unexpected(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
     fn error_response(&self) -> HttpResponse {
         match self {
             AppError::BadRequest(msg) => {
@@ -32,6 +98,43 @@ impl ResponseError for AppError {
                 HttpResponse::InternalServerError()
                     .json(serde_json::json!({ "error": "Unexpected error", "message": msg }))
             }
+            AppError::ServiceUnavailable(msg) => {
+                warn!("Service unavailable: {}", msg);
+                HttpResponse::ServiceUnavailable()
+                    .json(serde_json::json!({ "error": "Service unavailable", "message": msg }))
+            }
+            AppError::Conflict(msg) => {
+                warn!("Conflict: {}", msg);
+                HttpResponse::Conflict()
+                    .json(serde_json::json!({ "error": "Conflict", "message": msg }))
+            }
+            AppError::Unauthorized(msg) => {
+                warn!("Unauthorized: {}", msg);
+                HttpResponse::Unauthorized()
+                    .insert_header(("WWW-Authenticate", "Bearer"))
+                    .json(serde_json::json!({ "error": "Unauthorized", "message": msg }))
+            }
+            AppError::Forbidden(msg) => {
+                warn!("Forbidden: {}", msg);
+                HttpResponse::Forbidden()
+                    .json(serde_json::json!({ "error": "Forbidden", "message": msg }))
+            }
+            AppError::InsufficientStorage(msg) => {
+                warn!("Insufficient storage: {}", msg);
+                HttpResponse::InsufficientStorage()
+                    .json(serde_json::json!({ "error": "Insufficient storage", "message": msg }))
+            }
+            AppError::TooManyRequests(msg) => {
+                warn!("Too many requests: {}", msg);
+                HttpResponse::TooManyRequests()
+                    .json(serde_json::json!({ "error": "Too many requests", "message": msg }))
+            }
+            AppError::QuotaExceeded(msg) => {
+                warn!("Quota exceeded: {}", msg);
+                HttpResponse::TooManyRequests()
+                    .insert_header(("Retry-After", seconds_until_utc_midnight().to_string()))
+                    .json(serde_json::json!({ "error": "Quota exceeded", "message": msg }))
+            }
             AppError::InternalError(msg) => {
                 error!("Internal server error: {}", msg);
                 HttpResponse::InternalServerError()
@@ -0,0 +1,178 @@
+use log::debug;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::error::AppError;
+
+//Header producers set to make a POST /events retry-safe
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+//Set on responses that return a previously stored event instead of storing a new one
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+//Outcome of claiming an idempotency key for a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Claim {
+    //First time the key is seen; the caller should store the event
+    New,
+    //The key was already used for an identical request that produced this event
+    Replay(Uuid),
+}
+
+struct Entry {
+    fingerprint: [u8; 32],
+    event_id: Uuid,
+    expires: Instant,
+}
+
+//Keys are scoped to a tenant, so tenants can pick the same keys independently
+type TenantKey = (String, String);
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<TenantKey, Entry>,
+    //Keys in insertion order with their expiry; the TTL is fixed so this is also expiry order
+    expiries: VecDeque<(Instant, TenantKey)>,
+}
+
+//Remembers which event each idempotency key produced, for `ttl` after first use.  Requests are
+//identified by a SHA-256 fingerprint of their body so a reused key with different content is
+//rejected rather than silently replayed.
+pub struct IdempotencyCache {
+    ttl: Duration,
+    entries: Mutex<Entries>,
+}
+
+impl IdempotencyCache {
+    #[must_use]
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    #[must_use]
+    pub fn fingerprint(body: &[u8]) -> [u8; 32] {
+        Sha256::digest(body).into()
+    }
+
+    //Atomically records `key -> event_id` unless the tenant already used the key
+    pub fn claim(
+        &self,
+        tenant: &str,
+        key: &str,
+        fingerprint: [u8; 32],
+        event_id: Uuid,
+    ) -> Result<Claim, AppError> {
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let now = Instant::now();
+        while entries
+            .expiries
+            .front()
+            .is_some_and(|(expires, _)| *expires <= now)
+        {
+            //A released and re-claimed key has a newer entry that must survive the stale expiry
+            if let Some((expires, stale)) = entries.expiries.pop_front() {
+                if entries
+                    .by_key
+                    .get(&stale)
+                    .is_some_and(|e| e.expires == expires)
+                {
+                    entries.by_key.remove(&stale);
+                }
+            }
+        }
+
+        let tenant_key = (tenant.to_string(), key.to_string());
+        if let Some(entry) = entries.by_key.get(&tenant_key) {
+            if entry.fingerprint != fingerprint {
+                return Err(AppError::Conflict(format!(
+                    "Idempotency key '{key}' was already used with a different request"
+                )));
+            }
+            debug!(
+                "Replaying idempotency key {} -> event {}",
+                key, entry.event_id
+            );
+            return Ok(Claim::Replay(entry.event_id));
+        }
+
+        let expires = now + self.ttl;
+        entries.by_key.insert(
+            tenant_key.clone(),
+            Entry {
+                fingerprint,
+                event_id,
+                expires,
+            },
+        );
+        entries.expiries.push_back((expires, tenant_key));
+        Ok(Claim::New)
+    }
+
+    //Forgets a claim whose event could not be stored, so the producer can retry
+    pub fn release(&self, tenant: &str, key: &str) -> Result<(), AppError> {
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        entries
+            .by_key
+            .remove(&(tenant.to_string(), key.to_string()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claim_replay_and_conflict() {
+        let cache = IdempotencyCache::new(Duration::from_secs(60));
+        let body = IdempotencyCache::fingerprint(b"{\"event_type\":\"login\"}");
+        let other = IdempotencyCache::fingerprint(b"{\"event_type\":\"logout\"}");
+        let id = Uuid::new_v4();
+
+        assert_eq!(cache.claim("t", "k1", body, id).unwrap(), Claim::New);
+        assert_eq!(
+            cache.claim("t", "k1", body, Uuid::new_v4()).unwrap(),
+            Claim::Replay(id)
+        );
+        assert!(matches!(
+            cache.claim("t", "k1", other, Uuid::new_v4()),
+            Err(AppError::Conflict(_))
+        ));
+        assert_eq!(cache.claim("t", "k2", other, id).unwrap(), Claim::New);
+        //Another tenant may use the same key for a different request
+        assert_eq!(cache.claim("u", "k1", other, id).unwrap(), Claim::New);
+    }
+
+    #[test]
+    fn test_keys_expire_and_release() {
+        let cache = IdempotencyCache::new(Duration::ZERO);
+        let body = IdempotencyCache::fingerprint(b"{}");
+        assert_eq!(
+            cache.claim("t", "k1", body, Uuid::new_v4()).unwrap(),
+            Claim::New
+        );
+        assert_eq!(
+            cache.claim("t", "k1", body, Uuid::new_v4()).unwrap(),
+            Claim::New
+        );
+
+        let cache = IdempotencyCache::new(Duration::from_secs(60));
+        cache.claim("t", "k1", body, Uuid::new_v4()).unwrap();
+        cache.release("t", "k1").unwrap();
+        assert_eq!(
+            cache.claim("t", "k1", body, Uuid::new_v4()).unwrap(),
+            Claim::New
+        );
+    }
+}
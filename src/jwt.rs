@@ -0,0 +1,329 @@
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use log::{info, warn};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use crate::auth::{Credential, Principal, Scope};
+use crate::error::AppError;
+use crate::tenant::validate_tenant;
+
+pub struct JwtConfig {
+    //JWKS document ({"keys": [...]}) with the issuer's verification keys
+    pub jwks_file: String,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    //Claim holding the caller's tenant
+    pub tenant_claim: String,
+}
+
+struct LoadedKeys {
+    jwks: JwkSet,
+    modified: Option<SystemTime>,
+}
+
+//Verifies bearer JWTs against a JWKS file.  The file is reloaded when its modification time
+//changes, so keys can be rotated without a restart; a broken rewrite keeps the previous keys.
+pub struct JwtVerifier {
+    config: JwtConfig,
+    keys: RwLock<LoadedKeys>,
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn read_jwks(path: &str) -> Result<JwkSet, AppError> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| AppError::InternalError(format!("Failed to read {path}: {e}")))?;
+    serde_json::from_str(&contents)
+        .map_err(|e| AppError::BadRequest(format!("Invalid JWKS in {path}: {e}")))
+}
+
+//JWTs are three base64url segments, the first a decodable JOSE header.  Anything else presented
+//as a bearer token, including API keys that happen to contain two dots, is an API key.
+#[must_use]
+pub fn looks_like_jwt(token: &str) -> bool {
+    token.split('.').count() == 3 && decode_header(token).is_ok()
+}
+
+impl JwtVerifier {
+    pub fn new(config: JwtConfig) -> Result<Self, AppError> {
+        let jwks = read_jwks(&config.jwks_file)?;
+        let keys = LoadedKeys {
+            jwks,
+            modified: modified(&config.jwks_file),
+        };
+        Ok(Self {
+            config,
+            keys: RwLock::new(keys),
+        })
+    }
+
+    fn refresh(&self) -> Result<(), AppError> {
+        let current = modified(&self.config.jwks_file);
+        {
+            let keys = self
+                .keys
+                .read()
+                .map_err(|e| AppError::InternalError(e.to_string()))?;
+            if keys.modified == current {
+                return Ok(());
+            }
+        }
+        let mut keys = self
+            .keys
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        match read_jwks(&self.config.jwks_file) {
+            Ok(jwks) => {
+                info!(
+                    "Reloaded {} JWKS key(s) from {}",
+                    jwks.keys.len(),
+                    self.config.jwks_file
+                );
+                keys.jwks = jwks;
+            }
+            Err(e) => warn!("Keeping previous JWKS: {}", e),
+        }
+        keys.modified = current;
+        Ok(())
+    }
+
+    fn decoding_key(&self, kid: Option<&str>, alg: Algorithm) -> Result<DecodingKey, AppError> {
+        let keys = self
+            .keys
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let jwk = match kid {
+            Some(kid) => keys.jwks.find(kid),
+            //Tokens without a kid are only accepted when the set has a single key
+            None if keys.jwks.keys.len() == 1 => keys.jwks.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| AppError::Unauthorized("No matching signing key for token".to_string()))?;
+
+        //A key that declares its algorithm may only be used with that algorithm
+        if let Some(key_alg) = jwk.common.key_algorithm {
+            if Algorithm::from_str(&key_alg.to_string()).ok() != Some(alg) {
+                return Err(AppError::Unauthorized(
+                    "Token algorithm does not match its signing key".to_string(),
+                ));
+            }
+        }
+        DecodingKey::from_jwk(jwk)
+            .map_err(|e| AppError::Unauthorized(format!("Unusable signing key: {e}")))
+    }
+
+    pub fn verify(&self, token: &str) -> Result<Principal, AppError> {
+        self.refresh()?;
+        let header = decode_header(token)
+            .map_err(|e| AppError::Unauthorized(format!("Malformed token: {e}")))?;
+        let key = self.decoding_key(header.kid.as_deref(), header.alg)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        //Off by default in jsonwebtoken: tokens are not accepted before their nbf
+        validation.validate_nbf = true;
+        if let Some(issuer) = &self.config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        let claims = decode::<Value>(token, &key, &validation)
+            .map_err(|e| AppError::Unauthorized(format!("Invalid token: {e}")))?
+            .claims;
+
+        //Usage and rate limits are counted per subject, so every token must name one
+        let subject = claims["sub"]
+            .as_str()
+            .filter(|subject| !subject.is_empty())
+            .ok_or_else(|| AppError::Unauthorized("Token has no subject".to_string()))?;
+        let tenant = claims[self.config.tenant_claim.as_str()]
+            .as_str()
+            .map(str::to_string);
+        if let Some(tenant) = &tenant {
+            validate_tenant(tenant).map_err(|e| AppError::Unauthorized(e.to_string()))?;
+        }
+        Ok(Principal::new(
+            Credential::Jwt,
+            subject,
+            tenant,
+            scopes_from_claims(&claims),
+        ))
+    }
+}
+
+//Scopes from the OAuth "scope" claim (space separated) or a "scopes"/"scp" array.  Values that
+//are not event-tracker scopes are ignored.
+fn scopes_from_claims(claims: &Value) -> BTreeSet<Scope> {
+    let mut names: Vec<&str> = claims["scope"]
+        .as_str()
+        .map(|s| s.split_whitespace().collect())
+        .unwrap_or_default();
+    for claim in ["scopes", "scp"] {
+        if let Some(values) = claims[claim].as_array() {
+            names.extend(values.iter().filter_map(Value::as_str));
+        }
+    }
+    names.into_iter().filter_map(Scope::parse).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    //Base64 secrets (alphanumeric so the standard and URL-safe alphabets agree)
+    const SECRET: &str = "c2VjcmV0c2VjcmV0c2VjcmV0c2VjcmV0";
+    const OTHER_SECRET: &str = "b3RoZXJvdGhlcm90aGVyb3RoZXJvdGhl";
+
+    fn write_jwks(kid: &str, secret: &str) -> String {
+        let path = std::env::temp_dir().join(format!("jwks-{}.json", uuid::Uuid::new_v4()));
+        let jwks = json!({ "keys": [{ "kty": "oct", "kid": kid, "alg": "HS256", "k": secret }] });
+        std::fs::write(&path, jwks.to_string()).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn token(kid: &str, secret: &str, claims: Value) -> String {
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(kid.to_string());
+        encode(
+            &header,
+            &claims,
+            &EncodingKey::from_base64_secret(secret).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn verifier(path: &str) -> JwtVerifier {
+        JwtVerifier::new(JwtConfig {
+            jwks_file: path.to_string(),
+            issuer: Some("platform".into()),
+            audience: None,
+            tenant_claim: "tenant".into(),
+        })
+        .unwrap()
+    }
+
+    fn claims() -> Value {
+        json!({
+            "sub": "billing-service",
+            "iss": "platform",
+            "exp": chrono::Utc::now().timestamp() + 300,
+            "tenant": "acme",
+            "scope": "events:read events:write openid"
+        })
+    }
+
+    #[test]
+    fn test_verify_maps_claims_to_principal() {
+        let path = write_jwks("k1", SECRET);
+        let principal = verifier(&path)
+            .verify(&token("k1", SECRET, claims()))
+            .unwrap();
+
+        assert_eq!(principal.subject.as_deref(), Some("billing-service"));
+        assert_eq!(principal.tenant.as_deref(), Some("acme"));
+        assert!(principal.has_scope(Scope::EventsWrite));
+        assert!(!principal.has_scope(Scope::Admin));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_verify_rejects_bad_signature_issuer_and_expiry() {
+        let path = write_jwks("k1", SECRET);
+        let verifier = verifier(&path);
+
+        let forged = token("k1", OTHER_SECRET, claims());
+        assert!(matches!(
+            verifier.verify(&forged),
+            Err(AppError::Unauthorized(_))
+        ));
+
+        let mut wrong_issuer = claims();
+        wrong_issuer["iss"] = json!("elsewhere");
+        let wrong_issuer = token("k1", SECRET, wrong_issuer);
+        assert!(matches!(
+            verifier.verify(&wrong_issuer),
+            Err(AppError::Unauthorized(_))
+        ));
+
+        let mut expired = claims();
+        expired["exp"] = json!(chrono::Utc::now().timestamp() - 3600);
+        let expired = token("k1", SECRET, expired);
+        assert!(matches!(
+            verifier.verify(&expired),
+            Err(AppError::Unauthorized(_))
+        ));
+
+        let unknown_kid = token("k2", SECRET, claims());
+        assert!(matches!(
+            verifier.verify(&unknown_kid),
+            Err(AppError::Unauthorized(_))
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_verify_requires_a_subject() {
+        let path = write_jwks("k1", SECRET);
+        let verifier = verifier(&path);
+        let mut anonymous = claims();
+        anonymous["sub"] = json!("");
+        assert!(matches!(
+            verifier.verify(&token("k1", SECRET, anonymous)),
+            Err(AppError::Unauthorized(_))
+        ));
+
+        let principal = verifier.verify(&token("k1", SECRET, claims())).unwrap();
+        assert_eq!(
+            principal.credential_id().as_deref(),
+            Some("jwt:billing-service")
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_verify_rejects_token_before_nbf() {
+        let path = write_jwks("k1", SECRET);
+        let mut early = claims();
+        early["nbf"] = json!(chrono::Utc::now().timestamp() + 3600);
+        assert!(matches!(
+            verifier(&path).verify(&token("k1", SECRET, early)),
+            Err(AppError::Unauthorized(_))
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_looks_like_jwt_needs_a_header() {
+        assert!(looks_like_jwt(&token("k1", SECRET, claims())));
+        assert!(!looks_like_jwt("svc.prod.0123456789abcdef"));
+        assert!(!looks_like_jwt("plain-api-key"));
+    }
+
+    #[test]
+    fn test_jwks_reloads_when_file_changes() {
+        let path = write_jwks("k1", SECRET);
+        let verifier = verifier(&path);
+        let rotated = token("k2", OTHER_SECRET, claims());
+        assert!(verifier.verify(&rotated).is_err());
+
+        let replacement = write_jwks("k2", OTHER_SECRET);
+        std::fs::rename(&replacement, &path).unwrap();
+        //Make sure the modification time differs even on coarse-grained filesystems
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(5))
+            .unwrap();
+
+        assert!(verifier.verify(&rotated).is_ok());
+        std::fs::remove_file(path).unwrap();
+    }
+}
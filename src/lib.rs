@@ -1,4 +1,24 @@
 pub mod api;
+pub mod auth;
+pub mod catalog;
+pub mod config;
+pub mod dedup;
+pub mod erasure;
 pub mod error;
+pub mod idempotency;
+pub mod jwt;
+pub mod metrics;
 pub mod model;
+pub mod payload;
+pub mod projection;
+pub mod rate_limit;
+pub mod redaction;
+#[cfg(feature = "redis")]
+pub mod redis_limiter;
+pub mod reload;
+pub mod schema;
+pub mod skew;
 pub mod storage;
+pub mod tenant;
+pub mod upcast;
+pub mod usage;
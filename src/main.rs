@@ -1,41 +1,316 @@
-use log::{error, info};
+use clap::Parser;
+use log::{error, info, warn};
 use std::sync::Arc;
 
-use actix_governor::{Governor, GovernorConfigBuilder};
+use actix_web::middleware::from_fn;
 use actix_web::{web, App, HttpServer};
 
-use event_tracker::api::{get_event_by_id, get_events, post_event};
+use event_tracker::api::{
+    append_stream_events, delete_event, delete_event_type, delete_events, delete_projection,
+    delete_schema, get_catalog, get_erasure_by_id, get_erasures, get_event_by_id, get_event_type,
+    get_events, get_metrics, get_projection, get_projections, get_redaction_rules, get_schema,
+    get_schemas, get_stats, get_stream, get_usage, healthz, post_erasure, post_event, put_catalog,
+    put_event_type, put_projection, put_redaction_rules, put_schema, readyz,
+};
+use event_tracker::auth::{authenticate, ApiKeys};
+use event_tracker::catalog::EventTypeCatalog;
+use event_tracker::config::{Cli, Config, StoreBackend};
+use event_tracker::dedup::DedupWindow;
+use event_tracker::erasure::ErasureService;
+use event_tracker::idempotency::IdempotencyCache;
+use event_tracker::jwt::{JwtConfig, JwtVerifier};
+use event_tracker::metrics::{track_requests, Metrics};
+use event_tracker::projection::ProjectionRegistry;
+use event_tracker::rate_limit::{limit_requests, RequestLimiter};
+use event_tracker::redaction::Redactor;
+#[cfg(unix)]
+use event_tracker::reload::reload_on_sighup;
+use event_tracker::reload::{watch_files, ConfigReloader, ReloadTargets};
+use event_tracker::schema::SchemaRegistry;
+use event_tracker::skew::ClockSkewPolicy;
 use event_tracker::storage::{EventStore, InMemoryEventStore};
+use event_tracker::tenant::{enforce_retention, TenantConfig};
+use event_tracker::upcast::UpcasterRegistry;
+use event_tracker::usage::{UsageConfig, UsageTracker};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    log4rs::init_file("log4rs.yml", log4rs::config::Deserializers::default()).unwrap_or_else(|e| {
-        error!("Failed to initialize log4rs: {}", e);
+    //Logging is configured by the config, so problems loading it can only go to stderr
+    let cli = Cli::parse();
+    let config = Config::load(&cli).unwrap_or_else(|e| {
+        eprintln!("Failed to load configuration: {e}");
+        std::process::exit(2)
+    });
+    //The handle lets a reload swap in new log levels
+    let logger = log4rs::config::load_config_file(
+        &config.server.log_config,
+        log4rs::config::Deserializers::default(),
+    )
+    .map_err(|e| e.to_string())
+    .and_then(|log_config| log4rs::init_config(log_config).map_err(|e| e.to_string()))
+    .unwrap_or_else(|e| {
+        eprintln!("Failed to initialize log4rs: {e}");
         std::process::exit(1)
     });
     info!("Starting server...");
 
-    let host = std::env::var("BIND_ADDRESS").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
-    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let host = config.server.bind_address.clone();
+    //Per-tenant quotas and retention; without a file every tenant is unlimited
+    let tenant_config = match &config.store.tenants_file {
+        Some(path) => TenantConfig::load(path).unwrap_or_else(|e| {
+            error!("Failed to load tenant config: {}", e);
+            std::process::exit(12)
+        }),
+        None => TenantConfig::default(),
+    };
+    let in_memory_store = match config.store.backend {
+        StoreBackend::Memory => {
+            let mut in_memory_store = InMemoryEventStore::new().with_tenant_limits(tenant_config);
+            if let Some(capacity) = config.store.capacity_bytes {
+                in_memory_store = in_memory_store.with_capacity_bytes(capacity);
+            }
+            Arc::new(in_memory_store)
+        }
+    };
+    let store: Arc<dyn EventStore> = in_memory_store.clone();
     let store_data: web::Data<Arc<dyn EventStore>> = web::Data::new(store.clone());
+    //Retention is re-read every tick, so a reload can turn it on or off
+    {
+        let in_memory_store = in_memory_store.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                let purged = in_memory_store.tenant_limits().and_then(|tenant_config| {
+                    if !tenant_config.has_retention() {
+                        return Ok(0);
+                    }
+                    enforce_retention(in_memory_store.as_ref(), &tenant_config, chrono::Utc::now())
+                });
+                if let Err(e) = purged {
+                    error!("Failed to enforce retention: {}", e);
+                }
+            }
+        });
+    }
 
-    let governor_conf = GovernorConfigBuilder::default()
-        .seconds_per_request(5)
-        .burst_size(10)
-        .finish()
+    //Without a configured secret, receipt hashes cannot be compared across restarts or replicas
+    let erasure_secret = config
+        .ingest
+        .erasure_hmac_secret
+        .clone()
         .unwrap_or_else(|| {
-            error!("Failed to create governor config");
-            std::process::exit(2)
+            warn!("ERASURE_HMAC_SECRET is not set; using a random secret for erasure receipts");
+            uuid::Uuid::new_v4().to_string()
+        });
+    let erasure = web::Data::new(ErasureService::new(
+        config.ingest.erasure_subject_paths.clone(),
+        erasure_secret.as_bytes(),
+    ));
+
+    //Redaction rules as a JSON array; nothing is redacted unless rules are configured
+    let redactor = web::Data::new(Redactor::new(Vec::new()).unwrap_or_else(|e| {
+        error!("Failed to compile redaction rules: {}", e);
+        std::process::exit(5)
+    }));
+    if let Some(path) = &config.ingest.redaction_rules_file {
+        redactor.load_file(path).unwrap_or_else(|e| {
+            error!("Failed to load redaction rules: {}", e);
+            std::process::exit(8)
+        });
+    }
+
+    //Schemas as a JSON object of event type to schema; otherwise managed through the API
+    let schemas = web::Data::new(SchemaRegistry::new());
+    if let Some(path) = &config.ingest.schemas_file {
+        schemas.load_file(path).unwrap_or_else(|e| {
+            error!("Failed to load schemas: {}", e);
+            std::process::exit(7)
+        });
+    }
+    let catalog = match &config.ingest.event_catalog_file {
+        Some(path) => EventTypeCatalog::load(path).unwrap_or_else(|e| {
+            error!("Failed to load event type catalog: {}", e);
+            std::process::exit(6)
+        }),
+        None => EventTypeCatalog::new(),
+    };
+    let catalog = web::Data::new(catalog);
+    //Upcasters are code, so the service starts with none; embedding applications register their own
+    let upcasters = web::Data::new(UpcasterRegistry::new());
+    let projections = web::Data::new(ProjectionRegistry::new());
+
+    //Tolerated drift of client timestamps from the server clock; unset means unbounded
+    let skew = web::Data::new(ClockSkewPolicy {
+        max_future: config
+            .ingest
+            .clock_skew_max_future_secs
+            .map(chrono::Duration::seconds),
+        max_past: config
+            .ingest
+            .clock_skew_max_past_secs
+            .map(chrono::Duration::seconds),
+        action: config.ingest.clock_skew_action,
+    });
+
+    //How long an Idempotency-Key (or client event id) is remembered
+    let idempotency = web::Data::new(IdempotencyCache::new(std::time::Duration::from_secs(
+        config.ingest.idempotency_ttl_secs,
+    )));
+
+    //Content-based dedup is off unless a window is configured
+    let dedup = config
+        .ingest
+        .dedup_window_secs
+        .map(|seconds| web::Data::new(DedupWindow::new(std::time::Duration::from_secs(seconds))));
+
+    //Authentication is off unless a key file or JWKS is configured
+    let api_keys = config.auth.api_keys_file.as_ref().map(|path| {
+        web::Data::new(ApiKeys::load(path).unwrap_or_else(|e| {
+            error!("Failed to load API keys: {}", e);
+            std::process::exit(10)
+        }))
+    });
+    //Bearer JWTs are accepted when a JWKS file is configured; it is re-read whenever it changes
+    let jwt = config.auth.jwks_file.clone().map(|jwks_file| {
+        let jwt_config = JwtConfig {
+            jwks_file,
+            issuer: config.auth.jwt_issuer.clone(),
+            audience: config.auth.jwt_audience.clone(),
+            tenant_claim: config.auth.jwt_tenant_claim.clone(),
+        };
+        web::Data::new(JwtVerifier::new(jwt_config).unwrap_or_else(|e| {
+            error!("Failed to load JWKS: {}", e);
+            std::process::exit(11)
+        }))
+    });
+
+    //Daily ingestion quotas per key; without a file usage is tracked but unlimited
+    let usage_config = match &config.ingest.usage_quotas_file {
+        Some(path) => UsageConfig::load(path).unwrap_or_else(|e| {
+            error!("Failed to load usage quotas: {}", e);
+            std::process::exit(13)
+        }),
+        None => UsageConfig::default(),
+    };
+    let usage = web::Data::new(UsageTracker::new(usage_config));
+
+    let metrics = web::Data::new(Metrics::new().unwrap_or_else(|e| {
+        error!("Failed to create metrics registry: {}", e);
+        std::process::exit(3)
+    }));
+
+    //Request budgets for reads and writes, per API key by default
+    let limiter = web::Data::new(RequestLimiter::new(&config.rate_limit).unwrap_or_else(|e| {
+        error!("Failed to create rate limiter: {}", e);
+        std::process::exit(4)
+    }));
+
+    //Rate limits, tenant limits, schemas, redaction rules and log levels are reloaded on SIGHUP
+    //or when their files change; everything else needs a restart
+    let watch_interval = config.server.watch_interval_secs;
+    let config_path = Config::path(&cli);
+    let reloader = Arc::new(ConfigReloader::new(
+        Box::new(move || Ok((Config::load(&cli)?, Config::path(&cli)))),
+        ReloadTargets {
+            limiter: limiter.clone(),
+            store: in_memory_store,
+            schemas: schemas.clone(),
+            redactor: redactor.clone(),
+            logger: Some(logger),
+        },
+        config,
+        config_path,
+    ));
+    if watch_interval > 0 {
+        actix_web::rt::spawn(watch_files(
+            reloader.clone(),
+            std::time::Duration::from_secs(watch_interval),
+        ));
+    }
+    #[cfg(unix)]
+    actix_web::rt::spawn(async move {
+        if let Err(e) = reload_on_sighup(reloader).await {
+            error!("Failed to listen for SIGHUP: {}", e);
+        }
+    });
+
+    //Idle rate-limit keys are dropped so arbitrary keys cannot grow the buckets without bound
+    {
+        let limiter = limiter.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                if let Err(e) = limiter.retain_recent() {
+                    error!("Failed to clean up rate limit buckets: {}", e);
+                }
+            }
         });
+    }
 
     info!("Listening on http://{}", host);
     HttpServer::new(move || {
-        App::new()
-            .wrap(Governor::new(&governor_conf))
+        let mut app = App::new();
+        if let Some(dedup) = &dedup {
+            app = app.app_data(dedup.clone());
+        }
+        if let Some(api_keys) = &api_keys {
+            app = app.app_data(api_keys.clone());
+        }
+        if let Some(jwt) = &jwt {
+            app = app.app_data(jwt.clone());
+        }
+        //Wrapped inside track_requests so rejected credentials are still counted
+        app.wrap(from_fn(authenticate))
+            .wrap(from_fn(track_requests))
             .app_data(store_data.clone())
-            .service(post_event)
-            .service(get_events)
-            .service(get_event_by_id)
+            .app_data(metrics.clone())
+            .app_data(erasure.clone())
+            .app_data(redactor.clone())
+            .app_data(schemas.clone())
+            .app_data(upcasters.clone())
+            .app_data(catalog.clone())
+            .app_data(skew.clone())
+            .app_data(idempotency.clone())
+            .app_data(projections.clone())
+            .app_data(limiter.clone())
+            .app_data(usage.clone())
+            //Probes are registered outside the rate-limited scope so they never consume budget
+            .service(healthz)
+            .service(readyz)
+            .service(
+                web::scope("")
+                    .wrap(from_fn(limit_requests))
+                    .service(post_event)
+                    .service(get_events)
+                    .service(get_event_by_id)
+                    .service(delete_event)
+                    .service(delete_events)
+                    .service(append_stream_events)
+                    .service(get_stream)
+                    .service(get_projections)
+                    .service(get_projection)
+                    .service(put_projection)
+                    .service(delete_projection)
+                    .service(post_erasure)
+                    .service(get_erasures)
+                    .service(get_erasure_by_id)
+                    .service(get_redaction_rules)
+                    .service(put_redaction_rules)
+                    .service(get_schemas)
+                    .service(get_schema)
+                    .service(put_schema)
+                    .service(delete_schema)
+                    .service(get_catalog)
+                    .service(put_catalog)
+                    .service(get_event_type)
+                    .service(put_event_type)
+                    .service(delete_event_type)
+                    .service(get_stats)
+                    .service(get_usage)
+                    .service(get_metrics),
+            )
     })
     .bind(host)?
     .run()
@@ -0,0 +1,237 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::time::Instant;
+
+use crate::catalog::EventTypeCatalog;
+use crate::error::AppError;
+use crate::model::StoreStats;
+
+//event_type label shared by every event type the catalog does not list
+pub const OTHER_EVENT_TYPE: &str = "other";
+
+//Metric label for an event type.  Event types come from producers, so only catalogued types get
+//their own series; anything else would let a writer grow label cardinality without bound.
+#[must_use]
+pub fn event_type_label<'a>(
+    catalog: Option<&web::Data<EventTypeCatalog>>,
+    event_type: &'a str,
+) -> &'a str {
+    match catalog.map(|catalog| catalog.get(event_type)) {
+        Some(Ok(Some(_))) => event_type,
+        _ => OTHER_EVENT_TYPE,
+    }
+}
+
+//Prometheus collectors for the service.  Each instance owns its own registry so tests can build
+//isolated apps without colliding on the process-wide default registry.
+pub struct Metrics {
+    registry: Registry,
+    events_ingested: IntCounterVec,
+    events_deduplicated: IntCounterVec,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    errors: IntCounterVec,
+    rate_limited: IntCounter,
+    store_events: IntGauge,
+    store_events_received: IntGauge,
+    store_memory_bytes: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new_custom(Some("event_tracker".to_string()), None)?;
+
+        let events_ingested = IntCounterVec::new(
+            Opts::new("events_ingested_total", "Events stored, by event type"),
+            &["event_type"],
+        )?;
+        let events_deduplicated = IntCounterVec::new(
+            Opts::new(
+                "events_deduplicated_total",
+                "Duplicate events dropped by the dedup window, by event type",
+            ),
+            &["event_type"],
+        )?;
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled, by route"),
+            &["method", "route", "status"],
+        )?;
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency, by route",
+            ),
+            &["method", "route"],
+        )?;
+        let errors = IntCounterVec::new(
+            Opts::new(
+                "errors_total",
+                "Application errors returned, by AppError variant",
+            ),
+            &["variant"],
+        )?;
+        let rate_limited = IntCounter::new(
+            "rate_limited_total",
+            "Requests rejected by the rate limiter",
+        )?;
+        let store_events = IntGauge::new("store_events", "Events currently held in the store")?;
+        let store_events_received = IntGauge::new(
+            "store_events_received",
+            "Events inserted into the store since startup",
+        )?;
+        let store_memory_bytes = IntGauge::new(
+            "store_memory_bytes",
+            "Deep memory size of the events held in the store",
+        )?;
+
+        registry.register(Box::new(events_ingested.clone()))?;
+        registry.register(Box::new(events_deduplicated.clone()))?;
+        registry.register(Box::new(http_requests.clone()))?;
+        registry.register(Box::new(http_request_duration.clone()))?;
+        registry.register(Box::new(errors.clone()))?;
+        registry.register(Box::new(rate_limited.clone()))?;
+        registry.register(Box::new(store_events.clone()))?;
+        registry.register(Box::new(store_events_received.clone()))?;
+        registry.register(Box::new(store_memory_bytes.clone()))?;
+
+        Ok(Self {
+            registry,
+            events_ingested,
+            events_deduplicated,
+            http_requests,
+            http_request_duration,
+            errors,
+            rate_limited,
+            store_events,
+            store_events_received,
+            store_memory_bytes,
+        })
+    }
+
+    pub fn record_ingested(&self, event_type: &str) {
+        self.events_ingested.with_label_values(&[event_type]).inc();
+    }
+
+    pub fn record_deduplicated(&self, event_type: &str) {
+        self.events_deduplicated
+            .with_label_values(&[event_type])
+            .inc();
+    }
+
+    pub fn record_request(&self, method: &str, route: &str, status: StatusCode, seconds: f64) {
+        self.http_requests
+            .with_label_values(&[method, route, status.as_str()])
+            .inc();
+        self.http_request_duration
+            .with_label_values(&[method, route])
+            .observe(seconds);
+    }
+
+    //Called by the rate limiter itself; other 429s, such as exhausted daily quotas, are not
+    //rate-limit rejections
+    pub fn record_rate_limited(&self) {
+        self.rate_limited.inc();
+    }
+
+    pub fn record_error(&self, error: &AppError) {
+        self.errors.with_label_values(&[error.kind()]).inc();
+    }
+
+    pub fn set_store_stats(&self, stats: &StoreStats) {
+        self.store_events.set(to_gauge(stats.event_count));
+        self.store_events_received
+            .set(to_gauge(stats.events_received));
+        self.store_memory_bytes.set(to_gauge(stats.memory_bytes));
+    }
+
+    //Renders every registered collector in the Prometheus text exposition format
+    pub fn render(&self) -> Result<String, AppError> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        String::from_utf8(buffer).map_err(|e| AppError::InternalError(e.to_string()))
+    }
+}
+
+fn to_gauge(value: usize) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+//Middleware recording request counts, latency and AppError variants.
+//Wrap it outside the rate limiter so rejected requests are still observed.
+pub async fn track_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let metrics = req.app_data::<web::Data<Metrics>>().cloned();
+    let method = req.method().to_string();
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let started = Instant::now();
+
+    let result = next.call(req).await;
+
+    if let Some(metrics) = metrics {
+        let elapsed = started.elapsed().as_secs_f64();
+        match &result {
+            Ok(res) => {
+                metrics.record_request(&method, &route, res.status(), elapsed);
+                if let Some(app_error) = res.response().error().and_then(|e| e.as_error()) {
+                    metrics.record_error(app_error);
+                }
+            }
+            Err(e) => {
+                metrics.record_request(
+                    &method,
+                    &route,
+                    e.as_response_error().status_code(),
+                    elapsed,
+                );
+                if let Some(app_error) = e.as_error() {
+                    metrics.record_error(app_error);
+                }
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_includes_recorded_values() {
+        let metrics = Metrics::new().unwrap();
+        metrics.record_ingested("login");
+        metrics.record_ingested("login");
+        metrics.record_request("GET", "/events", StatusCode::TOO_MANY_REQUESTS, 0.01);
+        metrics.record_request("POST", "/events", StatusCode::TOO_MANY_REQUESTS, 0.01);
+        metrics.record_rate_limited();
+        metrics.record_error(&AppError::NotFound("missing".into()));
+        metrics.set_store_stats(&StoreStats {
+            event_count: 3,
+            events_received: 4,
+            memory_bytes: 1024,
+            capacity_bytes: None,
+        });
+
+        let output = metrics.render().unwrap();
+
+        assert!(output.contains(r#"event_tracker_events_ingested_total{event_type="login"} 2"#));
+        assert!(output.contains(r#"event_tracker_errors_total{variant="NotFound"} 1"#));
+        assert!(output.contains("event_tracker_rate_limited_total 1"));
+        assert!(output.contains("event_tracker_store_events 3"));
+        assert!(output.contains("event_tracker_store_memory_bytes 1024"));
+    }
+}
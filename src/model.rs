@@ -1,14 +1,179 @@
 use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::mem::size_of;
 use uuid::Uuid;
 
+use crate::error::AppError;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Event {
     pub id: Uuid,
     pub event_type: String,
     pub timestamp: DateTime<Utc>,
+    //When the server accepted the event, independent of the client-supplied timestamp
+    pub received_at: DateTime<Utc>,
     pub payload: Value,
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+    #[serde(default, skip_serializing_if = "EventMetadata::is_empty")]
+    pub metadata: EventMetadata,
+    //Set when the timestamp fell outside the clock-skew tolerance and the policy flags instead of rejecting
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub clock_skewed: bool,
+    //Position in an event-sourcing stream, for events appended through a stream
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<StreamPosition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StreamPosition {
+    pub stream_id: String,
+    //1-based and gapless within the stream at append time
+    pub version: u64,
+}
+
+//Body of POST /streams/{id}/events.  With expected_version set, the append only succeeds if the
+//stream is currently at that version (0 for a stream that does not exist yet).
+#[derive(Debug, Deserialize)]
+pub struct StreamAppend {
+    #[serde(default)]
+    pub expected_version: Option<u64>,
+    pub events: Vec<NewEvent>,
+}
+
+//Query parameters of GET /streams/{id}
+#[derive(Debug, Deserialize, Default)]
+pub struct StreamQuery {
+    #[serde(default)]
+    pub from_version: u64,
+}
+
+//Query parameters of GET /usage; `key` defaults to the caller's own
+#[derive(Debug, Deserialize, Default)]
+pub struct UsageQuery {
+    pub key: Option<String>,
+}
+
+//Events of one stream in version order, with the stream's current version
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StreamEvents {
+    pub stream_id: String,
+    pub version: u64,
+    pub events: Vec<Event>,
+}
+
+//Envelope fields describing where an event came from, kept apart from the free-form payload so
+//flows can be stitched together across services.  Each field is filterable and indexed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct EventMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub causation_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+}
+
+impl EventMetadata {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.fields().next().is_none()
+    }
+
+    //(field name, value) for each field that is set
+    pub fn fields(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        [
+            ("source", &self.source),
+            ("actor", &self.actor),
+            ("correlation_id", &self.correlation_id),
+            ("causation_id", &self.causation_id),
+            ("trace_id", &self.trace_id),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_deref().map(|v| (name, v)))
+    }
+
+    //True when every field set here has the same value in `other`
+    #[must_use]
+    pub fn is_subset_of(&self, other: &EventMetadata) -> bool {
+        self.fields()
+            .all(|(name, value)| other.fields().any(|(n, v)| n == name && v == value))
+    }
+
+    //Every field, set or not, for rewriting values in place
+    pub fn values_mut(&mut self) -> [&mut Option<String>; 5] {
+        [
+            &mut self.source,
+            &mut self.actor,
+            &mut self.correlation_id,
+            &mut self.causation_id,
+            &mut self.trace_id,
+        ]
+    }
+
+    fn heap_size(&self) -> usize {
+        self.fields().map(|(_, value)| value.len()).sum()
+    }
+}
+
+//Payloads recorded before versioning was introduced are treated as version 1
+#[must_use]
+pub fn default_schema_version() -> u32 {
+    1
+}
+
+impl Event {
+    //Approximate bytes held by this event: the struct itself plus the heap allocations behind
+    //event_type and the payload.  Map node overhead inside serde_json objects is not counted.
+    #[must_use]
+    pub fn deep_size(&self) -> usize {
+        size_of::<Self>()
+            + self.event_type.capacity()
+            + value_heap_size(&self.payload)
+            + self.metadata.heap_size()
+            + self
+                .stream
+                .as_ref()
+                .map_or(0, |stream| stream.stream_id.capacity())
+    }
+}
+
+fn value_heap_size(value: &Value) -> usize {
+    match value {
+        Value::Null | Value::Bool(_) | Value::Number(_) => 0,
+        Value::String(s) => s.capacity(),
+        Value::Array(items) => {
+            items.capacity() * size_of::<Value>() + items.iter().map(value_heap_size).sum::<usize>()
+        }
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| {
+                size_of::<String>() + k.capacity() + size_of::<Value>() + value_heap_size(v)
+            })
+            .sum(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct StoreStats {
+    pub event_count: usize,
+    pub events_received: usize,
+    pub memory_bytes: usize,
+    pub capacity_bytes: Option<usize>,
+}
+
+//Whether a store can take traffic
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreStatus {
+    Ready,
+    OverCapacity,
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -16,23 +181,150 @@ pub struct EventQuery {
     pub event_type: Option<String>,
     pub start: Option<DateTime<Utc>>,
     pub end: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pub metadata: EventMetadata,
+    //Only events the server received before this time; used by retention, not exposed as a
+    //query parameter
+    #[serde(skip)]
+    pub received_before: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Deserialize)]
+impl EventQuery {
+    #[must_use]
+    pub fn matches(&self, event: &Event) -> bool {
+        self.event_type
+            .as_ref()
+            .is_none_or(|t| &event.event_type == t)
+            && self.start.is_none_or(|start| event.timestamp >= start)
+            && self.end.is_none_or(|end| event.timestamp <= end)
+            && self.metadata.is_subset_of(&event.metadata)
+            && self
+                .received_before
+                .is_none_or(|cutoff| event.received_at < cutoff)
+    }
+
+    //True when no filter is set, i.e. the query matches every event
+    #[must_use]
+    pub fn is_unfiltered(&self) -> bool {
+        self.event_type.is_none()
+            && self.start.is_none()
+            && self.end.is_none()
+            && self.metadata.is_empty()
+            && self.received_before.is_none()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct NewEvent {
+    //Producer-chosen id; a retry carrying the same id returns the stored event instead of a duplicate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
     pub event_type: String,
-    pub timestamp: DateTime<Utc>,
+    //Defaults to the time the server received the event
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Utc>>,
     pub payload: Value,
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+    #[serde(default)]
+    pub metadata: EventMetadata,
 }
 
 impl NewEvent {
     #[must_use]
     pub fn into_event(self) -> Event {
+        let received_at = Utc::now();
         Event {
-            id: Uuid::new_v4(),
+            id: self.id.unwrap_or_else(Uuid::new_v4),
             event_type: self.event_type,
-            timestamp: self.timestamp,
+            timestamp: self.timestamp.unwrap_or(received_at),
+            received_at,
             payload: self.payload,
+            schema_version: self.schema_version,
+            metadata: self.metadata,
+            clock_skewed: false,
+            stream: None,
         }
     }
 }
+
+//Implemented by Rust payload types that map to a single event_type, so services embedding the
+//crate get compile-time checked events instead of hand-built JSON.  Only the constant is needed:
+//
+//    #[derive(Serialize, Deserialize)]
+//    struct Login { user_id: u64 }
+//
+//    impl TypedEvent for Login {
+//        const EVENT_TYPE: &'static str = "login";
+//    }
+pub trait TypedEvent: Serialize + DeserializeOwned {
+    const EVENT_TYPE: &'static str;
+    const SCHEMA_VERSION: u32 = 1;
+
+    fn to_new_event(&self, timestamp: DateTime<Utc>) -> Result<NewEvent, AppError> {
+        let payload =
+            serde_json::to_value(self).map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(NewEvent {
+            id: None,
+            event_type: Self::EVENT_TYPE.to_string(),
+            timestamp: Some(timestamp),
+            payload,
+            schema_version: Self::SCHEMA_VERSION,
+            metadata: EventMetadata::default(),
+        })
+    }
+
+    fn to_event(&self, timestamp: DateTime<Utc>) -> Result<Event, AppError> {
+        Ok(self.to_new_event(timestamp)?.into_event())
+    }
+
+    //Decodes the payload of a stored event.  Events of another type or schema version are
+    //rejected; run older events through an UpcasterRegistry first, e.g. by passing one to
+    //TypedEventStore::get_typed or query_typed.
+    fn from_event(event: &Event) -> Result<Self, AppError> {
+        if event.event_type != Self::EVENT_TYPE {
+            return Err(AppError::BadRequest(format!(
+                "Event {} has type '{}', expected '{}'",
+                event.id,
+                event.event_type,
+                Self::EVENT_TYPE
+            )));
+        }
+        if event.schema_version != Self::SCHEMA_VERSION {
+            return Err(AppError::BadRequest(format!(
+                "Event {} has schema version {}, expected {}",
+                event.id,
+                event.schema_version,
+                Self::SCHEMA_VERSION
+            )));
+        }
+        serde_json::from_value(event.payload.clone()).map_err(|e| {
+            AppError::BadRequest(format!(
+                "Event {} payload does not decode as '{}': {}",
+                event.id,
+                Self::EVENT_TYPE,
+                e
+            ))
+        })
+    }
+}
+
+//A stored event with its payload decoded into a TypedEvent
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedRecord<T> {
+    pub id: Uuid,
+    pub timestamp: DateTime<Utc>,
+    pub data: T,
+}
+
+impl<T: TypedEvent> TryFrom<&Event> for TypedRecord<T> {
+    type Error = AppError;
+
+    fn try_from(event: &Event) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: event.id,
+            timestamp: event.timestamp,
+            data: T::from_event(event)?,
+        })
+    }
+}
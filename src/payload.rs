@@ -0,0 +1,73 @@
+use serde_json::Value;
+
+//Helpers for addressing fields inside free-form event payloads.
+//Paths are dot separated (e.g. "user.email"); numeric segments index into arrays ("items.0.sku").
+
+#[must_use]
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(value, |current, segment| match current {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
+
+pub fn lookup_mut<'a>(value: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.')
+        .try_fold(value, |current, segment| match current {
+            Value::Object(map) => map.get_mut(segment),
+            Value::Array(items) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(move |i| items.get_mut(i)),
+            _ => None,
+        })
+}
+
+//Compares a payload value with an identifier given as a string, so that numeric ids such as
+//{"user_id": 42} match "42".  Arrays match when any element does.
+#[must_use]
+pub fn value_matches(value: &Value, expected: &str) -> bool {
+    match value {
+        Value::String(s) => s == expected,
+        Value::Number(n) => n.to_string() == expected,
+        Value::Bool(b) => b.to_string() == expected,
+        Value::Array(items) => items.iter().any(|item| value_matches(item, expected)),
+        Value::Null | Value::Object(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_lookup_nested_and_array_paths() {
+        let payload = json!({ "user": { "email": "a@example.com" }, "items": [{ "sku": "x1" }] });
+
+        assert_eq!(
+            lookup(&payload, "user.email"),
+            Some(&json!("a@example.com"))
+        );
+        assert_eq!(lookup(&payload, "items.0.sku"), Some(&json!("x1")));
+        assert_eq!(lookup(&payload, "items.1.sku"), None);
+        assert_eq!(lookup(&payload, "user.name"), None);
+    }
+
+    #[test]
+    fn test_lookup_mut_replaces_value() {
+        let mut payload = json!({ "user": { "id": 7 } });
+        *lookup_mut(&mut payload, "user.id").unwrap() = json!(null);
+        assert_eq!(payload, json!({ "user": { "id": null } }));
+    }
+
+    #[test]
+    fn test_value_matches() {
+        assert!(value_matches(&json!(42), "42"));
+        assert!(value_matches(&json!("bob"), "bob"));
+        assert!(value_matches(&json!(["a", "bob"]), "bob"));
+        assert!(!value_matches(&json!({ "bob": 1 }), "bob"));
+    }
+}
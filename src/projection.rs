@@ -0,0 +1,661 @@
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::model::{Event, EventQuery};
+use crate::payload::lookup;
+use crate::storage::EventStore;
+
+//How a projection folds matching events.  Fields are dot-separated payload paths.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "reducer", rename_all = "snake_case")]
+pub enum Reducer {
+    //Number of matching events
+    Count,
+    //Value of the field on the event with the latest timestamp
+    LastValue { field: String },
+    //Sum of a numeric field; events where it is missing or not a number are skipped
+    Sum { field: String },
+    //Distinct values of the field
+    Set { field: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProjectionDefinition {
+    //Only events of this type are folded; every event when unset
+    #[serde(default)]
+    pub event_type: Option<String>,
+    //Keeps separate state per distinct value of this payload field
+    #[serde(default)]
+    pub group_by: Option<String>,
+    #[serde(flatten)]
+    pub reducer: Reducer,
+}
+
+//Current state of a projection as returned by the API
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProjectionState {
+    pub name: String,
+    pub definition: ProjectionDefinition,
+    pub events_applied: u64,
+    //The folded value, or an object of folded values keyed by group when group_by is set
+    pub state: Value,
+}
+
+enum Accumulator {
+    Count(u64),
+    LastValue(DateTime<Utc>, Value),
+    //Integers are summed exactly; `fraction` holds non-integral input and, after an overflow,
+    //the running total
+    Sum {
+        integer: i128,
+        fraction: f64,
+        exact: bool,
+    },
+    Set(BTreeMap<String, Value>),
+}
+
+impl Accumulator {
+    fn new(reducer: &Reducer) -> Self {
+        match reducer {
+            Reducer::Count => Accumulator::Count(0),
+            Reducer::LastValue { .. } => {
+                Accumulator::LastValue(DateTime::<Utc>::MIN_UTC, Value::Null)
+            }
+            Reducer::Sum { .. } => Accumulator::Sum {
+                integer: 0,
+                fraction: 0.0,
+                exact: true,
+            },
+            Reducer::Set { .. } => Accumulator::Set(BTreeMap::new()),
+        }
+    }
+
+    fn apply(&mut self, reducer: &Reducer, event: &Event) {
+        match (self, reducer) {
+            (Accumulator::Count(count), _) => *count += 1,
+            (Accumulator::LastValue(at, value), Reducer::LastValue { field }) => {
+                if let Some(new) = lookup(&event.payload, field) {
+                    if event.timestamp >= *at {
+                        *at = event.timestamp;
+                        *value = new.clone();
+                    }
+                }
+            }
+            (
+                Accumulator::Sum {
+                    integer,
+                    fraction,
+                    exact,
+                },
+                Reducer::Sum { field },
+            ) => {
+                if let Some(Value::Number(n)) = lookup(&event.payload, field) {
+                    let whole = n
+                        .as_i64()
+                        .map(i128::from)
+                        .or_else(|| n.as_u64().map(i128::from));
+                    match whole {
+                        Some(whole) => match integer.checked_add(whole) {
+                            Some(sum) => *integer = sum,
+                            None => {
+                                *fraction += integer_as_f64(*integer) + integer_as_f64(whole);
+                                *integer = 0;
+                                *exact = false;
+                            }
+                        },
+                        None => {
+                            *fraction += n.as_f64().unwrap_or_default();
+                            *exact = false;
+                        }
+                    }
+                }
+            }
+            (Accumulator::Set(values), Reducer::Set { field }) => {
+                if let Some(new) = lookup(&event.payload, field) {
+                    values.insert(new.to_string(), new.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn value(&self) -> Value {
+        match self {
+            Accumulator::Count(count) => json!(count),
+            Accumulator::LastValue(_, value) => value.clone(),
+            //Whole-number sums are reported as integers as long as JSON numbers can hold them
+            Accumulator::Sum { integer, exact, .. } if *exact => i64::try_from(*integer)
+                .map(Value::from)
+                .or_else(|_| u64::try_from(*integer).map(Value::from))
+                .unwrap_or_else(|_| json!(integer_as_f64(*integer))),
+            Accumulator::Sum {
+                integer, fraction, ..
+            } => json!(integer_as_f64(*integer) + fraction),
+            Accumulator::Set(values) => Value::Array(values.values().cloned().collect()),
+        }
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn integer_as_f64(integer: i128) -> f64 {
+    integer as f64
+}
+
+struct Projection {
+    definition: ProjectionDefinition,
+    events_applied: u64,
+    //Keyed by group value; a single entry under "" when the projection is not grouped
+    groups: BTreeMap<String, Accumulator>,
+}
+
+impl Projection {
+    fn new(definition: ProjectionDefinition) -> Self {
+        Self {
+            definition,
+            events_applied: 0,
+            groups: BTreeMap::new(),
+        }
+    }
+
+    fn apply(&mut self, event: &Event) {
+        if self
+            .definition
+            .event_type
+            .as_ref()
+            .is_some_and(|t| t != &event.event_type)
+        {
+            return;
+        }
+        let group = match &self.definition.group_by {
+            Some(path) => match lookup(&event.payload, path) {
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+                None => return,
+            },
+            None => String::new(),
+        };
+        let reducer = &self.definition.reducer;
+        self.groups
+            .entry(group)
+            .or_insert_with(|| Accumulator::new(reducer))
+            .apply(reducer, event);
+        self.events_applied += 1;
+    }
+
+    fn state(&self, name: &str) -> ProjectionState {
+        let state = if self.definition.group_by.is_some() {
+            Value::Object(
+                self.groups
+                    .iter()
+                    .map(|(group, acc)| (group.clone(), acc.value()))
+                    .collect::<Map<String, Value>>(),
+            )
+        } else {
+            self.groups.get("").map_or_else(
+                || Accumulator::new(&self.definition.reducer).value(),
+                Accumulator::value,
+            )
+        };
+        ProjectionState {
+            name: name.to_string(),
+            definition: self.definition.clone(),
+            events_applied: self.events_applied,
+            state,
+        }
+    }
+}
+
+//One tenant's projections, plus the events folded while registrations are still backfilling
+#[derive(Default)]
+struct TenantProjections {
+    live: BTreeMap<String, Projection>,
+    //Events ingested since each in-progress backfill started, keyed by registration
+    backfills: HashMap<u64, Vec<Event>>,
+}
+
+//Named projections folded incrementally as events are ingested, kept separately per tenant.
+//Deleting events does not retract them from a projection; re-register it to rebuild from the
+//store.  Erasures rebuild every projection of the tenant (see `rebuild`).
+#[derive(Default)]
+pub struct ProjectionRegistry {
+    tenants: RwLock<HashMap<String, Arc<Mutex<TenantProjections>>>>,
+    next_backfill: AtomicU64,
+}
+
+impl ProjectionRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //Registers or replaces a projection and backfills it from the tenant's stored events.  The
+    //store is scanned without holding any registry lock; events ingested meanwhile are buffered
+    //and folded afterwards unless the scan already saw them.
+    pub fn register(
+        &self,
+        tenant: &str,
+        name: &str,
+        definition: ProjectionDefinition,
+        store: &dyn EventStore,
+    ) -> Result<ProjectionState, AppError> {
+        for field in [&definition.group_by, &reducer_field(&definition.reducer)]
+            .into_iter()
+            .flatten()
+        {
+            if field.is_empty() {
+                return Err(AppError::BadRequest(
+                    "Projection fields must not be empty".to_string(),
+                ));
+            }
+        }
+
+        let backfill = self.begin_backfill(tenant)?;
+        let existing = store.query_events(
+            tenant,
+            EventQuery {
+                event_type: definition.event_type.clone(),
+                ..Default::default()
+            },
+        );
+        self.finish_backfill(tenant, backfill, name, definition, existing)
+    }
+
+    //Rebuilds every projection of the tenant from the store, so values that an erasure deleted
+    //or anonymized no longer show up in set or last_value state
+    pub fn rebuild(&self, tenant: &str, store: &dyn EventStore) -> Result<(), AppError> {
+        for (name, definition) in self.list(tenant)? {
+            self.register(tenant, &name, definition, store)?;
+        }
+        Ok(())
+    }
+
+    //Starts buffering the tenant's ingested events for a backfill about to scan the store
+    fn begin_backfill(&self, tenant: &str) -> Result<u64, AppError> {
+        let backfill = self.next_backfill.fetch_add(1, Ordering::Relaxed);
+        let projections = {
+            let mut tenants = self
+                .tenants
+                .write()
+                .map_err(|e| AppError::InternalError(e.to_string()))?;
+            Arc::clone(tenants.entry(tenant.to_string()).or_default())
+        };
+        projections
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?
+            .backfills
+            .insert(backfill, Vec::new());
+        Ok(backfill)
+    }
+
+    //Folds the scanned events, then the buffered ones the scan did not include, and installs
+    //the projection
+    fn finish_backfill(
+        &self,
+        tenant: &str,
+        backfill: u64,
+        name: &str,
+        definition: ProjectionDefinition,
+        existing: Result<Vec<Event>, AppError>,
+    ) -> Result<ProjectionState, AppError> {
+        let projections = self
+            .tenant(tenant)?
+            .ok_or_else(|| AppError::InternalError(format!("No projections for {tenant}")))?;
+        let mut existing = match existing {
+            Ok(existing) => existing,
+            Err(e) => {
+                projections
+                    .lock()
+                    .map_err(|e| AppError::InternalError(e.to_string()))?
+                    .backfills
+                    .remove(&backfill);
+                return Err(e);
+            }
+        };
+        existing.sort_by_key(|event| event.received_at);
+        let scanned: HashSet<Uuid> = existing.iter().map(|event| event.id).collect();
+        let mut projection = Projection::new(definition);
+        for event in &existing {
+            projection.apply(event);
+        }
+
+        let mut projections = projections
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let buffered = projections.backfills.remove(&backfill).unwrap_or_default();
+        for event in buffered.iter().filter(|event| !scanned.contains(&event.id)) {
+            projection.apply(event);
+        }
+        info!(
+            "Registered projection {} for tenant {} from {} stored event(s)",
+            name, tenant, projection.events_applied
+        );
+        let state = projection.state(name);
+        projections.live.insert(name.to_string(), projection);
+        Ok(state)
+    }
+
+    fn tenant(&self, tenant: &str) -> Result<Option<Arc<Mutex<TenantProjections>>>, AppError> {
+        let tenants = self
+            .tenants
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(tenants.get(tenant).cloned())
+    }
+
+    //Folds a newly stored event.  Call it after the event is stored; tenants without
+    //projections are skipped without taking a lock.
+    pub fn apply(&self, tenant: &str, event: &Event) -> Result<(), AppError> {
+        let Some(projections) = self.tenant(tenant)? else {
+            return Ok(());
+        };
+        let mut projections = projections
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        for projection in projections.live.values_mut() {
+            projection.apply(event);
+        }
+        for buffered in projections.backfills.values_mut() {
+            buffered.push(event.clone());
+        }
+        debug!("Applied event {} to projections", event.id);
+        Ok(())
+    }
+
+    pub fn get(&self, tenant: &str, name: &str) -> Result<Option<ProjectionState>, AppError> {
+        let Some(projections) = self.tenant(tenant)? else {
+            return Ok(None);
+        };
+        let projections = projections
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(projections.live.get(name).map(|p| p.state(name)))
+    }
+
+    pub fn list(&self, tenant: &str) -> Result<BTreeMap<String, ProjectionDefinition>, AppError> {
+        let Some(projections) = self.tenant(tenant)? else {
+            return Ok(BTreeMap::new());
+        };
+        let projections = projections
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(projections
+            .live
+            .iter()
+            .map(|(name, p)| (name.clone(), p.definition.clone()))
+            .collect())
+    }
+
+    pub fn remove(&self, tenant: &str, name: &str) -> Result<bool, AppError> {
+        let Some(projections) = self.tenant(tenant)? else {
+            return Ok(false);
+        };
+        let mut projections = projections
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(projections.live.remove(name).is_some())
+    }
+}
+
+fn reducer_field(reducer: &Reducer) -> Option<String> {
+    match reducer {
+        Reducer::Count => None,
+        Reducer::LastValue { field } | Reducer::Sum { field } | Reducer::Set { field } => {
+            Some(field.clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::NewEvent;
+    use crate::storage::InMemoryEventStore;
+    use crate::tenant::DEFAULT_TENANT;
+    use chrono::TimeZone;
+
+    fn event(event_type: &str, hour: u32, payload: Value) -> Event {
+        NewEvent {
+            id: None,
+            event_type: event_type.into(),
+            timestamp: Some(Utc.with_ymd_and_hms(2025, 1, 1, hour, 0, 0).unwrap()),
+            payload,
+            schema_version: 1,
+            metadata: Default::default(),
+        }
+        .into_event()
+    }
+
+    fn definition(value: Value) -> ProjectionDefinition {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_count_per_group_backfills_and_updates() {
+        let store = InMemoryEventStore::new();
+        store
+            .add_event(DEFAULT_TENANT, event("login", 1, json!({ "user_id": 1 })))
+            .unwrap();
+        store
+            .add_event(DEFAULT_TENANT, event("logout", 2, json!({ "user_id": 1 })))
+            .unwrap();
+        let registry = ProjectionRegistry::new();
+
+        let state = registry
+            .register(
+                DEFAULT_TENANT,
+                "logins_per_user",
+                definition(
+                    json!({ "event_type": "login", "group_by": "user_id", "reducer": "count" }),
+                ),
+                &store,
+            )
+            .unwrap();
+        assert_eq!(state.state, json!({ "1": 1 }));
+
+        for e in [
+            event("login", 3, json!({ "user_id": 1 })),
+            event("login", 4, json!({ "user_id": "u2" })),
+            event("login", 5, json!({ "other": true })),
+            event("logout", 6, json!({ "user_id": 1 })),
+        ] {
+            registry.apply(DEFAULT_TENANT, &e).unwrap();
+        }
+        let state = registry
+            .get(DEFAULT_TENANT, "logins_per_user")
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.state, json!({ "1": 2, "u2": 1 }));
+        assert_eq!(state.events_applied, 3);
+    }
+
+    #[test]
+    fn test_sum_last_value_and_set() {
+        let store = InMemoryEventStore::new();
+        let registry = ProjectionRegistry::new();
+        registry
+            .register(
+                DEFAULT_TENANT,
+                "revenue",
+                definition(
+                    json!({ "event_type": "purchase", "reducer": "sum", "field": "amount" }),
+                ),
+                &store,
+            )
+            .unwrap();
+        registry
+            .register(
+                DEFAULT_TENANT,
+                "plan",
+                definition(json!({ "group_by": "user", "reducer": "last_value", "field": "plan" })),
+                &store,
+            )
+            .unwrap();
+        registry
+            .register(
+                DEFAULT_TENANT,
+                "skus",
+                definition(json!({ "reducer": "set", "field": "sku" })),
+                &store,
+            )
+            .unwrap();
+        assert_eq!(
+            registry
+                .get(DEFAULT_TENANT, "revenue")
+                .unwrap()
+                .unwrap()
+                .state,
+            json!(0)
+        );
+
+        for e in [
+            event("purchase", 1, json!({ "amount": 10, "sku": "a" })),
+            event("purchase", 2, json!({ "amount": 5, "sku": "b" })),
+            event("purchase", 3, json!({ "amount": "n/a", "sku": "a" })),
+            event("upgrade", 5, json!({ "user": "jo", "plan": "pro" })),
+            event("upgrade", 4, json!({ "user": "jo", "plan": "team" })),
+        ] {
+            registry.apply(DEFAULT_TENANT, &e).unwrap();
+        }
+
+        assert_eq!(
+            registry
+                .get(DEFAULT_TENANT, "revenue")
+                .unwrap()
+                .unwrap()
+                .state,
+            json!(15)
+        );
+        //Out-of-order arrival keeps the value with the latest timestamp
+        assert_eq!(
+            registry.get(DEFAULT_TENANT, "plan").unwrap().unwrap().state,
+            json!({ "jo": "pro" })
+        );
+        assert_eq!(
+            registry.get(DEFAULT_TENANT, "skus").unwrap().unwrap().state,
+            json!(["a", "b"])
+        );
+
+        registry
+            .apply(
+                DEFAULT_TENANT,
+                &event("purchase", 6, json!({ "amount": 0.5 })),
+            )
+            .unwrap();
+        assert_eq!(
+            registry
+                .get(DEFAULT_TENANT, "revenue")
+                .unwrap()
+                .unwrap()
+                .state,
+            json!(15.5)
+        );
+    }
+
+    #[test]
+    fn test_events_ingested_during_backfill_are_folded_once() {
+        let store = InMemoryEventStore::new();
+        let registry = ProjectionRegistry::new();
+        let ingest = |e: Event| {
+            store.add_event(DEFAULT_TENANT, e.clone()).unwrap();
+            registry.apply(DEFAULT_TENANT, &e).unwrap();
+        };
+
+        let backfill = registry.begin_backfill(DEFAULT_TENANT).unwrap();
+        //Stored before the scan: seen by both the scan and the buffer
+        ingest(event("login", 1, json!({})));
+        let scanned = store.query_events(DEFAULT_TENANT, EventQuery::default());
+        //Stored after the scan: only buffered
+        ingest(event("login", 2, json!({})));
+        let state = registry
+            .finish_backfill(
+                DEFAULT_TENANT,
+                backfill,
+                "all",
+                definition(json!({ "reducer": "count" })),
+                scanned,
+            )
+            .unwrap();
+        assert_eq!(state.state, json!(2));
+
+        ingest(event("login", 3, json!({})));
+        assert_eq!(
+            registry.get(DEFAULT_TENANT, "all").unwrap().unwrap().state,
+            json!(3)
+        );
+        //Other tenants are untouched
+        registry
+            .apply("other", &event("login", 4, json!({})))
+            .unwrap();
+        assert!(registry.list("other").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_integral_sums_are_exact() {
+        let store = InMemoryEventStore::new();
+        let registry = ProjectionRegistry::new();
+        registry
+            .register(
+                DEFAULT_TENANT,
+                "total",
+                definition(json!({ "reducer": "sum", "field": "n" })),
+                &store,
+            )
+            .unwrap();
+        let total = || {
+            registry
+                .get(DEFAULT_TENANT, "total")
+                .unwrap()
+                .unwrap()
+                .state
+        };
+
+        //2^53 + 1 is where f64 stops counting every integer
+        for n in [json!(9_007_199_254_740_992_u64), json!(1)] {
+            registry
+                .apply(DEFAULT_TENANT, &event("big", 1, json!({ "n": n })))
+                .unwrap();
+        }
+        assert_eq!(total(), json!(9_007_199_254_740_993_u64));
+
+        for _ in 0..2 {
+            registry
+                .apply(DEFAULT_TENANT, &event("big", 1, json!({ "n": i64::MAX })))
+                .unwrap();
+        }
+        //Beyond u64 only a float is left, but it is not saturated
+        assert!(total().as_u64().is_none());
+        assert!(total().as_f64().unwrap() > 1.8e19);
+    }
+
+    #[test]
+    fn test_register_rejects_empty_field_and_remove() {
+        let store = InMemoryEventStore::new();
+        let registry = ProjectionRegistry::new();
+        let result = registry.register(
+            DEFAULT_TENANT,
+            "bad",
+            definition(json!({ "reducer": "sum", "field": "" })),
+            &store,
+        );
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+        registry
+            .register(
+                DEFAULT_TENANT,
+                "all",
+                definition(json!({ "reducer": "count" })),
+                &store,
+            )
+            .unwrap();
+        assert!(registry.remove(DEFAULT_TENANT, "all").unwrap());
+        assert!(registry.get(DEFAULT_TENANT, "all").unwrap().is_none());
+        assert!(registry.list(DEFAULT_TENANT).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,649 @@
+use actix_governor::governor::clock::{Clock, DefaultClock};
+use actix_governor::governor::middleware::StateInformationMiddleware;
+use actix_governor::governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
+use actix_governor::KeyExtractor;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroU32;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::auth::{auth_configured, Principal};
+use crate::error::AppError;
+use crate::metrics::Metrics;
+use crate::tenant::{validate_tenant, DEFAULT_TENANT, TENANT_HEADER};
+
+//What a rate-limit bucket is shared by
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitKey {
+    //One bucket per API key or JWT subject; anonymous callers fall back to their IP
+    #[default]
+    Principal,
+    //One bucket per tenant, shared by all of its keys
+    Tenant,
+    //One bucket per peer IP, as before authentication existed
+    Ip,
+}
+
+//Buckets for the authenticated caller.  Runs after the authenticate middleware so the request's
+//Principal is already in its extensions.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientKeyExtractor {
+    key: RateLimitKey,
+}
+
+impl ClientKeyExtractor {
+    #[must_use]
+    pub fn new(key: RateLimitKey) -> Self {
+        Self { key }
+    }
+}
+
+fn peer_ip(req: &ServiceRequest) -> Option<String> {
+    req.peer_addr().map(|addr| addr.ip().to_string())
+}
+
+fn ip_key(req: &ServiceRequest) -> String {
+    format!("ip:{}", peer_ip(req).unwrap_or_default())
+}
+
+//Bucket of the tenant the request acts on.  Requests without credentials while authentication
+//is on are keyed by IP instead: the handler will refuse them, and the X-Tenant-Id header must
+//not let them spend another tenant's budget.
+fn tenant_key(req: &ServiceRequest) -> String {
+    let bound = match req.extensions().get::<Principal>() {
+        Some(principal) => principal.tenant.clone(),
+        None if auth_configured(req.request()) => return ip_key(req),
+        None => None,
+    };
+    let requested = || {
+        req.headers()
+            .get(TENANT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|tenant| validate_tenant(tenant).is_ok())
+            .map(str::to_string)
+    };
+    let tenant = bound
+        .or_else(requested)
+        .unwrap_or_else(|| DEFAULT_TENANT.to_string());
+    format!("tenant:{tenant}")
+}
+
+impl KeyExtractor for ClientKeyExtractor {
+    type Key = String;
+    type KeyExtractionError = AppError;
+
+    fn extract(&self, req: &ServiceRequest) -> Result<Self::Key, Self::KeyExtractionError> {
+        let key = match self.key {
+            RateLimitKey::Principal => req
+                .extensions()
+                .get::<Principal>()
+                .and_then(Principal::credential_id)
+                .unwrap_or_else(|| ip_key(req)),
+            RateLimitKey::Tenant => tenant_key(req),
+            RateLimitKey::Ip => ip_key(req),
+        };
+        Ok(key)
+    }
+}
+
+//Token bucket for one class of routes: a request is replenished every `period_ms`, up to `burst`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteLimit {
+    pub period_ms: u64,
+    pub burst: u32,
+}
+
+impl Default for RouteLimit {
+    fn default() -> Self {
+        Self {
+            period_ms: 5000,
+            burst: 10,
+        }
+    }
+}
+
+//Limit for the routes matching `path` (the route pattern, e.g. "/events/{id}") and, if set,
+//`method`.  Takes precedence over the read/write split and over client overrides.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RouteRule {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    pub limit: RouteLimit,
+}
+
+impl RouteRule {
+    fn matches(&self, pattern: &str, method: &Method) -> bool {
+        self.path == pattern
+            && self
+                .method
+                .as_deref()
+                .is_none_or(|m| m.eq_ignore_ascii_case(method.as_str()))
+    }
+
+    fn class(&self) -> String {
+        format!(
+            "route:{}:{}",
+            self.method.as_deref().unwrap_or("*").to_ascii_uppercase(),
+            self.path
+        )
+    }
+}
+
+//Read and write limits replacing the defaults for one client; unset classes keep the defaults
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientLimits {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reads: Option<RouteLimit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub writes: Option<RouteLimit>,
+}
+
+//Prefixes of the names `overrides` are keyed by
+pub const OVERRIDE_PREFIXES: [&str; 4] = ["key:", "jwt:", "tenant:", "ip:"];
+
+//Separate buckets for reads (GET, HEAD) and writes (POST, PUT, PATCH, DELETE), so reads can be
+//generous while ingestion and admin changes stay strict
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub key: RateLimitKey,
+    #[serde(default)]
+    pub reads: RouteLimit,
+    #[serde(default)]
+    pub writes: RouteLimit,
+    //Checked in order before the read/write split; the first match wins
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<RouteRule>,
+    //Limits for one API key ("key:<id>"), JWT subject ("jwt:<sub>"), tenant ("tenant:<id>") or
+    //peer IP ("ip:<addr>").  The credential's entry wins over its tenant's, which wins over
+    //the IP's.  Buckets are still shared according to `key`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub overrides: BTreeMap<String, ClientLimits>,
+    //Redis-compatible server holding buckets shared by all replicas (needs the `redis` feature)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redis_url: Option<String>,
+}
+
+//Rate-limit state reported on every limited response
+pub const LIMIT_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+pub const REMAINING_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+//Seconds until the bucket is full again
+pub const RESET_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+type KeyedLimiter = DefaultKeyedRateLimiter<String, StateInformationMiddleware>;
+
+struct Bucket {
+    limiter: KeyedLimiter,
+    quota: Quota,
+    route: RouteLimit,
+    //"reads", "writes", "<reads|writes>:<override>" or "route:<method>:<path>", naming the
+    //shared buckets in Redis
+    #[cfg_attr(not(feature = "redis"), allow(dead_code))]
+    class: String,
+}
+
+impl Bucket {
+    fn new(limit: RouteLimit, class: String) -> Result<Self, AppError> {
+        let burst = NonZeroU32::new(limit.burst);
+        let quota = Quota::with_period(Duration::from_millis(limit.period_ms))
+            .zip(burst)
+            .map(|(quota, burst)| quota.allow_burst(burst))
+            .ok_or_else(|| {
+                AppError::BadRequest(
+                    "Rate limit period_ms and burst must be greater than zero".to_string(),
+                )
+            })?;
+        Ok(Self {
+            limiter: RateLimiter::keyed(quota).with_middleware(),
+            quota,
+            route: limit,
+            class,
+        })
+    }
+
+    //Forgets keys whose buckets are full again, so one-off keys do not pile up
+    fn retain_recent(&self) {
+        self.limiter.retain_recent();
+        self.limiter.shrink_to_fit();
+    }
+
+    fn check(&self, key: &str) -> RateLimitState {
+        let limit = self.route.burst;
+        let key = key.to_string();
+        match self.limiter.check_key(&key) {
+            Ok(snapshot) => {
+                let remaining = snapshot.remaining_burst_capacity();
+                RateLimitState {
+                    limit,
+                    remaining,
+                    reset_secs: ceil_secs(self.quota.replenish_interval() * (limit - remaining)),
+                    retry_after_secs: None,
+                }
+            }
+            Err(not_until) => {
+                let wait = not_until.wait_time_from(DefaultClock::default().now());
+                RateLimitState {
+                    limit,
+                    remaining: 0,
+                    reset_secs: ceil_secs(wait + self.quota.replenish_interval() * (limit - 1)),
+                    retry_after_secs: Some(ceil_secs(wait).max(1)),
+                }
+            }
+        }
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+//Outcome of checking a request against its bucket; retry_after_secs is set when it was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitState {
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset_secs: u64,
+    pub retry_after_secs: Option<u64>,
+}
+
+impl RateLimitState {
+    fn apply(&self, headers: &mut HeaderMap) {
+        for (name, value) in [
+            (LIMIT_HEADER, u64::from(self.limit)),
+            (REMAINING_HEADER, u64::from(self.remaining)),
+            (RESET_HEADER, self.reset_secs),
+        ]
+        .into_iter()
+        .chain(self.retry_after_secs.map(|secs| (RETRY_AFTER, secs)))
+        {
+            headers.insert(name, HeaderValue::from(value));
+        }
+    }
+}
+
+struct ClientBuckets {
+    reads: Option<Bucket>,
+    writes: Option<Bucket>,
+}
+
+struct Limits {
+    config: RateLimitConfig,
+    key: ClientKeyExtractor,
+    reads: Bucket,
+    writes: Bucket,
+    routes: Vec<(RouteRule, Bucket)>,
+    overrides: HashMap<String, ClientBuckets>,
+    #[cfg(feature = "redis")]
+    shared: Option<crate::redis_limiter::RedisLimiter>,
+}
+
+impl Limits {
+    fn new(config: &RateLimitConfig) -> Result<Self, AppError> {
+        #[cfg(not(feature = "redis"))]
+        if config.redis_url.is_some() {
+            return Err(AppError::BadRequest(
+                "A rate limit Redis URL needs a build with the redis feature".to_string(),
+            ));
+        }
+        Ok(Self {
+            config: config.clone(),
+            key: ClientKeyExtractor::new(config.key),
+            reads: Bucket::new(config.reads, "reads".to_string())?,
+            writes: Bucket::new(config.writes, "writes".to_string())?,
+            routes: config
+                .routes
+                .iter()
+                .map(|rule| Ok((rule.clone(), Bucket::new(rule.limit, rule.class())?)))
+                .collect::<Result<_, AppError>>()?,
+            overrides: config
+                .overrides
+                .iter()
+                .map(|(name, limits)| {
+                    let bucket = |limit: Option<RouteLimit>, class: &str| {
+                        limit
+                            .map(|limit| Bucket::new(limit, format!("{class}:{name}")))
+                            .transpose()
+                    };
+                    let buckets = ClientBuckets {
+                        reads: bucket(limits.reads, "reads")?,
+                        writes: bucket(limits.writes, "writes")?,
+                    };
+                    Ok((name.clone(), buckets))
+                })
+                .collect::<Result<_, AppError>>()?,
+            #[cfg(feature = "redis")]
+            shared: config
+                .redis_url
+                .as_deref()
+                .map(crate::redis_limiter::RedisLimiter::new)
+                .transpose()?,
+        })
+    }
+
+    fn buckets(&self) -> impl Iterator<Item = &Bucket> {
+        let overrides = self
+            .overrides
+            .values()
+            .flat_map(|client| [client.reads.as_ref(), client.writes.as_ref()])
+            .flatten();
+        [&self.reads, &self.writes]
+            .into_iter()
+            .chain(self.routes.iter().map(|(_, bucket)| bucket))
+            .chain(overrides)
+    }
+
+    //The bucket a request is checked against
+    fn select(&self, req: &ServiceRequest) -> &Bucket {
+        let method = req.method();
+        let pattern = req
+            .match_pattern()
+            .unwrap_or_else(|| req.path().to_string());
+        if let Some((_, bucket)) = self
+            .routes
+            .iter()
+            .find(|(rule, _)| rule.matches(&pattern, method))
+        {
+            return bucket;
+        }
+
+        let read = matches!(*method, Method::GET | Method::HEAD);
+        let client = override_names(req).into_iter().flatten().find_map(|name| {
+            let buckets = self.overrides.get(&name)?;
+            if read {
+                buckets.reads.as_ref()
+            } else {
+                buckets.writes.as_ref()
+            }
+        });
+        client.unwrap_or(if read { &self.reads } else { &self.writes })
+    }
+}
+
+//Names a request's overrides may be listed under, most specific first
+fn override_names(req: &ServiceRequest) -> [Option<String>; 3] {
+    let credential = req
+        .extensions()
+        .get::<Principal>()
+        .and_then(Principal::credential_id);
+    [credential, Some(tenant_key(req)), Some(ip_key(req))]
+}
+
+//Keyed token buckets for reads (GET, HEAD) and writes (everything else).  With a Redis backend
+//the buckets are shared between replicas, and the in-process ones are only used while the
+//backend is unreachable.  The limits can be swapped at runtime with `reload`.
+pub struct RequestLimiter {
+    limits: RwLock<Arc<Limits>>,
+}
+
+impl RequestLimiter {
+    pub fn new(config: &RateLimitConfig) -> Result<Self, AppError> {
+        Ok(Self {
+            limits: RwLock::new(Arc::new(Limits::new(config)?)),
+        })
+    }
+
+    //Applies a new configuration and returns whether it differed.  Changed limits start with
+    //fresh in-process buckets; an unchanged configuration keeps the current bucket state.
+    pub fn reload(&self, config: &RateLimitConfig) -> Result<bool, AppError> {
+        if self.current()?.config == *config {
+            return Ok(false);
+        }
+        let limits = Arc::new(Limits::new(config)?);
+        *self
+            .limits
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))? = limits;
+        Ok(true)
+    }
+
+    //Drops the state of idle keys from the in-process buckets; run it periodically
+    pub fn retain_recent(&self) -> Result<(), AppError> {
+        let limits = self.current()?;
+        for bucket in limits.buckets() {
+            bucket.retain_recent();
+        }
+        Ok(())
+    }
+
+    fn current(&self) -> Result<Arc<Limits>, AppError> {
+        let limits = self
+            .limits
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(Arc::clone(&limits))
+    }
+
+    pub async fn check(&self, req: &ServiceRequest) -> Result<RateLimitState, AppError> {
+        let limits = self.current()?;
+        let key = limits.key.extract(req)?;
+        let bucket = limits.select(req);
+        //The backend logs when it goes away and comes back; meanwhile each replica limits alone
+        #[cfg(feature = "redis")]
+        if let Some(shared) = &limits.shared {
+            if let Ok(state) = shared.check(&bucket.class, &key, bucket.route).await {
+                return Ok(state);
+            }
+        }
+        Ok(bucket.check(&key))
+    }
+}
+
+//Middleware enforcing the RequestLimiter registered as app data, if any.  Wrap it inside
+//authenticate so buckets can be keyed by the caller.  Every limited response carries the
+//X-RateLimit-* headers; refusals are a 429 TooManyRequests error with Retry-After.
+pub async fn limit_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let Some(limiter) = req.app_data::<web::Data<RequestLimiter>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let state = limiter.check(&req).await?;
+    if let Some(retry_after) = state.retry_after_secs {
+        if let Some(metrics) = req.app_data::<web::Data<Metrics>>() {
+            metrics.record_rate_limited();
+        }
+        let mut res = req.error_response(AppError::TooManyRequests(format!(
+            "Rate limit of {} request(s) exceeded, retry in {}s",
+            state.limit, retry_after
+        )));
+        state.apply(res.headers_mut());
+        return Ok(res.map_into_right_body());
+    }
+    let mut res = next.call(req).await?;
+    state.apply(res.headers_mut());
+    Ok(res.map_into_left_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Credential;
+    use actix_web::test::TestRequest;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_key_extraction() {
+        let req = TestRequest::default()
+            .insert_header((TENANT_HEADER, "team-a"))
+            .to_srv_request();
+        req.extensions_mut().insert(Principal::new(
+            Credential::ApiKey,
+            "ingest",
+            Some("team-b".into()),
+            BTreeSet::new(),
+        ));
+        let extract = |key| ClientKeyExtractor::new(key).extract(&req).unwrap();
+        assert_eq!(extract(RateLimitKey::Principal), "key:ingest");
+        //The credentials' tenant wins over the header
+        assert_eq!(extract(RateLimitKey::Tenant), "tenant:team-b");
+
+        let anonymous = TestRequest::default()
+            .peer_addr("10.0.0.7:4000".parse().unwrap())
+            .to_srv_request();
+        let extract = |key| ClientKeyExtractor::new(key).extract(&anonymous).unwrap();
+        assert_eq!(extract(RateLimitKey::Principal), "ip:10.0.0.7");
+        assert_eq!(extract(RateLimitKey::Tenant), "tenant:default");
+    }
+
+    #[test]
+    fn test_tenant_header_cannot_pick_bucket_without_credentials() {
+        let keys = web::Data::new(crate::auth::ApiKeys::from_config(Default::default()));
+        let anonymous = TestRequest::default()
+            .app_data(keys)
+            .peer_addr("10.0.0.7:4000".parse().unwrap())
+            .insert_header((TENANT_HEADER, "victim"))
+            .to_srv_request();
+        let extract = |req| ClientKeyExtractor::new(RateLimitKey::Tenant).extract(req);
+        assert_eq!(extract(&anonymous).unwrap(), "ip:10.0.0.7");
+
+        //Without authentication the header selects the tenant, but only a valid one
+        let invalid = TestRequest::default()
+            .insert_header((TENANT_HEADER, "../victim"))
+            .to_srv_request();
+        assert_eq!(extract(&invalid).unwrap(), "tenant:default");
+    }
+
+    #[test]
+    fn test_retain_recent_forgets_idle_keys() {
+        let limiter = RequestLimiter::new(&RateLimitConfig {
+            writes: RouteLimit {
+                period_ms: 1,
+                burst: 1,
+            },
+            key: RateLimitKey::Tenant,
+            ..Default::default()
+        })
+        .unwrap();
+        let limits = limiter.current().unwrap();
+        for tenant in ["a", "b", "c"] {
+            limits.writes.check(&format!("tenant:{tenant}"));
+        }
+        assert_eq!(limits.writes.limiter.len(), 3);
+        std::thread::sleep(Duration::from_millis(5));
+        limiter.retain_recent().unwrap();
+        assert_eq!(limits.writes.limiter.len(), 0);
+    }
+
+    #[actix_rt::test]
+    async fn test_reload_replaces_limits() {
+        let limiter = RequestLimiter::new(&RateLimitConfig::default()).unwrap();
+        let req = || TestRequest::post().to_srv_request();
+        assert_eq!(limiter.check(&req()).await.unwrap().remaining, 9);
+        assert!(!limiter.reload(&RateLimitConfig::default()).unwrap());
+        assert_eq!(limiter.check(&req()).await.unwrap().remaining, 8);
+
+        let strict = RateLimitConfig {
+            writes: RouteLimit {
+                period_ms: 60_000,
+                burst: 1,
+            },
+            ..Default::default()
+        };
+        assert!(limiter.reload(&strict).unwrap());
+        assert_eq!(limiter.check(&req()).await.unwrap().limit, 1);
+        assert!(limiter
+            .check(&req())
+            .await
+            .unwrap()
+            .retry_after_secs
+            .is_some());
+        //An invalid configuration keeps the current limits
+        assert!(limiter
+            .reload(&RateLimitConfig {
+                reads: RouteLimit {
+                    period_ms: 0,
+                    burst: 1
+                },
+                ..Default::default()
+            })
+            .is_err());
+        assert_eq!(limiter.check(&req()).await.unwrap().limit, 1);
+    }
+
+    #[actix_rt::test]
+    async fn test_client_overrides() {
+        let config: RateLimitConfig = serde_json::from_value(serde_json::json!({
+            "writes": { "period_ms": 60000, "burst": 1 },
+            "overrides": {
+                "tenant:team-a": { "writes": { "period_ms": 60000, "burst": 5 } },
+                "key:loader": { "writes": { "period_ms": 60000, "burst": 7 } }
+            }
+        }))
+        .unwrap();
+        let limiter = RequestLimiter::new(&config).unwrap();
+        let caller = |id: &str, tenant: &str| {
+            let req = TestRequest::post().uri("/events").to_srv_request();
+            req.extensions_mut().insert(Principal::new(
+                Credential::ApiKey,
+                id,
+                Some(tenant.into()),
+                BTreeSet::new(),
+            ));
+            req
+        };
+
+        assert_eq!(
+            limiter
+                .check(&caller("other", "team-b"))
+                .await
+                .unwrap()
+                .limit,
+            1
+        );
+        assert_eq!(
+            limiter
+                .check(&caller("ingest", "team-a"))
+                .await
+                .unwrap()
+                .limit,
+            5
+        );
+        //The key's own entry wins over its tenant's
+        assert_eq!(
+            limiter
+                .check(&caller("loader", "team-a"))
+                .await
+                .unwrap()
+                .limit,
+            7
+        );
+        //Reads have no override, so they keep the default
+        let read = TestRequest::get().uri("/events").to_srv_request();
+        read.extensions_mut().insert(Principal::new(
+            Credential::ApiKey,
+            "ingest",
+            Some("team-a".into()),
+            BTreeSet::new(),
+        ));
+        assert_eq!(limiter.check(&read).await.unwrap().limit, 10);
+    }
+
+    #[test]
+    fn test_zero_limits_are_rejected() {
+        let config = RateLimitConfig {
+            writes: RouteLimit {
+                period_ms: 0,
+                burst: 1,
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            RequestLimiter::new(&config),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,486 @@
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::RwLock;
+
+use crate::error::AppError;
+use crate::model::Event;
+use crate::payload::lookup_mut;
+
+//Replacement for whole values removed by a path rule.  Pattern matches become "[redacted:<name>]"
+pub const REDACTED_MARKER: &str = "[redacted]";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BuiltinPattern {
+    Email,
+    CardNumber,
+    IpAddress,
+}
+
+impl BuiltinPattern {
+    fn name(self) -> &'static str {
+        match self {
+            BuiltinPattern::Email => "email",
+            BuiltinPattern::CardNumber => "card_number",
+            BuiltinPattern::IpAddress => "ip_address",
+        }
+    }
+
+    fn pattern(self) -> &'static str {
+        match self {
+            BuiltinPattern::Email => r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+            //13 to 19 digits, optionally grouped with spaces or dashes; see `accepts`
+            BuiltinPattern::CardNumber => r"\b(?:\d[ -]?){12,18}\d\b",
+            //IPv4, full IPv6 and "::" compressed IPv6
+            BuiltinPattern::IpAddress => {
+                r"(?i)\b(?:(?:25[0-5]|2[0-4]\d|1?\d?\d)\.){3}(?:25[0-5]|2[0-4]\d|1?\d?\d)\b|\b(?:[0-9a-f]{1,4}:){7}[0-9a-f]{1,4}\b|\b(?:[0-9a-f]{1,4}:){1,7}:(?:[0-9a-f]{1,4}(?::[0-9a-f]{1,4}){0,6})?"
+            }
+        }
+    }
+
+    //Second check on each match before it is replaced.  Card numbers must pass the Luhn
+    //checksum, so millisecond timestamps, order ids and phone numbers are left alone.
+    fn accepts(self) -> fn(&str) -> bool {
+        match self {
+            BuiltinPattern::CardNumber => luhn_valid,
+            BuiltinPattern::Email | BuiltinPattern::IpAddress => any_match,
+        }
+    }
+
+    //Check for JSON numbers, which are only scanned by patterns that can match one.  Card
+    //numbers sent as numbers must also start with 2-6 like the major networks' do, which keeps
+    //millisecond epoch timestamps (currently starting with 1) out even when they pass Luhn.
+    fn accepts_number(self) -> Option<fn(&str) -> bool> {
+        match self {
+            BuiltinPattern::CardNumber => Some(card_number_value),
+            BuiltinPattern::Email | BuiltinPattern::IpAddress => None,
+        }
+    }
+}
+
+fn any_match(_: &str) -> bool {
+    true
+}
+
+fn luhn_valid(number: &str) -> bool {
+    let digits = number.chars().filter_map(|c| c.to_digit(10));
+    let sum: u32 = digits
+        .rev()
+        .enumerate()
+        .map(|(i, digit)| match (i % 2, digit * 2) {
+            (0, _) => digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn card_number_value(number: &str) -> bool {
+    number.starts_with(['2', '3', '4', '5', '6']) && luhn_valid(number)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RedactionRule {
+    //Replace the whole value at a payload path (see payload::lookup)
+    Path { path: String },
+    //Replace matches of a regex inside any string value
+    Pattern { name: String, pattern: String },
+    //One of the bundled patterns
+    Builtin { name: BuiltinPattern },
+}
+
+impl RedactionRule {
+    //All bundled patterns.  None are active unless configured.
+    #[must_use]
+    pub fn builtins() -> Vec<RedactionRule> {
+        [
+            BuiltinPattern::Email,
+            BuiltinPattern::CardNumber,
+            BuiltinPattern::IpAddress,
+        ]
+        .into_iter()
+        .map(|name| RedactionRule::Builtin { name })
+        .collect()
+    }
+
+    //Reads a JSON array of rules, as used by the REDACTION_RULES_FILE config file
+    pub fn load_file(path: &str) -> Result<Vec<RedactionRule>, AppError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| AppError::InternalError(format!("Failed to read {path}: {e}")))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| AppError::BadRequest(format!("Invalid redaction rules in {path}: {e}")))
+    }
+}
+
+struct Pattern {
+    name: String,
+    regex: Regex,
+    accepts: fn(&str) -> bool,
+    accepts_number: Option<fn(&str) -> bool>,
+}
+
+struct CompiledRules {
+    rules: Vec<RedactionRule>,
+    paths: Vec<String>,
+    patterns: Vec<Pattern>,
+}
+
+impl CompiledRules {
+    fn compile(rules: Vec<RedactionRule>) -> Result<Self, AppError> {
+        let mut paths = Vec::new();
+        let mut patterns = Vec::new();
+        for rule in &rules {
+            match rule {
+                RedactionRule::Path { path } => paths.push(path.clone()),
+                RedactionRule::Pattern { name, pattern } => {
+                    let regex = Regex::new(pattern).map_err(|e| {
+                        AppError::BadRequest(format!("Invalid redaction pattern '{name}': {e}"))
+                    })?;
+                    patterns.push(Pattern {
+                        name: name.clone(),
+                        regex,
+                        accepts: any_match,
+                        accepts_number: None,
+                    });
+                }
+                RedactionRule::Builtin { name } => {
+                    let regex = Regex::new(name.pattern())
+                        .map_err(|e| AppError::InternalError(e.to_string()))?;
+                    patterns.push(Pattern {
+                        name: name.name().to_string(),
+                        regex,
+                        accepts: name.accepts(),
+                        accepts_number: name.accepts_number(),
+                    });
+                }
+            }
+        }
+        Ok(Self {
+            rules,
+            paths,
+            patterns,
+        })
+    }
+
+    fn redact(&self, payload: &mut Value) -> usize {
+        let mut redacted = 0;
+        for path in &self.paths {
+            if let Some(value) = lookup_mut(payload, path) {
+                *value = Value::String(REDACTED_MARKER.to_string());
+                redacted += 1;
+            }
+        }
+        if !self.patterns.is_empty() {
+            redacted += redact_strings(payload, &self.patterns);
+        }
+        redacted
+    }
+}
+
+//Applies redaction rules to event payloads before they are stored or logged.
+//Rules sit behind a RwLock so they can be replaced at runtime.  Once loaded from a file, the file
+//is the only source and runtime changes are refused, so a reload cannot silently discard them.
+pub struct Redactor {
+    compiled: RwLock<CompiledRules>,
+    file: RwLock<Option<String>>,
+}
+
+impl Redactor {
+    pub fn new(rules: Vec<RedactionRule>) -> Result<Self, AppError> {
+        Ok(Self {
+            compiled: RwLock::new(CompiledRules::compile(rules)?),
+            file: RwLock::new(None),
+        })
+    }
+
+    //Replaces the rules with a REDACTION_RULES_FILE and makes that file their owner
+    pub fn load_file(&self, path: &str) -> Result<(), AppError> {
+        self.swap(RedactionRule::load_file(path)?)?;
+        *self
+            .file
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))? = Some(path.to_string());
+        Ok(())
+    }
+
+    //Keeps the current rules but hands them back to the admin API
+    pub fn release_file(&self) -> Result<(), AppError> {
+        *self
+            .file
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))? = None;
+        Ok(())
+    }
+
+    pub fn rules(&self) -> Result<Vec<RedactionRule>, AppError> {
+        let compiled = self
+            .compiled
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(compiled.rules.clone())
+    }
+
+    pub fn set_rules(&self, rules: Vec<RedactionRule>) -> Result<(), AppError> {
+        let file = self
+            .file
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        if let Some(path) = file.as_deref() {
+            return Err(AppError::Conflict(format!(
+                "Redaction rules are managed by {path}; edit that file instead"
+            )));
+        }
+        self.swap(rules)
+    }
+
+    fn swap(&self, rules: Vec<RedactionRule>) -> Result<(), AppError> {
+        let replacement = CompiledRules::compile(rules)?;
+        *self
+            .compiled
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))? = replacement;
+        Ok(())
+    }
+
+    //Redacts the payload and the metadata values in place and returns the number of values
+    //changed.  Path rules only apply to the payload.
+    pub fn redact_event(&self, event: &mut Event) -> Result<usize, AppError> {
+        let compiled = self
+            .compiled
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let mut redacted = compiled.redact(&mut event.payload);
+        for value in event.metadata.values_mut().into_iter().flatten() {
+            redacted += usize::from(redact_string(value, &compiled.patterns));
+        }
+        Ok(redacted)
+    }
+
+    //Redacts the payload in place and returns the number of values changed
+    pub fn redact(&self, payload: &mut Value) -> Result<usize, AppError> {
+        let compiled = self
+            .compiled
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(compiled.redact(payload))
+    }
+}
+
+fn redact_string(s: &mut String, patterns: &[Pattern]) -> bool {
+    let mut changed = false;
+    for pattern in patterns {
+        let marker = format!("[redacted:{}]", pattern.name);
+        let mut replaced = false;
+        let redacted = pattern
+            .regex
+            .replace_all(s, |caps: &Captures| {
+                if (pattern.accepts)(&caps[0]) {
+                    replaced = true;
+                    marker.clone()
+                } else {
+                    caps[0].to_string()
+                }
+            })
+            .into_owned();
+        if replaced {
+            *s = redacted;
+            changed = true;
+        }
+    }
+    changed
+}
+
+fn redact_strings(value: &mut Value, patterns: &[Pattern]) -> usize {
+    match value {
+        Value::String(s) => usize::from(redact_string(s, patterns)),
+        Value::Array(items) => items
+            .iter_mut()
+            .map(|item| redact_strings(item, patterns))
+            .sum(),
+        Value::Object(map) => map
+            .values_mut()
+            .map(|item| redact_strings(item, patterns))
+            .sum(),
+        //A number cannot be partly redacted, so a match replaces the whole value
+        Value::Number(number) => {
+            let text = number.to_string();
+            let matched = patterns.iter().find(|pattern| {
+                pattern.accepts_number.is_some_and(|accepts| {
+                    pattern
+                        .regex
+                        .find_iter(&text)
+                        .any(|found| accepts(found.as_str()))
+                })
+            });
+            match matched {
+                Some(pattern) => {
+                    *value = Value::String(format!("[redacted:{}]", pattern.name));
+                    1
+                }
+                None => 0,
+            }
+        }
+        Value::Null | Value::Bool(_) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_builtin_patterns() {
+        let redactor = Redactor::new(RedactionRule::builtins()).unwrap();
+        let mut payload = json!({
+            "note": "mail jo@example.com from 10.0.0.12",
+            "card": "4111 1111 1111 1111",
+            "v6": "client fe80::1ff:fe23:4567:890a",
+            "nested": [{ "contact": "sam@example.org" }],
+            "count": 12,
+            "time": "12:30:45",
+        });
+
+        let redacted = redactor.redact(&mut payload).unwrap();
+
+        assert_eq!(redacted, 4);
+        assert_eq!(
+            payload["note"],
+            "mail [redacted:email] from [redacted:ip_address]"
+        );
+        assert_eq!(payload["card"], "[redacted:card_number]");
+        assert_eq!(payload["v6"], "client [redacted:ip_address]");
+        assert_eq!(payload["nested"][0]["contact"], "[redacted:email]");
+        assert_eq!(payload["count"], 12);
+        assert_eq!(payload["time"], "12:30:45");
+    }
+
+    #[test]
+    fn test_card_numbers_must_pass_luhn() {
+        let redactor = Redactor::new(RedactionRule::builtins()).unwrap();
+        let mut payload = json!({
+            "sent_at_ms": "1735689600000",
+            "order": "order 441234567890123 shipped",
+            "card": "4111-1111-1111-1111 and 4111-1111-1111-1112"
+        });
+
+        assert_eq!(redactor.redact(&mut payload).unwrap(), 1);
+        assert_eq!(
+            payload,
+            json!({
+                "sent_at_ms": "1735689600000",
+                "order": "order 441234567890123 shipped",
+                "card": "[redacted:card_number] and 4111-1111-1111-1112"
+            })
+        );
+    }
+
+    #[test]
+    fn test_numbers_are_checked_for_card_numbers() {
+        let redactor = Redactor::new(RedactionRule::builtins()).unwrap();
+        let mut payload = json!({
+            "card": 4111111111111111_u64,
+            //Passes Luhn, but no card number starts with 1
+            "sent_at_ms": 1735689600005_u64,
+            "count": 12,
+            "ratio": 0.5
+        });
+
+        assert_eq!(redactor.redact(&mut payload).unwrap(), 1);
+        assert_eq!(
+            payload,
+            json!({
+                "card": "[redacted:card_number]",
+                "sent_at_ms": 1735689600005_u64,
+                "count": 12,
+                "ratio": 0.5
+            })
+        );
+    }
+
+    #[test]
+    fn test_metadata_values_are_redacted() {
+        let redactor = Redactor::new(vec![
+            RedactionRule::Builtin {
+                name: BuiltinPattern::Email,
+            },
+            RedactionRule::Path {
+                path: "actor".into(),
+            },
+        ])
+        .unwrap();
+        let mut event = crate::model::NewEvent {
+            id: None,
+            event_type: "login".into(),
+            timestamp: None,
+            payload: json!({ "actor": "sam" }),
+            schema_version: 1,
+            metadata: crate::model::EventMetadata {
+                actor: Some("jo@example.com".into()),
+                source: Some("web".into()),
+                ..Default::default()
+            },
+        }
+        .into_event();
+
+        //The path rule addresses the payload only; the pattern covers both
+        assert_eq!(redactor.redact_event(&mut event).unwrap(), 2);
+        assert_eq!(event.metadata.actor.as_deref(), Some("[redacted:email]"));
+        assert_eq!(event.metadata.source.as_deref(), Some("web"));
+        assert_eq!(event.payload, json!({ "actor": REDACTED_MARKER }));
+    }
+
+    #[test]
+    fn test_path_and_custom_pattern_rules() {
+        let redactor = Redactor::new(vec![
+            RedactionRule::Path {
+                path: "user.ssn".into(),
+            },
+            RedactionRule::Pattern {
+                name: "token".into(),
+                pattern: r"tok_[a-z0-9]+".into(),
+            },
+        ])
+        .unwrap();
+        let mut payload = json!({ "user": { "ssn": 123456789 }, "auth": "bearer tok_abc123" });
+
+        redactor.redact(&mut payload).unwrap();
+
+        assert_eq!(
+            payload,
+            json!({ "user": { "ssn": REDACTED_MARKER }, "auth": "bearer [redacted:token]" })
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern_rejected() {
+        let result = Redactor::new(vec![RedactionRule::Pattern {
+            name: "broken".into(),
+            pattern: "(".into(),
+        }]);
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn test_rules_deserialize() {
+        let rules: Vec<RedactionRule> = serde_json::from_value(json!([
+            { "kind": "path", "path": "email" },
+            { "kind": "builtin", "name": "card_number" },
+        ]))
+        .unwrap();
+        assert_eq!(
+            rules,
+            vec![
+                RedactionRule::Path {
+                    path: "email".into()
+                },
+                RedactionRule::Builtin {
+                    name: BuiltinPattern::CardNumber
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use log::{info, warn};
+use redis::aio::MultiplexedConnection;
+use redis::{Client, Script};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::AppError;
+use crate::rate_limit::{RateLimitState, RouteLimit};
+
+//Redis keys are prefixed so a shared server can host other data
+const KEY_PREFIX: &str = "event-tracker:ratelimit";
+//Bounds on a single backend round trip, so an unhealthy server only delays requests briefly
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(250);
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(100);
+//After a failure the backend is skipped for this long before reconnecting
+const RETRY_AFTER_FAILURE: Duration = Duration::from_secs(5);
+
+//GCRA token bucket, evaluated atomically on the server using the server's clock so replicas
+//with drifting clocks agree.  The key holds the bucket's theoretical arrival time (ms); the
+//script returns {allowed, remaining, ms until full, ms until the next request is allowed}.
+const GCRA: &str = r"
+local interval = tonumber(ARGV[1])
+local burst = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local tat = math.max(tonumber(redis.call('GET', KEYS[1]) or now), now)
+local new_tat = tat + interval
+local allow_at = new_tat - interval * burst
+if now < allow_at then
+  return {0, 0, tat - now, allow_at - now}
+end
+redis.call('SET', KEYS[1], new_tat, 'PX', new_tat - now)
+return {1, math.floor((now + interval * burst - new_tat) / interval), new_tat - now, 0}
+";
+
+#[derive(Default)]
+struct Connection {
+    connection: Option<MultiplexedConnection>,
+    unavailable_until: Option<Instant>,
+}
+
+//Rate-limit buckets kept in a Redis-compatible server, so every replica draws from the same
+//budget.  Errors are returned to the caller, which falls back to its in-process buckets.
+pub struct RedisLimiter {
+    client: Client,
+    state: Mutex<Connection>,
+    script: Script,
+}
+
+fn ceil_secs(ms: i64) -> u64 {
+    u64::try_from(ms.max(0)).unwrap_or_default().div_ceil(1000)
+}
+
+impl RedisLimiter {
+    pub fn new(url: &str) -> Result<Self, AppError> {
+        let client = Client::open(url)
+            .map_err(|e| AppError::BadRequest(format!("Invalid rate limit Redis URL: {e}")))?;
+        Ok(Self {
+            client,
+            state: Mutex::new(Connection::default()),
+            script: Script::new(GCRA),
+        })
+    }
+
+    async fn connection(&self) -> Result<MultiplexedConnection, AppError> {
+        {
+            let state = self
+                .state
+                .lock()
+                .map_err(|e| AppError::InternalError(e.to_string()))?;
+            if let Some(connection) = &state.connection {
+                return Ok(connection.clone());
+            }
+            if state
+                .unavailable_until
+                .is_some_and(|until| Instant::now() < until)
+            {
+                return Err(AppError::ServiceUnavailable(
+                    "Rate limit backend is unavailable".to_string(),
+                ));
+            }
+        }
+        let connected = self
+            .client
+            .get_multiplexed_async_connection_with_timeouts(RESPONSE_TIMEOUT, CONNECT_TIMEOUT)
+            .await;
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        match connected {
+            Ok(connection) => {
+                if state.unavailable_until.take().is_some() {
+                    info!("Rate limit backend is reachable again");
+                }
+                state.connection = Some(connection.clone());
+                Ok(connection)
+            }
+            Err(e) => {
+                if state.unavailable_until.is_none() {
+                    warn!("Rate limit backend unreachable, using in-process buckets");
+                }
+                state.unavailable_until = Some(Instant::now() + RETRY_AFTER_FAILURE);
+                Err(AppError::ServiceUnavailable(format!(
+                    "Failed to connect to rate limit backend: {e}"
+                )))
+            }
+        }
+    }
+
+    //Drops a broken connection and skips the backend for a while
+    fn mark_unavailable(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            if state.unavailable_until.is_none() {
+                warn!("Rate limit backend failed, using in-process buckets");
+            }
+            state.connection = None;
+            state.unavailable_until = Some(Instant::now() + RETRY_AFTER_FAILURE);
+        }
+    }
+
+    //Takes one request from the `class` bucket of `key`
+    pub async fn check(
+        &self,
+        class: &str,
+        key: &str,
+        limit: RouteLimit,
+    ) -> Result<RateLimitState, AppError> {
+        let mut connection = self.connection().await?;
+        let reply: Result<(i64, i64, i64, i64), _> = self
+            .script
+            .key(format!("{KEY_PREFIX}:{class}:{key}"))
+            .arg(limit.period_ms)
+            .arg(limit.burst)
+            .invoke_async(&mut connection)
+            .await;
+        let (allowed, remaining, reset_ms, retry_ms) = reply.map_err(|e| {
+            self.mark_unavailable();
+            AppError::ServiceUnavailable(format!("Rate limit backend failed: {e}"))
+        })?;
+        Ok(RateLimitState {
+            limit: limit.burst,
+            remaining: u32::try_from(remaining.max(0)).unwrap_or(u32::MAX),
+            reset_secs: ceil_secs(reset_ms),
+            retry_after_secs: (allowed == 0).then(|| ceil_secs(retry_ms).max(1)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_invalid_url() {
+        assert!(matches!(
+            RedisLimiter::new("not a url"),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn test_unreachable_backend_fails_fast() {
+        //Nothing listens on port 1, so the first check fails and later ones skip the backend
+        let limiter = RedisLimiter::new("redis://127.0.0.1:1").unwrap();
+        let limit = RouteLimit::default();
+        assert!(limiter.check("reads", "key:a", limit).await.is_err());
+        let started = Instant::now();
+        assert!(matches!(
+            limiter.check("reads", "key:a", limit).await,
+            Err(AppError::ServiceUnavailable(_))
+        ));
+        assert!(started.elapsed() < CONNECT_TIMEOUT);
+    }
+}
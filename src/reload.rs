@@ -0,0 +1,448 @@
+use actix_web::web;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::rate_limit::RequestLimiter;
+use crate::redaction::Redactor;
+use crate::schema::SchemaRegistry;
+use crate::storage::InMemoryEventStore;
+use crate::tenant::TenantConfig;
+
+//Services whose settings can change while the server keeps running
+pub struct ReloadTargets {
+    pub limiter: web::Data<RequestLimiter>,
+    pub store: Arc<InMemoryEventStore>,
+    pub schemas: web::Data<SchemaRegistry>,
+    pub redactor: web::Data<Redactor>,
+    //Absent when logging was not set up from a log4rs file, e.g. in tests
+    pub logger: Option<log4rs::Handle>,
+}
+
+//Produces the current configuration and the config file it came from, if any
+pub type ConfigLoader = Box<dyn Fn() -> Result<(Config, Option<String>), AppError> + Send + Sync>;
+
+struct Applied {
+    config: Config,
+    config_path: Option<String>,
+    modified: HashMap<String, Option<SystemTime>>,
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+//The config file and every file it points to that holds reloadable settings
+fn watched_files(
+    config: &Config,
+    config_path: Option<&String>,
+) -> HashMap<String, Option<SystemTime>> {
+    [
+        config_path,
+        Some(&config.server.log_config),
+        config.store.tenants_file.as_ref(),
+        config.ingest.schemas_file.as_ref(),
+        config.ingest.redaction_rules_file.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|path| (path.clone(), modified(path)))
+    .collect()
+}
+
+//Re-reads the configuration on demand (SIGHUP) or when a watched file changes, and applies rate
+//limits, tenant quotas and retention, schemas, redaction rules and log levels in place.  Events,
+//open connections and rate-limit buckets whose settings did not change are untouched.  Every
+//component is reloaded on its own: one whose file is broken keeps its previous settings.
+pub struct ConfigReloader {
+    load: ConfigLoader,
+    targets: ReloadTargets,
+    applied: Mutex<Applied>,
+}
+
+impl ConfigReloader {
+    #[must_use]
+    pub fn new(
+        load: ConfigLoader,
+        targets: ReloadTargets,
+        config: Config,
+        config_path: Option<String>,
+    ) -> Self {
+        let modified = watched_files(&config, config_path.as_ref());
+        Self {
+            load,
+            targets,
+            applied: Mutex::new(Applied {
+                config,
+                config_path,
+                modified,
+            }),
+        }
+    }
+
+    //Reloads if any watched file changed since the last reload; returns what was reloaded
+    pub fn poll(&self) -> Result<Vec<&'static str>, AppError> {
+        let changed = {
+            let applied = self
+                .applied
+                .lock()
+                .map_err(|e| AppError::InternalError(e.to_string()))?;
+            applied
+                .modified
+                .iter()
+                .any(|(path, stamp)| modified(path) != *stamp)
+        };
+        if changed {
+            self.reload(false)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    //Loads the configuration and applies whatever changed.  With `force`, file-backed settings
+    //are re-read even if their files look unchanged.
+    pub fn reload(&self, force: bool) -> Result<Vec<&'static str>, AppError> {
+        let mut applied = self
+            .applied
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let (config, config_path) = match (self.load)() {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                //Remember the broken files so they are not re-read until they change again
+                applied.modified = watched_files(&applied.config, applied.config_path.as_ref());
+                return Err(e);
+            }
+        };
+        let modified = watched_files(&config, config_path.as_ref());
+        let changed = |new: Option<&String>, old: Option<&String>| {
+            force
+                || new != old
+                || new.is_some_and(|path| modified.get(path) != applied.modified.get(path))
+        };
+        let previous = &applied.config;
+        let mut reloaded = Vec::new();
+        let mut failures = Vec::new();
+
+        match self.targets.limiter.reload(&config.rate_limit) {
+            Ok(true) => reloaded.push("rate limits"),
+            Ok(false) => {}
+            Err(e) => failures.push(format!("rate limits: {e}")),
+        }
+        if changed(
+            config.store.tenants_file.as_ref(),
+            previous.store.tenants_file.as_ref(),
+        ) {
+            let limits = match &config.store.tenants_file {
+                Some(path) => TenantConfig::load(path),
+                None => Ok(TenantConfig::default()),
+            };
+            match limits.and_then(|limits| self.targets.store.set_tenant_limits(limits)) {
+                Ok(()) => reloaded.push("tenant limits"),
+                Err(e) => failures.push(format!("tenant limits: {e}")),
+            }
+        }
+        //A file owns its schemas or redaction rules, and the admin API refuses changes to them.
+        //Once the file is dropped from the config, the current ones are kept and become editable.
+        match &config.ingest.schemas_file {
+            Some(path) if changed(Some(path), previous.ingest.schemas_file.as_ref()) => {
+                match self.targets.schemas.load_file(path) {
+                    Ok(()) => reloaded.push("schemas"),
+                    Err(e) => failures.push(format!("schemas: {e}")),
+                }
+            }
+            Some(_) => {}
+            None => self.targets.schemas.release_file()?,
+        }
+        match &config.ingest.redaction_rules_file {
+            Some(path) if changed(Some(path), previous.ingest.redaction_rules_file.as_ref()) => {
+                match self.targets.redactor.load_file(path) {
+                    Ok(()) => reloaded.push("redaction rules"),
+                    Err(e) => failures.push(format!("redaction rules: {e}")),
+                }
+            }
+            Some(_) => {}
+            None => self.targets.redactor.release_file()?,
+        }
+        if let Some(logger) = &self.targets.logger {
+            if changed(
+                Some(&config.server.log_config),
+                Some(&previous.server.log_config),
+            ) {
+                match log4rs::config::load_config_file(
+                    &config.server.log_config,
+                    log4rs::config::Deserializers::default(),
+                ) {
+                    Ok(log_config) => {
+                        logger.set_config(log_config);
+                        reloaded.push("log config");
+                    }
+                    Err(e) => failures.push(format!("log config: {e}")),
+                }
+            }
+        }
+
+        if needs_restart(previous, &config) {
+            warn!("Some changed settings only take effect after a restart");
+        }
+        if !reloaded.is_empty() {
+            info!("Reloaded {}", reloaded.join(", "));
+        }
+        applied.config = config;
+        applied.config_path = config_path;
+        applied.modified = modified;
+
+        if failures.is_empty() {
+            Ok(reloaded)
+        } else {
+            Err(AppError::BadRequest(format!(
+                "Failed to reload {}",
+                failures.join("; ")
+            )))
+        }
+    }
+}
+
+//True when settings that are only read at startup differ
+fn needs_restart(previous: &Config, config: &Config) -> bool {
+    let mut startup_only = config.clone();
+    startup_only.rate_limit = previous.rate_limit.clone();
+    startup_only
+        .server
+        .log_config
+        .clone_from(&previous.server.log_config);
+    startup_only
+        .store
+        .tenants_file
+        .clone_from(&previous.store.tenants_file);
+    startup_only
+        .ingest
+        .schemas_file
+        .clone_from(&previous.ingest.schemas_file);
+    startup_only
+        .ingest
+        .redaction_rules_file
+        .clone_from(&previous.ingest.redaction_rules_file);
+    startup_only != *previous
+}
+
+//Checks the watched files every `interval` until the server stops
+pub async fn watch_files(reloader: Arc<ConfigReloader>, interval: Duration) {
+    let mut ticks = actix_web::rt::time::interval(interval);
+    loop {
+        ticks.tick().await;
+        if let Err(e) = reloader.poll() {
+            warn!("Keeping previous settings: {}", e);
+        }
+    }
+}
+
+//Reloads everything on SIGHUP until the server stops
+#[cfg(unix)]
+pub async fn reload_on_sighup(reloader: Arc<ConfigReloader>) -> std::io::Result<()> {
+    let mut hangups = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    while hangups.recv().await.is_some() {
+        info!("Received SIGHUP, reloading configuration");
+        if let Err(e) = reloader.reload(true) {
+            warn!("Keeping previous settings: {}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limit::RateLimitConfig;
+    use crate::redaction::RedactionRule;
+    use serde_json::json;
+
+    struct Fixture {
+        dir: std::path::PathBuf,
+        reloader: ConfigReloader,
+        limiter: web::Data<RequestLimiter>,
+        store: Arc<InMemoryEventStore>,
+        schemas: web::Data<SchemaRegistry>,
+        redactor: web::Data<Redactor>,
+    }
+
+    impl Fixture {
+        fn path(&self, name: &str) -> String {
+            self.dir.join(name).to_string_lossy().into_owned()
+        }
+
+        //Rewrites a file with a modification time that differs from any earlier write
+        fn write(&self, name: &str, contents: &str) {
+            let path = self.path(name);
+            let stamp = modified(&path).map_or(SystemTime::now(), |m| m + Duration::from_secs(5));
+            std::fs::write(&path, contents).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(stamp).unwrap();
+        }
+    }
+
+    fn fixture() -> Fixture {
+        let dir = std::env::temp_dir().join(format!("reload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir
+            .join("event-tracker.toml")
+            .to_string_lossy()
+            .into_owned();
+        let config = Config::default();
+        let limiter = web::Data::new(RequestLimiter::new(&config.rate_limit).unwrap());
+        let store = Arc::new(InMemoryEventStore::new());
+        let schemas = web::Data::new(SchemaRegistry::new());
+        let redactor = web::Data::new(Redactor::new(RedactionRule::builtins()).unwrap());
+        let path = config_path.clone();
+        let load: ConfigLoader = Box::new(move || {
+            let config = Config::from_file(Some(&path))?;
+            config.validate()?;
+            Ok((config, Some(path.clone())))
+        });
+        let fixture = Fixture {
+            dir,
+            reloader: ConfigReloader::new(
+                load,
+                ReloadTargets {
+                    limiter: limiter.clone(),
+                    store: store.clone(),
+                    schemas: schemas.clone(),
+                    redactor: redactor.clone(),
+                    logger: None,
+                },
+                config,
+                Some(config_path),
+            ),
+            limiter,
+            store,
+            schemas,
+            redactor,
+        };
+        fixture.write("event-tracker.toml", "");
+        fixture
+    }
+
+    #[test]
+    fn test_changed_files_are_reloaded_in_place() {
+        let f = fixture();
+        assert_eq!(f.reloader.poll().unwrap(), Vec::<&str>::new());
+
+        f.write(
+            "tenants.json",
+            &json!({ "default": { "retention_secs": 60 } }).to_string(),
+        );
+        f.write(
+            "schemas.json",
+            &json!({ "login": { "required": ["user_id"] } }).to_string(),
+        );
+        f.write(
+            "redaction.json",
+            &json!([{ "kind": "path", "path": "email" }]).to_string(),
+        );
+        f.write(
+            "event-tracker.toml",
+            &format!(
+                "[store]\ntenants_file = {:?}\n[ingest]\nschemas_file = {:?}\nredaction_rules_file = {:?}\n[rate_limit.writes]\nburst = 3\n",
+                f.path("tenants.json"),
+                f.path("schemas.json"),
+                f.path("redaction.json")
+            ),
+        );
+        let reloaded = f.reloader.poll().unwrap();
+        assert_eq!(
+            reloaded,
+            vec!["rate limits", "tenant limits", "schemas", "redaction rules"]
+        );
+        assert!(f.store.tenant_limits().unwrap().has_retention());
+        assert!(f.schemas.get("login").unwrap().is_some());
+        assert_eq!(
+            f.redactor.rules().unwrap(),
+            vec![RedactionRule::Path {
+                path: "email".into()
+            }]
+        );
+        assert!(!f
+            .limiter
+            .reload(&RateLimitConfig {
+                writes: crate::rate_limit::RouteLimit {
+                    period_ms: 5000,
+                    burst: 3
+                },
+                ..Default::default()
+            })
+            .unwrap());
+
+        //Only the file that changed is re-read
+        f.write("schemas.json", &json!({}).to_string());
+        assert_eq!(f.reloader.poll().unwrap(), vec!["schemas"]);
+        assert!(f.schemas.get("login").unwrap().is_none());
+        std::fs::remove_dir_all(&f.dir).unwrap();
+    }
+
+    #[test]
+    fn test_files_own_schemas_and_redaction_rules() {
+        let f = fixture();
+        f.schemas.register("signup", json!({})).unwrap();
+        f.write("schemas.json", &json!({ "login": {} }).to_string());
+        f.write("redaction.json", &json!([]).to_string());
+        f.write(
+            "event-tracker.toml",
+            &format!(
+                "[ingest]\nschemas_file = {:?}\nredaction_rules_file = {:?}\n",
+                f.path("schemas.json"),
+                f.path("redaction.json")
+            ),
+        );
+        f.reloader.poll().unwrap();
+
+        //The file wins: what was registered at runtime is gone, and further changes are refused
+        assert!(f.schemas.get("signup").unwrap().is_none());
+        assert!(matches!(
+            f.schemas.register("signup", json!({})),
+            Err(AppError::Conflict(_))
+        ));
+        assert!(matches!(
+            f.redactor.set_rules(RedactionRule::builtins()),
+            Err(AppError::Conflict(_))
+        ));
+        f.reloader.reload(true).unwrap();
+        assert_eq!(f.schemas.list().unwrap().len(), 1);
+
+        //Without the files the current settings stay and the admin API takes over again
+        f.write("event-tracker.toml", "");
+        f.reloader.poll().unwrap();
+        assert!(f.schemas.get("login").unwrap().is_some());
+        f.schemas.register("signup", json!({})).unwrap();
+        f.redactor.set_rules(RedactionRule::builtins()).unwrap();
+        std::fs::remove_dir_all(&f.dir).unwrap();
+    }
+
+    #[test]
+    fn test_broken_files_keep_previous_settings() {
+        let f = fixture();
+        f.write(
+            "schemas.json",
+            &json!({ "login": { "type": "object" } }).to_string(),
+        );
+        f.write(
+            "event-tracker.toml",
+            &format!("[ingest]\nschemas_file = {:?}\n", f.path("schemas.json")),
+        );
+        f.reloader.poll().unwrap();
+
+        f.write("schemas.json", "{ not json");
+        assert!(f.reloader.poll().is_err());
+        assert!(f.schemas.get("login").unwrap().is_some());
+        //The broken file is not re-read until it changes again
+        assert_eq!(f.reloader.poll().unwrap(), Vec::<&str>::new());
+
+        //An invalid config file changes nothing at all
+        f.write("event-tracker.toml", "[rate_limit.writes]\nburst = 0\n");
+        assert!(f.reloader.reload(true).is_err());
+        assert!(f.schemas.get("login").unwrap().is_some());
+        std::fs::remove_dir_all(&f.dir).unwrap();
+    }
+}
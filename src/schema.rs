@@ -0,0 +1,299 @@
+use jsonschema::error::ValidationErrorKind;
+use jsonschema::{ValidationError, Validator};
+use log::debug;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use crate::error::AppError;
+
+struct RegisteredSchema {
+    schema: Value,
+    validator: Arc<Validator>,
+}
+
+fn compile(event_type: &str, schema: &Value) -> Result<Arc<Validator>, AppError> {
+    jsonschema::validator_for(schema)
+        .map(Arc::new)
+        .map_err(|e| {
+            AppError::BadRequest(format!("Invalid schema for event type '{event_type}': {e}"))
+        })
+}
+
+//JSON Schemas keyed by event_type.  Event types without a registered schema are not validated.
+//Once loaded from a file, the file is the only source: runtime changes are refused so a reload
+//cannot silently discard them.
+#[derive(Default)]
+pub struct SchemaRegistry {
+    schemas: RwLock<HashMap<String, RegisteredSchema>>,
+    file: RwLock<Option<String>>,
+}
+
+impl SchemaRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //Reads a JSON object of event_type -> schema, as used by the SCHEMAS_FILE config file
+    pub fn read_file(path: &str) -> Result<BTreeMap<String, Value>, AppError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| AppError::InternalError(format!("Failed to read {path}: {e}")))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| AppError::BadRequest(format!("Invalid schemas in {path}: {e}")))
+    }
+
+    //Replaces every registered schema.  All schemas are compiled first, so one invalid schema
+    //leaves the registry unchanged.
+    pub fn replace(&self, schemas: BTreeMap<String, Value>) -> Result<(), AppError> {
+        let mut replacement = HashMap::new();
+        for (event_type, schema) in schemas {
+            let validator = compile(&event_type, &schema)?;
+            replacement.insert(event_type, RegisteredSchema { schema, validator });
+        }
+        *self
+            .schemas
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))? = replacement;
+        Ok(())
+    }
+
+    //Replaces every schema with the contents of a SCHEMAS_FILE and makes that file their owner
+    pub fn load_file(&self, path: &str) -> Result<(), AppError> {
+        self.replace(Self::read_file(path)?)?;
+        *self
+            .file
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))? = Some(path.to_string());
+        Ok(())
+    }
+
+    //Keeps the current schemas but hands them back to the admin API
+    pub fn release_file(&self) -> Result<(), AppError> {
+        *self
+            .file
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))? = None;
+        Ok(())
+    }
+
+    fn check_editable(&self) -> Result<(), AppError> {
+        let file = self
+            .file
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        match file.as_deref() {
+            Some(path) => Err(AppError::Conflict(format!(
+                "Schemas are managed by {path}; edit that file instead"
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    //Compiles and stores the schema, replacing any previous one for the event type
+    pub fn register(&self, event_type: &str, schema: Value) -> Result<(), AppError> {
+        self.check_editable()?;
+        let validator = compile(event_type, &schema)?;
+        let mut schemas = self
+            .schemas
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        schemas.insert(
+            event_type.to_string(),
+            RegisteredSchema { schema, validator },
+        );
+        Ok(())
+    }
+
+    pub fn get(&self, event_type: &str) -> Result<Option<Value>, AppError> {
+        let schemas = self
+            .schemas
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(schemas.get(event_type).map(|s| s.schema.clone()))
+    }
+
+    pub fn list(&self) -> Result<BTreeMap<String, Value>, AppError> {
+        let schemas = self
+            .schemas
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(schemas
+            .iter()
+            .map(|(event_type, s)| (event_type.clone(), s.schema.clone()))
+            .collect())
+    }
+
+    pub fn remove(&self, event_type: &str) -> Result<bool, AppError> {
+        self.check_editable()?;
+        let mut schemas = self
+            .schemas
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(schemas.remove(event_type).is_some())
+    }
+
+    //Checks a payload against the schema for its event type.  Every violation is reported with
+    //the JSON pointer of the offending value and the schema keyword it fails, but never the value
+    //itself: the payload is not redacted yet, and rejections are logged.
+    pub fn validate(&self, event_type: &str, payload: &Value) -> Result<(), AppError> {
+        let validator = {
+            let schemas = self
+                .schemas
+                .read()
+                .map_err(|e| AppError::InternalError(e.to_string()))?;
+            match schemas.get(event_type) {
+                Some(registered) => Arc::clone(&registered.validator),
+                None => return Ok(()),
+            }
+        };
+
+        let violations: Vec<String> = validator
+            .iter_errors(payload)
+            .map(|error| format!("'{}': {}", error.instance_path, describe(&error)))
+            .collect();
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        debug!(
+            "Payload for event type {} has {} schema violation(s)",
+            event_type,
+            violations.len()
+        );
+        Err(AppError::BadRequest(format!(
+            "Payload does not match the schema for event type '{}': {}",
+            event_type,
+            violations.join("; ")
+        )))
+    }
+}
+
+//What a violation breaks, in terms of the schema only
+fn describe(error: &ValidationError) -> String {
+    match &error.kind {
+        ValidationErrorKind::Required { property } => format!("{property} is a required property"),
+        _ => format!("does not satisfy '{}'", error.schema_path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn login_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["user_id"],
+            "properties": {
+                "user_id": { "type": "integer" },
+                "device": {
+                    "type": "object",
+                    "properties": { "os": { "type": "string" } }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_reports_every_violation_with_pointer() {
+        let registry = SchemaRegistry::new();
+        registry.register("login", login_schema()).unwrap();
+
+        let result = registry.validate("login", &json!({ "device": { "os": 10 } }));
+
+        let Err(AppError::BadRequest(message)) = result else {
+            panic!("expected BadRequest, got {result:?}");
+        };
+        assert!(message.contains("'': \"user_id\" is a required property"));
+        assert!(message
+            .contains("'/device/os': does not satisfy '/properties/device/properties/os/type'"));
+    }
+
+    #[test]
+    fn test_violations_do_not_echo_payload_values() {
+        let registry = SchemaRegistry::new();
+        registry
+            .register(
+                "signup",
+                json!({ "properties": { "email": { "type": "string", "maxLength": 5 } } }),
+            )
+            .unwrap();
+
+        let result = registry.validate("signup", &json!({ "email": "jo@example.com" }));
+
+        let Err(AppError::BadRequest(message)) = result else {
+            panic!("expected BadRequest, got {result:?}");
+        };
+        assert!(message.contains("'/email': does not satisfy '/properties/email/maxLength'"));
+        assert!(!message.contains("jo@example.com"));
+    }
+
+    #[test]
+    fn test_replace_is_all_or_nothing() {
+        let registry = SchemaRegistry::new();
+        registry.register("login", login_schema()).unwrap();
+
+        let invalid = BTreeMap::from([
+            ("logout".to_string(), json!({ "type": "object" })),
+            ("signup".to_string(), json!({ "type": 12 })),
+        ]);
+        assert!(registry.replace(invalid).is_err());
+        assert!(registry.get("login").unwrap().is_some());
+
+        let valid = BTreeMap::from([("logout".to_string(), json!({ "type": "object" }))]);
+        registry.replace(valid).unwrap();
+        assert!(registry.get("login").unwrap().is_none());
+        assert!(registry.validate("logout", &json!([])).is_err());
+    }
+
+    #[test]
+    fn test_file_backed_schemas_refuse_runtime_changes() {
+        let path = std::env::temp_dir().join(format!("schemas-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, json!({ "login": login_schema() }).to_string()).unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let registry = SchemaRegistry::new();
+        registry.load_file(&path).unwrap();
+
+        assert!(matches!(
+            registry.register("logout", json!({})),
+            Err(AppError::Conflict(_))
+        ));
+        assert!(matches!(
+            registry.remove("login"),
+            Err(AppError::Conflict(_))
+        ));
+
+        registry.release_file().unwrap();
+        registry.register("logout", json!({})).unwrap();
+        assert_eq!(registry.list().unwrap().len(), 2);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_validate_passes_conforming_and_unregistered() {
+        let registry = SchemaRegistry::new();
+        registry.register("login", login_schema()).unwrap();
+
+        assert!(registry.validate("login", &json!({ "user_id": 1 })).is_ok());
+        assert!(registry.validate("logout", &json!("anything")).is_ok());
+    }
+
+    #[test]
+    fn test_register_rejects_invalid_schema() {
+        let registry = SchemaRegistry::new();
+        let result = registry.register("login", json!({ "type": 12 }));
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        assert!(registry.get("login").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_remove() {
+        let registry = SchemaRegistry::new();
+        registry.register("login", login_schema()).unwrap();
+        assert!(registry.remove("login").unwrap());
+        assert!(!registry.remove("login").unwrap());
+        assert!(registry.list().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::model::Event;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockSkewAction {
+    //Refuse the event with a 400
+    #[default]
+    Reject,
+    //Store the event but set clock_skewed so it can be told apart
+    Flag,
+}
+
+//How far an event's client timestamp may drift from the server's received_at.  Either bound
+//can be left unset to disable that side of the check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ClockSkewPolicy {
+    pub max_future: Option<Duration>,
+    pub max_past: Option<Duration>,
+    pub action: ClockSkewAction,
+}
+
+impl ClockSkewPolicy {
+    //Describes how the timestamp is out of bounds, or None when it is acceptable
+    fn violation(&self, timestamp: DateTime<Utc>, received_at: DateTime<Utc>) -> Option<String> {
+        let skew = timestamp - received_at;
+        if let Some(max_future) = self.max_future {
+            if skew > max_future {
+                return Some(format!(
+                    "{}s in the future (limit {}s)",
+                    skew.num_seconds(),
+                    max_future.num_seconds()
+                ));
+            }
+        }
+        if let Some(max_past) = self.max_past {
+            if -skew > max_past {
+                return Some(format!(
+                    "{}s in the past (limit {}s)",
+                    (-skew).num_seconds(),
+                    max_past.num_seconds()
+                ));
+            }
+        }
+        None
+    }
+
+    pub fn apply(&self, event: &mut Event) -> Result<(), AppError> {
+        let Some(violation) = self.violation(event.timestamp, event.received_at) else {
+            return Ok(());
+        };
+        match self.action {
+            ClockSkewAction::Reject => Err(AppError::BadRequest(format!(
+                "Event timestamp {} is {}",
+                event.timestamp.to_rfc3339(),
+                violation
+            ))),
+            ClockSkewAction::Flag => {
+                warn!("Event {} timestamp is {}", event.id, violation);
+                event.clock_skewed = true;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::NewEvent;
+    use serde_json::json;
+
+    fn event_at(offset: Duration) -> Event {
+        let mut event = NewEvent {
+            id: None,
+            event_type: "login".into(),
+            timestamp: None,
+            payload: json!({}),
+            schema_version: 1,
+            metadata: Default::default(),
+        }
+        .into_event();
+        event.timestamp = event.received_at + offset;
+        event
+    }
+
+    fn policy(action: ClockSkewAction) -> ClockSkewPolicy {
+        ClockSkewPolicy {
+            max_future: Some(Duration::minutes(5)),
+            max_past: Some(Duration::days(7)),
+            action,
+        }
+    }
+
+    #[test]
+    fn test_reject_outside_bounds() {
+        let policy = policy(ClockSkewAction::Reject);
+        assert!(policy.apply(&mut event_at(Duration::minutes(1))).is_ok());
+        assert!(policy.apply(&mut event_at(-Duration::days(1))).is_ok());
+        assert!(matches!(
+            policy.apply(&mut event_at(Duration::days(365))),
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(matches!(
+            policy.apply(&mut event_at(-Duration::days(30))),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_flag_marks_event_and_accepts() {
+        let policy = policy(ClockSkewAction::Flag);
+        let mut future = event_at(Duration::hours(2));
+        policy.apply(&mut future).unwrap();
+        assert!(future.clock_skewed);
+
+        let mut on_time = event_at(Duration::zero());
+        policy.apply(&mut on_time).unwrap();
+        assert!(!on_time.clock_skewed);
+    }
+
+    #[test]
+    fn test_unset_bounds_accept_anything() {
+        let policy = ClockSkewPolicy::default();
+        let mut event = event_at(Duration::days(3650));
+        policy.apply(&mut event).unwrap();
+        assert!(!event.clock_skewed);
+    }
+}
@@ -1,27 +1,268 @@
-use log::{debug, info};
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::RwLock;
 use uuid::Uuid;
 
 use crate::error::AppError;
-use crate::model::{Event, EventQuery};
+use crate::model::{
+    Event, EventQuery, StoreStats, StoreStatus, StreamEvents, StreamPosition, TypedEvent,
+    TypedRecord,
+};
+use crate::tenant::TenantConfig;
+use crate::upcast::UpcasterRegistry;
 
 //Trait implementation that all other storage implementations use
 //Web api accepts any Struct/Object that implements this trait
 //can expand as needed
+//Every event belongs to exactly one tenant; ids, streams and queries never cross tenants
 pub trait EventStore: Send + Sync {
-    fn add_event(&self, event: Event) -> Result<(), AppError>;
-    fn query_events(&self, query: EventQuery) -> Result<Vec<Event>, AppError>;
-    fn get_by_id(&self, id: Uuid) -> Result<Option<Event>, AppError>;
+    //Stores a new event.  Ids are write-once: fails with Conflict if the id is already stored.
+    fn add_event(&self, tenant: &str, event: Event) -> Result<(), AppError>;
+    fn query_events(&self, tenant: &str, query: EventQuery) -> Result<Vec<Event>, AppError>;
+    fn get_by_id(&self, tenant: &str, id: Uuid) -> Result<Option<Event>, AppError>;
+    fn update_event(&self, tenant: &str, event: Event) -> Result<bool, AppError>;
+    //Replaces an event like update_event but without the byte quota check, so an erasure can
+    //always overwrite identifying values, even for a tenant that is at its quota
+    fn rewrite_event(&self, tenant: &str, event: Event) -> Result<bool, AppError>;
+    fn delete(&self, tenant: &str, id: Uuid) -> Result<usize, AppError>;
+    fn delete_matching(&self, tenant: &str, query: EventQuery) -> Result<usize, AppError>;
+    //Stats for one tenant, or for the whole store when tenant is None
+    fn stats(&self, tenant: Option<&str>) -> Result<StoreStats, AppError>;
+    fn status(&self) -> Result<StoreStatus, AppError>;
+    //Tenants that have stored at least one event
+    fn tenants(&self) -> Result<Vec<String>, AppError>;
+    //Appends events to a stream atomically, assigning consecutive versions.  Fails with Conflict
+    //when expected_version is set and differs from the stream's current version.
+    fn append_to_stream(
+        &self,
+        tenant: &str,
+        stream_id: &str,
+        expected_version: Option<u64>,
+        events: Vec<Event>,
+    ) -> Result<StreamEvents, AppError>;
+    //Events of a stream from `from_version` onwards in version order, or None for an unknown stream
+    fn read_stream(
+        &self,
+        tenant: &str,
+        stream_id: &str,
+        from_version: u64,
+    ) -> Result<Option<StreamEvents>, AppError>;
+}
+
+//Typed helpers available on every EventStore (including Arc<dyn EventStore>) for Rust services
+//working with TypedEvent payloads
+pub trait TypedEventStore {
+    fn add_typed<T: TypedEvent>(
+        &self,
+        tenant: &str,
+        data: &T,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Event, AppError>;
+    //With `upcasters`, older events are brought up to the newest schema version before decoding
+    fn get_typed<T: TypedEvent>(
+        &self,
+        tenant: &str,
+        id: Uuid,
+        upcasters: Option<&UpcasterRegistry>,
+    ) -> Result<Option<TypedRecord<T>>, AppError>;
+    //Events of T's type that decode as T, upcast first as in get_typed.  Records that do not
+    //decode (e.g. a schema_version with no upcaster path or a malformed payload) are logged and
+    //skipped rather than failing the query.
+    fn query_typed<T: TypedEvent>(
+        &self,
+        tenant: &str,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        upcasters: Option<&UpcasterRegistry>,
+    ) -> Result<Vec<TypedRecord<T>>, AppError>;
+}
+
+impl<S: EventStore + ?Sized> TypedEventStore for S {
+    fn add_typed<T: TypedEvent>(
+        &self,
+        tenant: &str,
+        data: &T,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Event, AppError> {
+        let event = data.to_event(timestamp)?;
+        self.add_event(tenant, event.clone())?;
+        Ok(event)
+    }
+
+    fn get_typed<T: TypedEvent>(
+        &self,
+        tenant: &str,
+        id: Uuid,
+        upcasters: Option<&UpcasterRegistry>,
+    ) -> Result<Option<TypedRecord<T>>, AppError> {
+        self.get_by_id(tenant, id)?
+            .map(|event| TypedRecord::try_from(&upcast(upcasters, event)?))
+            .transpose()
+    }
+
+    fn query_typed<T: TypedEvent>(
+        &self,
+        tenant: &str,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        upcasters: Option<&UpcasterRegistry>,
+    ) -> Result<Vec<TypedRecord<T>>, AppError> {
+        let events = self.query_events(
+            tenant,
+            EventQuery {
+                event_type: Some(T::EVENT_TYPE.to_string()),
+                start,
+                end,
+                ..Default::default()
+            },
+        )?;
+        let events = match upcasters {
+            Some(upcasters) => upcasters.upcast_all(events)?,
+            None => events,
+        };
+        events
+            .iter()
+            .filter_map(|event| match TypedRecord::try_from(event) {
+                Ok(record) => Some(Ok(record)),
+                Err(AppError::BadRequest(e)) => {
+                    warn!("Skipping event {} in typed query: {}", event.id, e);
+                    None
+                }
+                Err(e) => Some(Err(e)),
+            })
+            .collect()
+    }
+}
+
+fn upcast(upcasters: Option<&UpcasterRegistry>, event: Event) -> Result<Event, AppError> {
+    match upcasters {
+        Some(upcasters) => upcasters.upcast(event),
+        None => Ok(event),
+    }
 }
 
 //Initial Struct and implementation for in-memory storage of events.  Also can continue to be used for testing
 //Guarded with a RwLock--Reads could be many, writes should be few
 #[derive(Default)]
 pub struct InMemoryEventStore {
-    events: RwLock<HashMap<Uuid, Event>>,
+    events: RwLock<HashMap<String, EventMap>>,
     count: AtomicUsize,
+    bytes: AtomicUsize,
+    capacity_bytes: Option<usize>,
+    //Behind a lock so quotas and retention can be reloaded without dropping events
+    tenant_limits: RwLock<TenantConfig>,
+}
+
+//One tenant's events keyed by id, plus a secondary index from each metadata (field, value) pair
+//to the ids of the events carrying it.  All mutation goes through insert/remove to keep the
+//index and byte count in step.
+#[derive(Default)]
+struct EventMap {
+    by_id: HashMap<Uuid, Event>,
+    by_metadata: HashMap<(&'static str, String), HashSet<Uuid>>,
+    streams: HashMap<String, Stream>,
+    bytes: usize,
+    received: usize,
+}
+
+//Stream versions keep counting after events are deleted, so expected_version stays meaningful
+#[derive(Default)]
+struct Stream {
+    version: u64,
+    events: BTreeMap<u64, Uuid>,
+}
+
+impl EventMap {
+    fn len(&self) -> usize {
+        self.by_id.len()
+    }
+
+    fn get(&self, id: &Uuid) -> Option<&Event> {
+        self.by_id.get(id)
+    }
+
+    //Returns the event previously stored under the same id, if any
+    fn insert(&mut self, event: Event) -> Option<Event> {
+        let replaced = self.remove(&event.id);
+        for (field, value) in event.metadata.fields() {
+            self.by_metadata
+                .entry((field, value.to_string()))
+                .or_default()
+                .insert(event.id);
+        }
+        if let Some(position) = &event.stream {
+            let stream = self.streams.entry(position.stream_id.clone()).or_default();
+            stream.events.insert(position.version, event.id);
+            stream.version = stream.version.max(position.version);
+        }
+        self.bytes += entry_size(&event);
+        self.by_id.insert(event.id, event);
+        replaced
+    }
+
+    fn remove(&mut self, id: &Uuid) -> Option<Event> {
+        let removed = self.by_id.remove(id)?;
+        self.bytes -= entry_size(&removed);
+        if let Some(position) = &removed.stream {
+            if let Some(stream) = self.streams.get_mut(&position.stream_id) {
+                stream.events.remove(&position.version);
+            }
+        }
+        for (field, value) in removed.metadata.fields() {
+            let key = (field, value.to_string());
+            if let Some(ids) = self.by_metadata.get_mut(&key) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.by_metadata.remove(&key);
+                }
+            }
+        }
+        Some(removed)
+    }
+
+    fn stream_events(&self, stream_id: &str, from_version: u64) -> Option<StreamEvents> {
+        let stream = self.streams.get(stream_id)?;
+        Some(StreamEvents {
+            stream_id: stream_id.to_string(),
+            version: stream.version,
+            events: stream
+                .events
+                .range(from_version..)
+                .filter_map(|(_, id)| self.by_id.get(id).cloned())
+                .collect(),
+        })
+    }
+
+    //Uses the smallest metadata index set when the query filters on metadata, otherwise scans
+    fn matching<'a>(&'a self, query: &'a EventQuery) -> Vec<&'a Event> {
+        let mut index_sets = Vec::new();
+        for (field, value) in query.metadata.fields() {
+            match self.by_metadata.get(&(field, value.to_string())) {
+                Some(ids) => index_sets.push(ids),
+                None => return Vec::new(),
+            }
+        }
+
+        match index_sets.into_iter().min_by_key(|ids| ids.len()) {
+            Some(ids) => ids
+                .iter()
+                .filter_map(|id| self.by_id.get(id))
+                .filter(|event| query.matches(event))
+                .collect(),
+            None => self
+                .by_id
+                .values()
+                .filter(|event| query.matches(event))
+                .collect(),
+        }
+    }
+}
+
+//Bytes a single entry occupies in the map: the key plus the deep size of the event
+fn entry_size(event: &Event) -> usize {
+    std::mem::size_of::<Uuid>() + event.deep_size()
 }
 
 impl InMemoryEventStore {
@@ -30,70 +271,430 @@ impl InMemoryEventStore {
         Self {
             events: RwLock::new(HashMap::new()),
             count: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+            capacity_bytes: None,
+            tenant_limits: RwLock::new(TenantConfig::default()),
         }
     }
 
+    //Memory budget in bytes; once usage reaches it the store reports OverCapacity
+    #[must_use]
+    pub fn with_capacity_bytes(mut self, capacity_bytes: usize) -> Self {
+        self.capacity_bytes = Some(capacity_bytes);
+        self
+    }
+
+    //Per-tenant event and byte quotas; writes that would exceed them fail with InsufficientStorage
+    #[must_use]
+    pub fn with_tenant_limits(mut self, tenant_limits: TenantConfig) -> Self {
+        self.tenant_limits = RwLock::new(tenant_limits);
+        self
+    }
+
+    pub fn tenant_limits(&self) -> Result<TenantConfig, AppError> {
+        let limits = self
+            .tenant_limits
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(limits.clone())
+    }
+
+    //Replaces quotas and retention in place; stored events are kept even if now over quota
+    pub fn set_tenant_limits(&self, tenant_limits: TenantConfig) -> Result<(), AppError> {
+        let mut limits = self
+            .tenant_limits
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        *limits = tenant_limits;
+        Ok(())
+    }
+
     pub fn metrics(&self) -> usize {
         self.count.load(Ordering::Relaxed)
     }
+
+    pub fn memory_usage(&self) -> usize {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    fn check_quota(
+        &self,
+        tenant: &str,
+        map: &EventMap,
+        new_events: &[Event],
+    ) -> Result<(), AppError> {
+        let config = self
+            .tenant_limits
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let limits = config.limits(tenant);
+        let added: usize = new_events.iter().map(entry_size).sum();
+        self.check_capacity(added)?;
+        if let Some(max_events) = limits.max_events {
+            if map.len() + new_events.len() > max_events {
+                return Err(AppError::InsufficientStorage(format!(
+                    "Tenant '{tenant}' is limited to {max_events} stored event(s)"
+                )));
+            }
+        }
+        if let Some(max_bytes) = limits.max_bytes {
+            if map.bytes + added > max_bytes {
+                return Err(AppError::InsufficientStorage(format!(
+                    "Tenant '{tenant}' is limited to {max_bytes} bytes of stored events"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    //The store-wide memory budget applies to every tenant; callers hold the events write lock,
+    //so `bytes` cannot change between the check and the write.
+    fn check_capacity(&self, added: usize) -> Result<(), AppError> {
+        if let Some(capacity) = self.capacity_bytes {
+            if self.memory_usage() + added > capacity {
+                return Err(AppError::InsufficientStorage(format!(
+                    "The store is limited to {capacity} bytes of stored events"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    //Quota check for replacing `replaced` with `event`.  Only growth is checked, so shrinking an
+    //event (e.g. anonymizing it) works even when the tenant is over a lowered quota.
+    fn check_update_quota(
+        &self,
+        tenant: &str,
+        map: &EventMap,
+        replaced: &Event,
+        event: &Event,
+    ) -> Result<(), AppError> {
+        let grown = entry_size(event).saturating_sub(entry_size(replaced));
+        if grown == 0 {
+            return Ok(());
+        }
+        self.check_capacity(grown)?;
+        let config = self
+            .tenant_limits
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        if let Some(max_bytes) = config.limits(tenant).max_bytes {
+            if map.bytes + grown > max_bytes {
+                return Err(AppError::InsufficientStorage(format!(
+                    "Tenant '{tenant}' is limited to {max_bytes} bytes of stored events"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl InMemoryEventStore {
+    fn replace(&self, tenant: &str, event: Event, enforce_quota: bool) -> Result<bool, AppError> {
+        let mut tenants = self
+            .events
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        debug!("Updating event with ID: {} for tenant {}", event.id, tenant);
+
+        let Some(events) = tenants.get_mut(tenant) else {
+            return Ok(false);
+        };
+        let Some(replaced) = events.get(&event.id) else {
+            return Ok(false);
+        };
+        if enforce_quota {
+            self.check_update_quota(tenant, events, replaced, &event)?;
+        }
+        let added = entry_size(&event);
+        if let Some(replaced) = events.insert(event) {
+            self.bytes
+                .fetch_sub(entry_size(&replaced), Ordering::Relaxed);
+        }
+        self.bytes.fetch_add(added, Ordering::Relaxed);
+        Ok(true)
+    }
 }
 
 impl EventStore for InMemoryEventStore {
-    fn add_event(&self, event: Event) -> Result<(), AppError> {
-        let mut events = self
+    fn add_event(&self, tenant: &str, event: Event) -> Result<(), AppError> {
+        let mut tenants = self
             .events
             .write()
             .map_err(|e| AppError::InternalError(e.to_string()))?;
-        debug!("Inserting event with ID: {}", event.id);
+        debug!(
+            "Inserting event with ID: {} for tenant {}",
+            event.id, tenant
+        );
 
-        events.insert(event.id, event);
+        //Checked under the write lock so concurrent writers of one id cannot both succeed.  A
+        //rejected write must not leave an entry behind for a tenant that has no events.
+        let empty = EventMap::default();
+        let existing = tenants.get(tenant).unwrap_or(&empty);
+        if existing.get(&event.id).is_some() {
+            return Err(AppError::Conflict(format!(
+                "Event {} already exists",
+                event.id
+            )));
+        }
+        self.check_quota(tenant, existing, std::slice::from_ref(&event))?;
+        let events = tenants.entry(tenant.to_string()).or_default();
+        let added = entry_size(&event);
+        events.insert(event);
+        events.received += 1;
+        let total_bytes = self.bytes.fetch_add(added, Ordering::Relaxed) + added;
         self.count.fetch_add(1, Ordering::Relaxed);
-        let current_count = events.len();
-        let estimated_event_size = std::mem::size_of::<Event>();
-        let estimated_total_bytes = current_count * estimated_event_size;
 
         info!(
-            "Current event count: {}, Estimated memory usage: {} bytes",
-            current_count, estimated_total_bytes
+            "Current event count for tenant {}: {}, Memory usage: {} bytes",
+            tenant,
+            events.len(),
+            total_bytes
         );
         Ok(())
     }
 
-    fn query_events(&self, query: EventQuery) -> Result<Vec<Event>, AppError> {
-        let events = self
+    fn query_events(&self, tenant: &str, query: EventQuery) -> Result<Vec<Event>, AppError> {
+        let tenants = self
             .events
             .read()
             .map_err(|e| AppError::InternalError(e.to_string()))?;
-        let result: Vec<Event> = events
-            .values()
-            .filter(|event| {
-                query
-                    .event_type
-                    .as_ref()
-                    .map_or(true, |t| &event.event_type == t)
-                    && query.start.map_or(true, |start| event.timestamp >= start)
-                    && query.end.map_or(true, |end| event.timestamp <= end)
-            })
-            .cloned()
-            .collect();
+        let result: Vec<Event> = tenants.get(tenant).map_or_else(Vec::new, |events| {
+            events.matching(&query).into_iter().cloned().collect()
+        });
 
         debug!(
-            "Query: type={:?}, start={:?}, end={:?} -> {} result(s)",
+            "Query: tenant={}, type={:?}, start={:?}, end={:?}, metadata={:?} -> {} result(s)",
+            tenant,
             query.event_type,
             query.start,
             query.end,
+            query.metadata,
             result.len()
         );
         Ok(result)
     }
 
-    fn get_by_id(&self, id: Uuid) -> Result<Option<Event>, AppError> {
-        debug!("Retrieving event with ID: {}", id);
-        let events = self
+    fn get_by_id(&self, tenant: &str, id: Uuid) -> Result<Option<Event>, AppError> {
+        debug!("Retrieving event with ID: {} for tenant {}", id, tenant);
+        let tenants = self
             .events
             .read()
             .map_err(|e| AppError::InternalError(e.to_string()))?;
-        Ok(events.get(&id).cloned())
+        Ok(tenants
+            .get(tenant)
+            .and_then(|events| events.get(&id))
+            .cloned())
+    }
+
+    //Replaces an existing event in place; does not count as a newly received event
+    fn update_event(&self, tenant: &str, event: Event) -> Result<bool, AppError> {
+        self.replace(tenant, event, true)
+    }
+
+    fn rewrite_event(&self, tenant: &str, event: Event) -> Result<bool, AppError> {
+        self.replace(tenant, event, false)
+    }
+
+    fn delete(&self, tenant: &str, id: Uuid) -> Result<usize, AppError> {
+        let mut tenants = self
+            .events
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        debug!("Deleting event with ID: {} for tenant {}", id, tenant);
+
+        let Some(events) = tenants.get_mut(tenant) else {
+            return Ok(0);
+        };
+        match events.remove(&id) {
+            Some(removed) => {
+                self.bytes
+                    .fetch_sub(entry_size(&removed), Ordering::Relaxed);
+                info!(
+                    "Deleted event {}, current event count for tenant {}: {}",
+                    id,
+                    tenant,
+                    events.len()
+                );
+                Ok(1)
+            }
+            None => Ok(0),
+        }
+    }
+
+    fn delete_matching(&self, tenant: &str, query: EventQuery) -> Result<usize, AppError> {
+        let mut tenants = self
+            .events
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let Some(events) = tenants.get_mut(tenant) else {
+            return Ok(0);
+        };
+
+        let ids: Vec<Uuid> = events
+            .matching(&query)
+            .into_iter()
+            .map(|event| event.id)
+            .collect();
+        let mut freed = 0;
+        for id in &ids {
+            if let Some(removed) = events.remove(id) {
+                freed += entry_size(&removed);
+            }
+        }
+        self.bytes.fetch_sub(freed, Ordering::Relaxed);
+
+        info!(
+            "Deleted {} event(s) of tenant {} matching type={:?}, start={:?}, end={:?}, metadata={:?}, received_before={:?}; current event count: {}",
+            ids.len(),
+            tenant,
+            query.event_type,
+            query.start,
+            query.end,
+            query.metadata,
+            query.received_before,
+            events.len()
+        );
+        Ok(ids.len())
+    }
+
+    fn stats(&self, tenant: Option<&str>) -> Result<StoreStats, AppError> {
+        let tenants = self
+            .events
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let Some(tenant) = tenant else {
+            return Ok(StoreStats {
+                event_count: tenants.values().map(EventMap::len).sum(),
+                events_received: self.metrics(),
+                memory_bytes: self.memory_usage(),
+                capacity_bytes: self.capacity_bytes,
+            });
+        };
+        let events = tenants.get(tenant);
+        Ok(StoreStats {
+            event_count: events.map_or(0, EventMap::len),
+            events_received: events.map_or(0, |events| events.received),
+            memory_bytes: events.map_or(0, |events| events.bytes),
+            capacity_bytes: self
+                .tenant_limits
+                .read()
+                .map_err(|e| AppError::InternalError(e.to_string()))?
+                .limits(tenant)
+                .max_bytes,
+        })
+    }
+
+    fn status(&self) -> Result<StoreStatus, AppError> {
+        let stats = self.stats(None)?;
+        match stats.capacity_bytes {
+            Some(capacity) if stats.memory_bytes >= capacity => Ok(StoreStatus::OverCapacity),
+            _ => Ok(StoreStatus::Ready),
+        }
+    }
+
+    fn tenants(&self) -> Result<Vec<String>, AppError> {
+        let tenants = self
+            .events
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let mut names: Vec<String> = tenants.keys().cloned().collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn append_to_stream(
+        &self,
+        tenant: &str,
+        stream_id: &str,
+        expected_version: Option<u64>,
+        events: Vec<Event>,
+    ) -> Result<StreamEvents, AppError> {
+        let mut tenants = self
+            .events
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let empty = EventMap::default();
+        let existing = tenants.get(tenant).unwrap_or(&empty);
+
+        let current = existing
+            .streams
+            .get(stream_id)
+            .map_or(0, |stream| stream.version);
+        if let Some(expected) = expected_version {
+            if expected != current {
+                return Err(AppError::Conflict(format!(
+                    "Stream '{stream_id}' is at version {current}, expected {expected}"
+                )));
+            }
+        }
+        //Event ids are write-once; appending must never replace an existing event, nor one
+        //earlier in the same batch
+        let mut ids = HashSet::with_capacity(events.len());
+        if let Some(repeated) = events
+            .iter()
+            .find(|event| existing.get(&event.id).is_some() || !ids.insert(event.id))
+        {
+            return Err(AppError::Conflict(format!(
+                "Event {} already exists",
+                repeated.id
+            )));
+        }
+        self.check_quota(tenant, existing, &events)?;
+        if events.is_empty() {
+            return Ok(StreamEvents {
+                stream_id: stream_id.to_string(),
+                version: current,
+                events: Vec::new(),
+            });
+        }
+        let map = tenants.entry(tenant.to_string()).or_default();
+
+        let mut appended = Vec::with_capacity(events.len());
+        for (version, mut event) in (current + 1..).zip(events) {
+            event.stream = Some(StreamPosition {
+                stream_id: stream_id.to_string(),
+                version,
+            });
+            self.bytes.fetch_add(entry_size(&event), Ordering::Relaxed);
+            self.count.fetch_add(1, Ordering::Relaxed);
+            map.received += 1;
+            map.insert(event.clone());
+            appended.push(event);
+        }
+        let version = current + appended.len() as u64;
+        info!(
+            "Appended {} event(s) to stream {} of tenant {}, now at version {}",
+            appended.len(),
+            stream_id,
+            tenant,
+            version
+        );
+
+        Ok(StreamEvents {
+            stream_id: stream_id.to_string(),
+            version,
+            events: appended,
+        })
+    }
+
+    fn read_stream(
+        &self,
+        tenant: &str,
+        stream_id: &str,
+        from_version: u64,
+    ) -> Result<Option<StreamEvents>, AppError> {
+        debug!(
+            "Reading stream {} of tenant {} from version {}",
+            stream_id, tenant, from_version
+        );
+        let tenants = self
+            .events
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(tenants
+            .get(tenant)
+            .and_then(|events| events.stream_events(stream_id, from_version)))
     }
 }
 
@@ -102,16 +703,23 @@ mod tests {
     use std::sync::Arc;
 
     use super::*;
-    use chrono::{DateTime, Utc};
+    use crate::model::EventMetadata;
     use serde_json::json;
     use tokio::task;
 
+    const TENANT: &str = "acme";
+
     fn sample_event(id: Option<Uuid>, event_type: &str, ts: &str) -> Event {
         Event {
             id: id.unwrap_or_else(Uuid::new_v4),
             event_type: event_type.to_string(),
             timestamp: DateTime::parse_from_rfc3339(ts).unwrap().to_utc(),
             payload: json!({ "example": true }),
+            schema_version: 1,
+            metadata: Default::default(),
+            received_at: Utc::now(),
+            clock_skewed: false,
+            stream: None,
         }
     }
 
@@ -121,9 +729,9 @@ mod tests {
         let event = sample_event(None, "test", "2025-01-01T12:00:00Z");
 
         let id = event.id;
-        store.add_event(event.clone()).unwrap();
+        store.add_event(TENANT, event.clone()).unwrap();
 
-        let retrieved = store.get_by_id(id).unwrap();
+        let retrieved = store.get_by_id(TENANT, id).unwrap();
         assert_eq!(retrieved, Some(event));
         assert_eq!(store.metrics(), 1);
     }
@@ -133,15 +741,19 @@ mod tests {
         let store = InMemoryEventStore::new();
         let e1 = sample_event(None, "login", "2025-01-01T12:00:00Z");
         let e2 = sample_event(None, "logout", "2025-01-01T13:00:00Z");
-        store.add_event(e1.clone()).unwrap();
-        store.add_event(e2.clone()).unwrap();
+        store.add_event(TENANT, e1.clone()).unwrap();
+        store.add_event(TENANT, e2.clone()).unwrap();
 
         let results = store
-            .query_events(EventQuery {
-                event_type: Some("login".to_string()),
-                start: None,
-                end: None,
-            })
+            .query_events(
+                TENANT,
+                EventQuery {
+                    event_type: Some("login".to_string()),
+                    start: None,
+                    end: None,
+                    ..Default::default()
+                },
+            )
             .unwrap();
 
         assert_eq!(results.len(), 1);
@@ -156,9 +768,9 @@ mod tests {
         let e2 = sample_event(None, "test", "2025-01-01T11:00:00Z");
         let e3 = sample_event(None, "test", "2025-01-01T12:00:00Z");
 
-        store.add_event(e1.clone()).unwrap();
-        store.add_event(e2.clone()).unwrap();
-        store.add_event(e3.clone()).unwrap();
+        store.add_event(TENANT, e1.clone()).unwrap();
+        store.add_event(TENANT, e2.clone()).unwrap();
+        store.add_event(TENANT, e3.clone()).unwrap();
 
         let start = DateTime::parse_from_rfc3339("2025-01-01T10:30:00Z")
             .unwrap()
@@ -168,11 +780,15 @@ mod tests {
             .to_utc();
 
         let results = store
-            .query_events(EventQuery {
-                event_type: None,
-                start: Some(start),
-                end: Some(end),
-            })
+            .query_events(
+                TENANT,
+                EventQuery {
+                    event_type: None,
+                    start: Some(start),
+                    end: Some(end),
+                    ..Default::default()
+                },
+            )
             .unwrap();
 
         assert_eq!(results.len(), 1);
@@ -184,7 +800,7 @@ mod tests {
     fn test_get_by_id_not_found() {
         let store = InMemoryEventStore::new();
         let random_id = Uuid::new_v4();
-        let result = store.get_by_id(random_id).unwrap();
+        let result = store.get_by_id(TENANT, random_id).unwrap();
         assert!(result.is_none());
     }
 
@@ -192,11 +808,15 @@ mod tests {
     fn test_query_events_no_match() {
         let store = InMemoryEventStore::new();
         let result = store
-            .query_events(EventQuery {
-                event_type: Some("nonexistent".into()),
-                start: None,
-                end: None,
-            })
+            .query_events(
+                TENANT,
+                EventQuery {
+                    event_type: Some("nonexistent".into()),
+                    start: None,
+                    end: None,
+                    ..Default::default()
+                },
+            )
             .unwrap();
         assert!(result.is_empty());
     }
@@ -205,10 +825,7 @@ mod tests {
     fn test_poisoned_lock_add_event() {
         use std::panic::{catch_unwind, AssertUnwindSafe};
 
-        let store = InMemoryEventStore {
-            events: RwLock::new(HashMap::new()),
-            count: AtomicUsize::new(0),
-        };
+        let store = InMemoryEventStore::new();
 
         let _ = catch_unwind(AssertUnwindSafe(|| {
             let _guard = store.events.write().unwrap();
@@ -216,33 +833,666 @@ mod tests {
         }));
 
         let event = sample_event(None, "test", "2025-01-01T12:00:00Z");
-        let result = store.add_event(event);
+        let result = store.add_event(TENANT, event);
         assert!(matches!(result, Err(AppError::InternalError(_))));
     }
 
+    #[test]
+    fn test_memory_usage_counts_heap_allocations() {
+        let store = InMemoryEventStore::new();
+        let mut event = sample_event(None, "test", "2025-01-01T12:00:00Z");
+        event.payload = json!({ "description": "x".repeat(4096), "tags": ["a", "b", "c"] });
+        let expected = std::mem::size_of::<Uuid>() + event.deep_size();
+
+        store.add_event(TENANT, event).unwrap();
+
+        assert_eq!(store.memory_usage(), expected);
+        assert!(store.memory_usage() > 4096 + std::mem::size_of::<Event>());
+    }
+
+    #[test]
+    fn test_add_event_never_replaces() {
+        let store = InMemoryEventStore::new();
+        let id = Uuid::new_v4();
+        let first = sample_event(Some(id), "test", "2025-01-01T12:00:00Z");
+        let mut second = sample_event(Some(id), "test", "2025-01-01T12:00:00Z");
+        second.payload = json!({ "blob": "y".repeat(1024) });
+        let expected = std::mem::size_of::<Uuid>() + first.deep_size();
+
+        store.add_event(TENANT, first.clone()).unwrap();
+        assert!(matches!(
+            store.add_event(TENANT, second),
+            Err(AppError::Conflict(_))
+        ));
+
+        assert_eq!(store.get_by_id(TENANT, id).unwrap(), Some(first));
+        assert_eq!(store.memory_usage(), expected);
+        let stats = store.stats(None).unwrap();
+        assert_eq!(stats.event_count, 1);
+        assert_eq!(stats.events_received, 1);
+        assert_eq!(stats.memory_bytes, expected);
+    }
+
+    #[test]
+    fn test_concurrent_adds_of_one_id_store_it_once() {
+        let store = std::sync::Arc::new(InMemoryEventStore::new());
+        let id = Uuid::new_v4();
+        let writers: Vec<_> = (0..8)
+            .map(|n| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    let mut event = sample_event(Some(id), "test", "2025-01-01T12:00:00Z");
+                    event.payload = json!({ "writer": n });
+                    store.add_event(TENANT, event).is_ok()
+                })
+            })
+            .collect();
+        let stored = writers
+            .into_iter()
+            .map(|writer| writer.join().unwrap())
+            .filter(|ok| *ok)
+            .count();
+        assert_eq!(stored, 1);
+        assert_eq!(store.metrics(), 1);
+    }
+
+    #[test]
+    fn test_delete_by_id() {
+        let store = InMemoryEventStore::new();
+        let event = sample_event(None, "test", "2025-01-01T12:00:00Z");
+        let id = event.id;
+        store.add_event(TENANT, event).unwrap();
+
+        assert_eq!(store.delete(TENANT, id).unwrap(), 1);
+        assert_eq!(store.delete(TENANT, id).unwrap(), 0);
+        assert!(store.get_by_id(TENANT, id).unwrap().is_none());
+        assert_eq!(store.memory_usage(), 0);
+        assert_eq!(store.metrics(), 1);
+    }
+
+    #[test]
+    fn test_update_event() {
+        let store = InMemoryEventStore::new();
+        let mut event = sample_event(None, "test", "2025-01-01T12:00:00Z");
+        store.add_event(TENANT, event.clone()).unwrap();
+
+        event.payload = json!({ "example": false, "note": "n".repeat(100) });
+        assert!(store.update_event(TENANT, event.clone()).unwrap());
+        assert_eq!(
+            store.get_by_id(TENANT, event.id).unwrap(),
+            Some(event.clone())
+        );
+        assert_eq!(
+            store.memory_usage(),
+            std::mem::size_of::<Uuid>() + event.deep_size()
+        );
+        assert_eq!(store.metrics(), 1);
+
+        let missing = sample_event(None, "test", "2025-01-01T12:00:00Z");
+        assert!(!store.update_event(TENANT, missing).unwrap());
+    }
+
+    #[test]
+    fn test_delete_matching() {
+        let store = InMemoryEventStore::new();
+        let keep = sample_event(None, "login", "2025-01-01T12:00:00Z");
+        let expected_bytes = std::mem::size_of::<Uuid>() + keep.deep_size();
+        store.add_event(TENANT, keep.clone()).unwrap();
+        store
+            .add_event(TENANT, sample_event(None, "test", "2025-01-01T12:00:00Z"))
+            .unwrap();
+        store
+            .add_event(TENANT, sample_event(None, "test", "2025-01-02T12:00:00Z"))
+            .unwrap();
+
+        let deleted = store
+            .delete_matching(
+                TENANT,
+                EventQuery {
+                    event_type: Some("test".into()),
+                    start: None,
+                    end: None,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(deleted, 2);
+        let remaining = store.query_events(TENANT, EventQuery::default()).unwrap();
+        assert_eq!(remaining, vec![keep]);
+        assert_eq!(store.memory_usage(), expected_bytes);
+    }
+
+    #[test]
+    fn test_status_over_capacity() {
+        let mut event = sample_event(None, "test", "2025-01-01T12:00:00Z");
+        event.payload = json!({ "blob": "q".repeat(512) });
+        let capacity = entry_size(&event);
+        let store = InMemoryEventStore::new().with_capacity_bytes(capacity);
+        assert_eq!(store.status().unwrap(), StoreStatus::Ready);
+
+        store.add_event(TENANT, event).unwrap();
+
+        assert_eq!(store.status().unwrap(), StoreStatus::OverCapacity);
+        assert_eq!(store.stats(None).unwrap().capacity_bytes, Some(capacity));
+    }
+
+    #[test]
+    fn test_writes_over_capacity_are_rejected() {
+        let event = sample_event(None, "test", "2025-01-01T12:00:00Z");
+        let store = InMemoryEventStore::new().with_capacity_bytes(entry_size(&event));
+        store.add_event(TENANT, event).unwrap();
+
+        //The budget is shared by all tenants
+        let result = store.add_event("other", sample_event(None, "test", "2025-01-01T12:00:01Z"));
+        assert!(matches!(result, Err(AppError::InsufficientStorage(_))));
+        let result = store.append_to_stream(
+            TENANT,
+            "orders",
+            None,
+            vec![sample_event(None, "test", "2025-01-01T12:00:02Z")],
+        );
+        assert!(matches!(result, Err(AppError::InsufficientStorage(_))));
+        assert_eq!(store.stats(None).unwrap().event_count, 1);
+        assert_eq!(store.stats(Some("other")).unwrap().event_count, 0);
+    }
+
+    #[test]
+    fn test_query_by_metadata_uses_index() {
+        let store = InMemoryEventStore::new();
+        let mut first = sample_event(None, "order_placed", "2025-01-01T12:00:00Z");
+        first.metadata = EventMetadata {
+            source: Some("checkout".into()),
+            correlation_id: Some("flow-1".into()),
+            ..Default::default()
+        };
+        let mut second = sample_event(None, "payment_taken", "2025-01-01T12:01:00Z");
+        second.metadata = EventMetadata {
+            source: Some("billing".into()),
+            correlation_id: Some("flow-1".into()),
+            causation_id: Some(first.id.to_string()),
+            ..Default::default()
+        };
+        let mut other = sample_event(None, "order_placed", "2025-01-01T12:02:00Z");
+        other.metadata.correlation_id = Some("flow-2".into());
+        for event in [&first, &second, &other] {
+            store.add_event(TENANT, event.clone()).unwrap();
+        }
+
+        let flow = store
+            .query_events(
+                TENANT,
+                EventQuery {
+                    metadata: EventMetadata {
+                        correlation_id: Some("flow-1".into()),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(flow.len(), 2);
+
+        let billing = store
+            .query_events(
+                TENANT,
+                EventQuery {
+                    metadata: EventMetadata {
+                        correlation_id: Some("flow-1".into()),
+                        source: Some("billing".into()),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(billing, vec![second.clone()]);
+
+        //Index entries follow deletes and updates
+        store.delete(TENANT, second.id).unwrap();
+        other.metadata.correlation_id = Some("flow-1".into());
+        store.update_event(TENANT, other.clone()).unwrap();
+        let flow = store
+            .query_events(
+                TENANT,
+                EventQuery {
+                    metadata: EventMetadata {
+                        correlation_id: Some("flow-1".into()),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let mut ids: Vec<Uuid> = flow.iter().map(|e| e.id).collect();
+        ids.sort();
+        let mut expected = vec![first.id, other.id];
+        expected.sort();
+        assert_eq!(ids, expected);
+        assert!(store
+            .query_events(
+                TENANT,
+                EventQuery {
+                    metadata: EventMetadata {
+                        correlation_id: Some("flow-2".into()),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }
+            )
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_append_and_read_stream_in_order() {
+        let store = InMemoryEventStore::new();
+        let opened = sample_event(None, "account_opened", "2025-01-01T12:00:00Z");
+        let deposited = sample_event(None, "deposited", "2025-01-01T11:00:00Z");
+        let withdrawn = sample_event(None, "withdrawn", "2025-01-01T13:00:00Z");
+
+        let first = store
+            .append_to_stream(
+                TENANT,
+                "account-1",
+                Some(0),
+                vec![opened.clone(), deposited.clone()],
+            )
+            .unwrap();
+        assert_eq!(first.version, 2);
+        assert_eq!(first.events[1].stream.as_ref().unwrap().version, 2);
+        store
+            .append_to_stream(TENANT, "account-1", Some(2), vec![withdrawn.clone()])
+            .unwrap();
+
+        //Stream order is append order, not timestamp order
+        let stream = store.read_stream(TENANT, "account-1", 0).unwrap().unwrap();
+        let ids: Vec<Uuid> = stream.events.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![opened.id, deposited.id, withdrawn.id]);
+        assert_eq!(stream.version, 3);
+
+        let tail = store.read_stream(TENANT, "account-1", 3).unwrap().unwrap();
+        assert_eq!(tail.events.len(), 1);
+        assert!(store.read_stream(TENANT, "account-2", 0).unwrap().is_none());
+        assert_eq!(store.stats(None).unwrap().events_received, 3);
+    }
+
+    #[test]
+    fn test_append_with_stale_expected_version_conflicts() {
+        let store = InMemoryEventStore::new();
+        store
+            .append_to_stream(
+                TENANT,
+                "cart-1",
+                None,
+                vec![sample_event(None, "a", "2025-01-01T12:00:00Z")],
+            )
+            .unwrap();
+
+        let stale = store.append_to_stream(
+            TENANT,
+            "cart-1",
+            Some(0),
+            vec![sample_event(None, "b", "2025-01-01T12:00:00Z")],
+        );
+        assert!(matches!(stale, Err(AppError::Conflict(_))));
+
+        let existing = store
+            .read_stream(TENANT, "cart-1", 0)
+            .unwrap()
+            .unwrap()
+            .events[0]
+            .clone();
+        let duplicate = store.append_to_stream(TENANT, "cart-1", Some(1), vec![existing]);
+        assert!(matches!(duplicate, Err(AppError::Conflict(_))));
+        assert_eq!(
+            store
+                .read_stream(TENANT, "cart-1", 0)
+                .unwrap()
+                .unwrap()
+                .version,
+            1
+        );
+    }
+
+    #[test]
+    fn test_append_rejects_repeated_id_within_batch() {
+        let store = InMemoryEventStore::new();
+        let event = sample_event(None, "a", "2025-01-01T12:00:00Z");
+        let result = store.append_to_stream(
+            TENANT,
+            "cart-1",
+            None,
+            vec![
+                event.clone(),
+                sample_event(None, "b", "2025-01-01T12:00:00Z"),
+                event,
+            ],
+        );
+        assert!(matches!(result, Err(AppError::Conflict(_))));
+
+        //Nothing was stored or counted
+        assert!(store.read_stream(TENANT, "cart-1", 0).unwrap().is_none());
+        assert_eq!(store.metrics(), 0);
+        assert_eq!(store.memory_usage(), 0);
+    }
+
+    #[test]
+    fn test_stream_version_survives_deletes_and_updates() {
+        let store = InMemoryEventStore::new();
+        let appended = store
+            .append_to_stream(
+                TENANT,
+                "order-1",
+                None,
+                vec![
+                    sample_event(None, "a", "2025-01-01T12:00:00Z"),
+                    sample_event(None, "b", "2025-01-01T12:00:00Z"),
+                ],
+            )
+            .unwrap();
+
+        let mut anonymized = appended.events[0].clone();
+        anonymized.payload = json!({ "example": "[erased]" });
+        assert!(store.update_event(TENANT, anonymized.clone()).unwrap());
+        store.delete(TENANT, appended.events[1].id).unwrap();
+
+        let stream = store.read_stream(TENANT, "order-1", 0).unwrap().unwrap();
+        assert_eq!(stream.version, 2);
+        assert_eq!(stream.events, vec![anonymized]);
+    }
+
+    #[test]
+    fn test_tenants_are_isolated() {
+        let store = InMemoryEventStore::new();
+        let event = sample_event(None, "login", "2025-01-01T12:00:00Z");
+        store.add_event(TENANT, event.clone()).unwrap();
+        store
+            .append_to_stream(
+                "other",
+                "cart-1",
+                None,
+                vec![sample_event(None, "a", "2025-01-01T12:00:00Z")],
+            )
+            .unwrap();
+
+        assert!(store.get_by_id("other", event.id).unwrap().is_none());
+        assert_eq!(store.delete("other", event.id).unwrap(), 0);
+        assert!(!store.update_event("other", event.clone()).unwrap());
+        assert_eq!(
+            store
+                .query_events("other", EventQuery::default())
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(store.read_stream(TENANT, "cart-1", 0).unwrap().is_none());
+        //The same stream id starts from scratch in another tenant
+        let appended = store
+            .append_to_stream(
+                TENANT,
+                "cart-1",
+                Some(0),
+                vec![sample_event(None, "a", "2025-01-01T12:00:00Z")],
+            )
+            .unwrap();
+        assert_eq!(appended.version, 1);
+
+        assert_eq!(store.tenants().unwrap(), vec![TENANT, "other"]);
+        assert_eq!(store.stats(Some(TENANT)).unwrap().event_count, 2);
+        assert_eq!(store.stats(Some("unknown")).unwrap().event_count, 0);
+        assert_eq!(store.stats(None).unwrap().event_count, 3);
+    }
+
+    #[test]
+    fn test_rejected_writes_do_not_create_tenants() {
+        let limits: TenantConfig =
+            serde_json::from_value(json!({ "default": { "max_bytes": 1 } })).unwrap();
+        let store = InMemoryEventStore::new().with_tenant_limits(limits);
+        let event = sample_event(None, "a", "2025-01-01T12:00:00Z");
+
+        assert!(store.add_event("over-quota", event.clone()).is_err());
+        assert!(store
+            .append_to_stream("wrong-version", "s", Some(3), vec![event.clone()])
+            .is_err());
+        assert!(store
+            .append_to_stream("empty", "s", None, Vec::new())
+            .is_ok());
+        assert!(store.tenants().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_tenant_quotas() {
+        let limits: TenantConfig = serde_json::from_value(json!({
+            "default": { "max_events": 2 },
+            "tenants": { "small": { "max_bytes": 1024 } }
+        }))
+        .unwrap();
+        let store = InMemoryEventStore::new().with_tenant_limits(limits);
+        for _ in 0..2 {
+            store
+                .add_event(TENANT, sample_event(None, "a", "2025-01-01T12:00:00Z"))
+                .unwrap();
+        }
+        assert!(matches!(
+            store.add_event(TENANT, sample_event(None, "a", "2025-01-01T12:00:00Z")),
+            Err(AppError::InsufficientStorage(_))
+        ));
+
+        let mut large = sample_event(None, "a", "2025-01-01T12:00:00Z");
+        large.payload = json!({ "blob": "z".repeat(2048) });
+        assert!(matches!(
+            store.append_to_stream("small", "s", None, vec![large]),
+            Err(AppError::InsufficientStorage(_))
+        ));
+        assert_eq!(
+            store.stats(Some("small")).unwrap().capacity_bytes,
+            Some(1024)
+        );
+        assert_eq!(store.stats(None).unwrap().event_count, 2);
+    }
+
+    #[test]
+    fn test_updates_cannot_exceed_byte_quota() {
+        let limits: TenantConfig = serde_json::from_value(json!({
+            "default": { "max_bytes": 1024 }
+        }))
+        .unwrap();
+        let store = InMemoryEventStore::new().with_tenant_limits(limits);
+        let mut event = sample_event(None, "a", "2025-01-01T12:00:00Z");
+        event.payload = json!({ "blob": "z".repeat(256) });
+        store.add_event(TENANT, event.clone()).unwrap();
+
+        let mut grown = event.clone();
+        grown.payload = json!({ "blob": "z".repeat(2048) });
+        assert!(matches!(
+            store.update_event(TENANT, grown),
+            Err(AppError::InsufficientStorage(_))
+        ));
+        assert_eq!(
+            store.get_by_id(TENANT, event.id).unwrap(),
+            Some(event.clone())
+        );
+
+        //Shrinking is always allowed
+        let mut shrunk = event;
+        shrunk.payload = json!({ "blob": "[erased]" });
+        assert!(store.update_event(TENANT, shrunk).unwrap());
+    }
+
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Login {
+        user_id: u64,
+        method: String,
+    }
+
+    impl TypedEvent for Login {
+        const EVENT_TYPE: &'static str = "login";
+    }
+
+    #[test]
+    fn test_typed_round_trip() {
+        let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+        let login = Login {
+            user_id: 7,
+            method: "sso".into(),
+        };
+        let ts = DateTime::parse_from_rfc3339("2025-01-01T12:00:00Z")
+            .unwrap()
+            .to_utc();
+
+        let stored = store.add_typed(TENANT, &login, ts).unwrap();
+        store
+            .add_event(TENANT, sample_event(None, "logout", "2025-01-01T13:00:00Z"))
+            .unwrap();
+
+        assert_eq!(stored.event_type, "login");
+        assert_eq!(stored.payload, json!({ "user_id": 7, "method": "sso" }));
+
+        let fetched = store
+            .get_typed::<Login>(TENANT, stored.id, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.data, login);
+        assert_eq!(fetched.timestamp, ts);
+
+        let results = store
+            .query_typed::<Login>(TENANT, None, None, None)
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, stored.id);
+    }
+
+    #[test]
+    fn test_typed_decode_errors() {
+        let store = InMemoryEventStore::new();
+        let logout = sample_event(None, "logout", "2025-01-01T12:00:00Z");
+        let mut malformed = sample_event(None, "login", "2025-01-01T12:00:00Z");
+        malformed.payload = json!({ "user_id": "not-a-number" });
+        let mut newer = sample_event(None, "login", "2025-01-01T12:00:00Z");
+        newer.schema_version = 2;
+
+        assert!(matches!(
+            Login::from_event(&logout),
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(matches!(
+            Login::from_event(&newer),
+            Err(AppError::BadRequest(_))
+        ));
+
+        store.add_event(TENANT, malformed.clone()).unwrap();
+        assert!(matches!(
+            store.get_typed::<Login>(TENANT, malformed.id, None),
+            Err(AppError::BadRequest(_))
+        ));
+
+        //One bad record does not hide the rest of the type from a query
+        let valid = store
+            .add_typed(
+                TENANT,
+                &Login {
+                    user_id: 7,
+                    method: "sso".into(),
+                },
+                Utc::now(),
+            )
+            .unwrap();
+        store.add_event(TENANT, newer).unwrap();
+        let results = store
+            .query_typed::<Login>(TENANT, None, None, None)
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, valid.id);
+    }
+
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct LoginV2 {
+        user_id: u64,
+        method: String,
+        mfa: bool,
+    }
+
+    impl TypedEvent for LoginV2 {
+        const EVENT_TYPE: &'static str = "login";
+        const SCHEMA_VERSION: u32 = 2;
+    }
+
+    #[test]
+    fn test_typed_reads_upcast_older_events() {
+        let store = InMemoryEventStore::new();
+        let stored = store
+            .add_typed(
+                TENANT,
+                &Login {
+                    user_id: 7,
+                    method: "sso".into(),
+                },
+                Utc::now(),
+            )
+            .unwrap();
+        let upcasters = UpcasterRegistry::new();
+        upcasters
+            .register("login", 1, |mut payload| {
+                payload["mfa"] = json!(false);
+                payload
+            })
+            .unwrap();
+        let expected = LoginV2 {
+            user_id: 7,
+            method: "sso".into(),
+            mfa: false,
+        };
+
+        let fetched = store
+            .get_typed::<LoginV2>(TENANT, stored.id, Some(&upcasters))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.data, expected);
+        let results = store
+            .query_typed::<LoginV2>(TENANT, None, None, Some(&upcasters))
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].data, expected);
+
+        //Without the registry the v1 event does not decode as v2
+        assert!(store
+            .query_typed::<LoginV2>(TENANT, None, None, None)
+            .unwrap()
+            .is_empty());
+    }
+
     #[tokio::test]
     async fn test_concurrent_reads() {
         let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
 
         store
-            .add_event(Event {
-                id: Uuid::new_v4(),
-                event_type: "test".into(),
-                timestamp: Utc::now(),
-                payload: serde_json::json!({"user_id": 1}),
-            })
+            .add_event(
+                TENANT,
+                Event {
+                    id: Uuid::new_v4(),
+                    event_type: "test".into(),
+                    timestamp: Utc::now(),
+                    payload: serde_json::json!({"user_id": 1}),
+                    schema_version: 1,
+                    metadata: Default::default(),
+                    received_at: Utc::now(),
+                    clock_skewed: false,
+                    stream: None,
+                },
+            )
             .unwrap();
 
         let store1 = Arc::clone(&store);
         let store2 = Arc::clone(&store);
 
         let t1 = task::spawn_blocking(move || {
-            let res = store1.query_events(EventQuery::default()).unwrap();
+            let res = store1.query_events(TENANT, EventQuery::default()).unwrap();
             assert!(!res.is_empty());
         });
 
         let t2 = task::spawn_blocking(move || {
-            let res = store2.query_events(EventQuery::default()).unwrap();
+            let res = store2.query_events(TENANT, EventQuery::default()).unwrap();
             assert!(!res.is_empty());
         });
 
@@ -258,17 +1508,25 @@ mod tests {
         let store_writer = Arc::clone(&store);
 
         let reader = task::spawn_blocking(move || {
-            let _ = store_reader.query_events(EventQuery::default());
+            let _ = store_reader.query_events(TENANT, EventQuery::default());
         });
 
         let writer = task::spawn_blocking(move || {
             store_writer
-                .add_event(Event {
-                    id: Uuid::new_v4(),
-                    event_type: "write".into(),
-                    timestamp: Utc::now(),
-                    payload: serde_json::json!({"val": 42}),
-                })
+                .add_event(
+                    TENANT,
+                    Event {
+                        id: Uuid::new_v4(),
+                        event_type: "write".into(),
+                        timestamp: Utc::now(),
+                        payload: serde_json::json!({"val": 42}),
+                        schema_version: 1,
+                        metadata: Default::default(),
+                        received_at: Utc::now(),
+                        clock_skewed: false,
+                        stream: None,
+                    },
+                )
                 .unwrap();
         });
 
@@ -0,0 +1,205 @@
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::error::AppError;
+use crate::model::EventQuery;
+use crate::storage::EventStore;
+
+//Selects the tenant for callers whose credentials are not bound to one
+pub const TENANT_HEADER: &str = "X-Tenant-Id";
+//Tenant used when neither the credentials nor the request name one
+pub const DEFAULT_TENANT: &str = "default";
+
+//Tenant ids end up in logs and stream/projection keys, so keep them to a safe alphabet
+pub fn validate_tenant(tenant: &str) -> Result<(), AppError> {
+    let valid = !tenant.is_empty()
+        && tenant.len() <= 64
+        && tenant
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!(
+            "Invalid tenant '{tenant}': use 1-64 letters, digits, '-', '_' or '.'"
+        )))
+    }
+}
+
+//Storage quota and retention for one tenant.  Unset fields are unlimited.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct TenantLimits {
+    pub max_events: Option<usize>,
+    pub max_bytes: Option<usize>,
+    //Events whose timestamp is older than this are purged
+    pub retention_secs: Option<u64>,
+}
+
+//Limits for named tenants, with `default` applying to every other tenant.  Loaded from the
+//TENANTS_FILE config file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct TenantConfig {
+    #[serde(default)]
+    pub default: TenantLimits,
+    #[serde(default)]
+    pub tenants: HashMap<String, TenantLimits>,
+}
+
+impl TenantConfig {
+    pub fn load(path: &str) -> Result<Self, AppError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| AppError::InternalError(format!("Failed to read {path}: {e}")))?;
+        let config: TenantConfig = serde_json::from_str(&contents)
+            .map_err(|e| AppError::BadRequest(format!("Invalid tenant config in {path}: {e}")))?;
+        for tenant in config.tenants.keys() {
+            validate_tenant(tenant)?;
+        }
+        Ok(config)
+    }
+
+    #[must_use]
+    pub fn limits(&self, tenant: &str) -> &TenantLimits {
+        self.tenants.get(tenant).unwrap_or(&self.default)
+    }
+
+    //True when any tenant has a retention period, i.e. the purge task needs to run
+    #[must_use]
+    pub fn has_retention(&self) -> bool {
+        self.default.retention_secs.is_some()
+            || self.tenants.values().any(|l| l.retention_secs.is_some())
+    }
+}
+
+//Deletes every tenant's events that fell out of its retention period as of `now`
+pub fn enforce_retention(
+    store: &dyn EventStore,
+    config: &TenantConfig,
+    now: DateTime<Utc>,
+) -> Result<usize, AppError> {
+    let mut purged = 0;
+    for tenant in store.tenants()? {
+        let Some(retention_secs) = config.limits(&tenant).retention_secs else {
+            continue;
+        };
+        //A period too long to represent reaches back before any event could have been stored
+        let Some(cutoff) = i64::try_from(retention_secs)
+            .ok()
+            .and_then(Duration::try_seconds)
+            .and_then(|retention| now.checked_sub_signed(retention))
+        else {
+            continue;
+        };
+        //By arrival time: a client-supplied timestamp may be backdated arbitrarily far
+        let deleted = store.delete_matching(
+            &tenant,
+            EventQuery {
+                received_before: Some(cutoff),
+                ..Default::default()
+            },
+        )?;
+        if deleted > 0 {
+            info!(
+                "Purged {} event(s) of tenant {} received before {}",
+                deleted,
+                tenant,
+                cutoff.to_rfc3339()
+            );
+        }
+        purged += deleted;
+    }
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::NewEvent;
+    use crate::storage::InMemoryEventStore;
+    use serde_json::json;
+
+    //An event both timestamped and received at `at`
+    fn event_at(at: DateTime<Utc>) -> crate::model::Event {
+        let mut event = NewEvent {
+            id: None,
+            event_type: "login".into(),
+            timestamp: Some(at),
+            payload: json!({}),
+            schema_version: 1,
+            metadata: Default::default(),
+        }
+        .into_event();
+        event.received_at = at;
+        event
+    }
+
+    #[test]
+    fn test_validate_tenant() {
+        assert!(validate_tenant("team-a.prod_1").is_ok());
+        assert!(validate_tenant("").is_err());
+        assert!(validate_tenant("a/b").is_err());
+        assert!(validate_tenant(&"x".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn test_retention_only_purges_configured_tenants() {
+        let config: TenantConfig = serde_json::from_value(json!({
+            "tenants": { "short": { "retention_secs": 3600 } }
+        }))
+        .unwrap();
+        assert!(config.has_retention());
+        let store = InMemoryEventStore::new();
+        let now = Utc::now();
+        store
+            .add_event("short", event_at(now - Duration::hours(2)))
+            .unwrap();
+        store.add_event("short", event_at(now)).unwrap();
+        store
+            .add_event("forever", event_at(now - Duration::days(365)))
+            .unwrap();
+
+        assert_eq!(enforce_retention(&store, &config, now).unwrap(), 1);
+        assert_eq!(store.stats(Some("short")).unwrap().event_count, 1);
+        assert_eq!(store.stats(Some("forever")).unwrap().event_count, 1);
+    }
+
+    #[test]
+    fn test_retention_uses_arrival_time() {
+        let config: TenantConfig = serde_json::from_value(json!({
+            "default": { "retention_secs": 3600 }
+        }))
+        .unwrap();
+        let store = InMemoryEventStore::new();
+        let now = Utc::now();
+        let mut backdated = event_at(now);
+        backdated.timestamp = now - Duration::days(30);
+        store.add_event("imports", backdated).unwrap();
+
+        assert_eq!(enforce_retention(&store, &config, now).unwrap(), 0);
+        assert_eq!(
+            enforce_retention(&store, &config, now + Duration::hours(2)).unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_huge_retention_keeps_everything() {
+        let config: TenantConfig = serde_json::from_value(json!({
+            "default": { "retention_secs": u64::MAX }
+        }))
+        .unwrap();
+        let store = InMemoryEventStore::new();
+        let now = Utc::now();
+        store
+            .add_event("old", event_at(now - Duration::days(3650)))
+            .unwrap();
+
+        assert_eq!(enforce_retention(&store, &config, now).unwrap(), 0);
+        let config: TenantConfig = serde_json::from_value(json!({
+            "default": { "retention_secs": i64::MAX / 1000 + 1 }
+        }))
+        .unwrap();
+        assert_eq!(enforce_retention(&store, &config, now).unwrap(), 0);
+    }
+}
@@ -0,0 +1,157 @@
+use log::debug;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use crate::error::AppError;
+use crate::model::Event;
+
+//Transforms a payload from one schema version to the next
+pub type Upcaster = Arc<dyn Fn(Value) -> Value + Send + Sync>;
+
+//Upcasters keyed by event_type and the version they upgrade from.  Stored events keep the
+//version they were written with; reads are brought up to the newest version by chaining
+//upcasters (v1 -> v2 -> v3 ...) so consumers only ever see the current payload shape.
+#[derive(Default)]
+pub struct UpcasterRegistry {
+    upcasters: RwLock<HashMap<String, BTreeMap<u32, Upcaster>>>,
+}
+
+impl UpcasterRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //Registers the transform from `from_version` to `from_version + 1` for an event type
+    pub fn register<F>(
+        &self,
+        event_type: &str,
+        from_version: u32,
+        upcaster: F,
+    ) -> Result<(), AppError>
+    where
+        F: Fn(Value) -> Value + Send + Sync + 'static,
+    {
+        let mut upcasters = self
+            .upcasters
+            .write()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        upcasters
+            .entry(event_type.to_string())
+            .or_default()
+            .insert(from_version, Arc::new(upcaster));
+        Ok(())
+    }
+
+    //Newest payload version known for an event type, if any upcasters are registered
+    pub fn current_version(&self, event_type: &str) -> Result<Option<u32>, AppError> {
+        let upcasters = self
+            .upcasters
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        Ok(upcasters
+            .get(event_type)
+            .and_then(|chain| chain.keys().next_back())
+            .map(|from| from + 1))
+    }
+
+    pub fn upcast(&self, mut event: Event) -> Result<Event, AppError> {
+        let upcasters = self
+            .upcasters
+            .read()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let Some(chain) = upcasters.get(&event.event_type) else {
+            return Ok(event);
+        };
+
+        let stored_version = event.schema_version;
+        while let Some(upcaster) = chain.get(&event.schema_version) {
+            event.payload = upcaster(event.payload);
+            event.schema_version += 1;
+        }
+        if event.schema_version != stored_version {
+            debug!(
+                "Upcast event {} from v{} to v{}",
+                event.id, stored_version, event.schema_version
+            );
+        }
+        Ok(event)
+    }
+
+    pub fn upcast_all(&self, events: Vec<Event>) -> Result<Vec<Event>, AppError> {
+        events.into_iter().map(|event| self.upcast(event)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn event(event_type: &str, schema_version: u32, payload: Value) -> Event {
+        Event {
+            id: Uuid::new_v4(),
+            event_type: event_type.into(),
+            timestamp: Utc::now(),
+            payload,
+            schema_version,
+            metadata: Default::default(),
+            received_at: Utc::now(),
+            clock_skewed: false,
+            stream: None,
+        }
+    }
+
+    fn registry() -> UpcasterRegistry {
+        let registry = UpcasterRegistry::new();
+        //v1 -> v2: "name" split into first/last
+        registry
+            .register("signup", 1, |mut payload| {
+                let name = payload["name"].as_str().unwrap_or_default().to_string();
+                let (first, last) = name.split_once(' ').unwrap_or((&name, ""));
+                payload["user"] = json!({ "first": first, "last": last });
+                payload.as_object_mut().map(|p| p.remove("name"));
+                payload
+            })
+            .unwrap();
+        //v2 -> v3: "plan" defaults to "free"
+        registry
+            .register("signup", 2, |mut payload| {
+                if payload.get("plan").is_none() {
+                    payload["plan"] = json!("free");
+                }
+                payload
+            })
+            .unwrap();
+        registry
+    }
+
+    #[test]
+    fn test_upcast_chains_to_current_version() {
+        let registry = registry();
+        let upcast = registry
+            .upcast(event("signup", 1, json!({ "name": "Jo Smith" })))
+            .unwrap();
+
+        assert_eq!(upcast.schema_version, 3);
+        assert_eq!(
+            upcast.payload,
+            json!({ "user": { "first": "Jo", "last": "Smith" }, "plan": "free" })
+        );
+        assert_eq!(registry.current_version("signup").unwrap(), Some(3));
+    }
+
+    #[test]
+    fn test_upcast_leaves_current_and_unknown_types() {
+        let registry = registry();
+        let current = event("signup", 3, json!({ "plan": "pro" }));
+        let other = event("login", 1, json!({ "name": "Jo Smith" }));
+
+        assert_eq!(registry.upcast(current.clone()).unwrap(), current);
+        assert_eq!(registry.upcast(other.clone()).unwrap(), other);
+        assert_eq!(registry.current_version("login").unwrap(), None);
+    }
+}
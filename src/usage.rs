@@ -0,0 +1,250 @@
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::auth::Principal;
+use crate::error::AppError;
+use crate::model::NewEvent;
+
+//Usage key for callers without credentials, i.e. when authentication is off
+pub const ANONYMOUS_KEY: &str = "anonymous";
+
+//Daily ingestion allowance for one API key or JWT subject.  Unset fields are unlimited.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct DailyQuota {
+    pub max_events: Option<u64>,
+    pub max_bytes: Option<u64>,
+}
+
+//Quotas for named keys, with `default` applying to every other key.  Loaded from the
+//USAGE_QUOTAS_FILE config file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct UsageConfig {
+    #[serde(default)]
+    pub default: DailyQuota,
+    #[serde(default)]
+    pub keys: HashMap<String, DailyQuota>,
+}
+
+impl UsageConfig {
+    pub fn load(path: &str) -> Result<Self, AppError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| AppError::InternalError(format!("Failed to read {path}: {e}")))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| AppError::BadRequest(format!("Invalid usage quotas in {path}: {e}")))
+    }
+
+    #[must_use]
+    pub fn quota(&self, key: &str) -> DailyQuota {
+        self.keys.get(key).copied().unwrap_or(self.default)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct DailyUsage {
+    day: NaiveDate,
+    events: u64,
+    bytes: u64,
+}
+
+//A key's ingestion so far on the current UTC day, as returned by GET /usage
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UsageReport {
+    pub key: String,
+    pub day: NaiveDate,
+    pub events: u64,
+    pub bytes: u64,
+    pub quota: DailyQuota,
+    pub resets_at: DateTime<Utc>,
+}
+
+//Usage key of the caller: "key:<API key id>" or "jwt:<subject>"
+#[must_use]
+pub fn usage_key(principal: &Principal) -> String {
+    principal
+        .credential_id()
+        .unwrap_or_else(|| ANONYMOUS_KEY.to_string())
+}
+
+//Bytes an event counts against the quota: its JSON encoding as the producer sent it
+pub fn ingested_bytes(new_event: &NewEvent) -> Result<u64, AppError> {
+    let bytes =
+        serde_json::to_vec(new_event).map_err(|e| AppError::InternalError(e.to_string()))?;
+    Ok(bytes.len() as u64)
+}
+
+fn next_day(day: NaiveDate) -> DateTime<Utc> {
+    day.checked_add_days(Days::new(1))
+        .unwrap_or(day)
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or_default()
+        .and_utc()
+}
+
+//Counts events and bytes ingested per key per UTC day.  The request rate limiter cannot tell
+//one 1 MB event from one tiny one, so ingestion is charged here by size as well as count.
+pub struct UsageTracker {
+    config: UsageConfig,
+    usage: Mutex<HashMap<String, DailyUsage>>,
+}
+
+impl UsageTracker {
+    #[must_use]
+    pub fn new(config: UsageConfig) -> Self {
+        Self {
+            config,
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    //Adds `events` and `bytes` to the key's usage for the day of `now`, or rejects the whole
+    //charge with QuotaExceeded if it would go over either limit
+    pub fn charge(
+        &self,
+        key: &str,
+        events: u64,
+        bytes: u64,
+        now: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let quota = self.config.quota(key);
+        let mut usage = self
+            .usage
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let today = now.date_naive();
+        let entry = usage.entry(key.to_string()).or_default();
+        if entry.day != today {
+            *entry = DailyUsage {
+                day: today,
+                ..Default::default()
+            };
+        }
+
+        let resets_at = next_day(today).to_rfc3339();
+        if let Some(max_events) = quota.max_events {
+            if entry.events + events > max_events {
+                return Err(AppError::QuotaExceeded(format!(
+                    "'{key}' is limited to {max_events} event(s) per day, resets at {resets_at}"
+                )));
+            }
+        }
+        if let Some(max_bytes) = quota.max_bytes {
+            if entry.bytes + bytes > max_bytes {
+                return Err(AppError::QuotaExceeded(format!(
+                    "'{key}' is limited to {max_bytes} bytes per day, resets at {resets_at}"
+                )));
+            }
+        }
+        entry.events += events;
+        entry.bytes += bytes;
+        Ok(())
+    }
+
+    //Gives back a charge whose events could not be stored
+    pub fn refund(
+        &self,
+        key: &str,
+        events: u64,
+        bytes: u64,
+        now: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut usage = self
+            .usage
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        if let Some(entry) = usage.get_mut(key) {
+            if entry.day == now.date_naive() {
+                entry.events = entry.events.saturating_sub(events);
+                entry.bytes = entry.bytes.saturating_sub(bytes);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn report(&self, key: &str, now: DateTime<Utc>) -> Result<UsageReport, AppError> {
+        let usage = self
+            .usage
+            .lock()
+            .map_err(|e| AppError::InternalError(e.to_string()))?;
+        let today = now.date_naive();
+        let current = usage
+            .get(key)
+            .filter(|entry| entry.day == today)
+            .copied()
+            .unwrap_or_default();
+        Ok(UsageReport {
+            key: key.to_string(),
+            day: today,
+            events: current.events,
+            bytes: current.bytes,
+            quota: self.config.quota(key),
+            resets_at: next_day(today),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Credential;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn tracker() -> UsageTracker {
+        UsageTracker::new(
+            serde_json::from_value(json!({
+                "default": { "max_events": 2 },
+                "keys": { "bulk": { "max_bytes": 100 } }
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_usage_keys_are_prefixed_by_credential() {
+        let key = Principal::new(Credential::ApiKey, "ingest", None, Default::default());
+        let token = Principal::new(Credential::Jwt, "ingest", None, Default::default());
+        assert_eq!(usage_key(&key), "key:ingest");
+        assert_eq!(usage_key(&token), "jwt:ingest");
+        assert_eq!(usage_key(&Principal::unrestricted()), ANONYMOUS_KEY);
+    }
+
+    #[test]
+    fn test_charge_enforces_events_and_bytes() {
+        let tracker = tracker();
+        let now = Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap();
+        tracker.charge("small", 1, 10_000, now).unwrap();
+        tracker.charge("small", 1, 10_000, now).unwrap();
+        assert!(matches!(
+            tracker.charge("small", 1, 1, now),
+            Err(AppError::QuotaExceeded(_))
+        ));
+
+        //A rejected charge is not recorded
+        assert!(tracker.charge("bulk", 5, 101, now).is_err());
+        tracker.charge("bulk", 5, 100, now).unwrap();
+        let report = tracker.report("bulk", now).unwrap();
+        assert_eq!((report.events, report.bytes), (5, 100));
+        assert_eq!(report.quota.max_bytes, Some(100));
+
+        tracker.refund("bulk", 1, 40, now).unwrap();
+        tracker.charge("bulk", 1, 40, now).unwrap();
+    }
+
+    #[test]
+    fn test_usage_resets_at_utc_midnight() {
+        let tracker = tracker();
+        let evening = Utc.with_ymd_and_hms(2025, 1, 1, 23, 59, 0).unwrap();
+        tracker.charge("small", 2, 0, evening).unwrap();
+        let report = tracker.report("small", evening).unwrap();
+        assert_eq!(
+            report.resets_at,
+            Utc.with_ymd_and_hms(2025, 1, 2, 0, 0, 0).unwrap()
+        );
+
+        let morning = Utc.with_ymd_and_hms(2025, 1, 2, 0, 1, 0).unwrap();
+        assert_eq!(tracker.report("small", morning).unwrap().events, 0);
+        tracker.charge("small", 2, 0, morning).unwrap();
+    }
+}
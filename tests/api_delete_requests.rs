@@ -0,0 +1,137 @@
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use chrono::{DateTime, Utc};
+use event_tracker::api::{delete_event, delete_events, get_event_by_id, get_events};
+use event_tracker::model::{Event, EventQuery};
+use event_tracker::storage::{EventStore, InMemoryEventStore};
+use event_tracker::tenant::DEFAULT_TENANT;
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+fn insert_test_events(store: &Arc<dyn EventStore>, types_and_times: &[(&str, &str)]) -> Vec<Uuid> {
+    types_and_times
+        .iter()
+        .map(|(event_type, timestamp_str)| {
+            let event = Event {
+                id: Uuid::new_v4(),
+                event_type: event_type.to_string(),
+                timestamp: DateTime::parse_from_rfc3339(timestamp_str)
+                    .unwrap()
+                    .to_utc(),
+                payload: json!({ "test": true }),
+                schema_version: 1,
+                metadata: Default::default(),
+                received_at: Utc::now(),
+                clock_skewed: false,
+                stream: None,
+            };
+            let id = event.id;
+            store
+                .add_event(DEFAULT_TENANT, event)
+                .expect("Failed to insert test event");
+            id
+        })
+        .collect()
+}
+
+#[actix_rt::test]
+async fn test_delete_event_by_id() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let ids = insert_test_events(&store, &[("login", "2025-01-01T12:00:00Z")]);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .service(get_event_by_id)
+            .service(delete_event),
+    )
+    .await;
+
+    let uri = format!("/events/{}", ids[0]);
+    let req = test::TestRequest::delete().uri(&uri).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["deleted"], 1);
+
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_delete_event_by_id_not_found() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store))
+            .service(delete_event),
+    )
+    .await;
+
+    let uri = format!("/events/{}", Uuid::new_v4());
+    let req = test::TestRequest::delete().uri(&uri).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_delete_events_by_query() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    insert_test_events(
+        &store,
+        &[
+            ("test", "2025-01-01T12:00:00Z"),
+            ("test", "2025-01-02T12:00:00Z"),
+            ("login", "2025-01-02T12:00:00Z"),
+            ("test", "2025-01-03T12:00:00Z"),
+        ],
+    );
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .service(get_events)
+            .service(delete_events),
+    )
+    .await;
+
+    let req = test::TestRequest::delete()
+        .uri("/events?event_type=test&start=2025-01-01T00:00:00Z&end=2025-01-02T23:59:59Z")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["deleted"], 2);
+
+    let remaining = store
+        .query_events(DEFAULT_TENANT, EventQuery::default())
+        .unwrap();
+    assert_eq!(remaining.len(), 2);
+}
+
+#[actix_rt::test]
+async fn test_delete_events_requires_filter() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    insert_test_events(&store, &[("test", "2025-01-01T12:00:00Z")]);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .service(delete_events),
+    )
+    .await;
+
+    let req = test::TestRequest::delete().uri("/events").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        store
+            .query_events(DEFAULT_TENANT, EventQuery::default())
+            .unwrap()
+            .len(),
+        1
+    );
+}
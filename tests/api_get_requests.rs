@@ -1,9 +1,11 @@
 use actix_web::http::StatusCode;
 use actix_web::{test, web, App};
 use chrono::{DateTime, TimeZone, Utc};
-use event_tracker::api::{get_event_by_id, get_events};
-use event_tracker::model::Event;
+use event_tracker::api::{get_event_by_id, get_events, get_stats};
+use event_tracker::model::{Event, StoreStats};
 use event_tracker::storage::{EventStore, InMemoryEventStore};
+use event_tracker::tenant::DEFAULT_TENANT;
+use event_tracker::upcast::UpcasterRegistry;
 use serde_json::json;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -19,9 +21,16 @@ pub fn insert_test_events(store: Arc<dyn EventStore>, types_and_times: &[(&str,
             event_type: event_type.to_string(),
             timestamp,
             payload: json!({ "test": true }),
+            schema_version: 1,
+            metadata: Default::default(),
+            received_at: Utc::now(),
+            clock_skewed: false,
+            stream: None,
         };
 
-        store.add_event(event).expect("Failed to insert test event");
+        store
+            .add_event(DEFAULT_TENANT, event)
+            .expect("Failed to insert test event");
     }
 }
 
@@ -33,8 +42,13 @@ async fn test_get_events_returns_inserted_event() {
         event_type: "login".into(),
         timestamp: Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap(),
         payload: serde_json::json!({ "user_id": 1 }),
+        schema_version: 1,
+        metadata: Default::default(),
+        received_at: Utc::now(),
+        clock_skewed: false,
+        stream: None,
     };
-    store.add_event(event.clone()).unwrap();
+    store.add_event(DEFAULT_TENANT, event.clone()).unwrap();
 
     let app = test::init_service(
         App::new()
@@ -259,9 +273,14 @@ async fn test_get_event_by_id_success() {
         event_type: "test".into(),
         timestamp: Utc::now(),
         payload: serde_json::json!({ "val": 123 }),
+        schema_version: 1,
+        metadata: Default::default(),
+        received_at: Utc::now(),
+        clock_skewed: false,
+        stream: None,
     };
 
-    store.add_event(event.clone()).unwrap();
+    store.add_event(DEFAULT_TENANT, event.clone()).unwrap();
 
     let app = test::init_service(
         App::new()
@@ -281,3 +300,106 @@ async fn test_get_event_by_id_success() {
     assert_eq!(returned_event.id, event.id);
     assert_eq!(returned_event.event_type, event.event_type);
 }
+
+#[actix_rt::test]
+async fn test_get_stats_reports_deep_memory_usage() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let event = Event {
+        id: Uuid::new_v4(),
+        event_type: "upload".into(),
+        timestamp: Utc::now(),
+        payload: serde_json::json!({ "body": "z".repeat(2048) }),
+        schema_version: 1,
+        metadata: Default::default(),
+        received_at: Utc::now(),
+        clock_skewed: false,
+        stream: None,
+    };
+    store.add_event(DEFAULT_TENANT, event.clone()).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store))
+            .service(get_stats),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/stats").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body_bytes = test::read_body(resp).await;
+    let stats: StoreStats = serde_json::from_slice(&body_bytes).unwrap();
+
+    assert_eq!(stats.event_count, 1);
+    assert_eq!(stats.events_received, 1);
+    assert!(stats.memory_bytes >= event.deep_size());
+}
+
+#[actix_rt::test]
+async fn test_get_events_upcasts_older_schema_versions() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let old = Event {
+        id: Uuid::new_v4(),
+        event_type: "purchase".into(),
+        timestamp: Utc::now(),
+        payload: json!({ "amount_cents": 1250 }),
+        schema_version: 1,
+        metadata: Default::default(),
+        received_at: Utc::now(),
+        clock_skewed: false,
+        stream: None,
+    };
+    let current = Event {
+        id: Uuid::new_v4(),
+        event_type: "purchase".into(),
+        timestamp: Utc::now(),
+        payload: json!({ "amount": { "value": 3000, "currency": "USD" } }),
+        schema_version: 2,
+        metadata: Default::default(),
+        received_at: Utc::now(),
+        clock_skewed: false,
+        stream: None,
+    };
+    store.add_event(DEFAULT_TENANT, old.clone()).unwrap();
+    store.add_event(DEFAULT_TENANT, current.clone()).unwrap();
+
+    let upcasters = UpcasterRegistry::new();
+    upcasters
+        .register(
+            "purchase",
+            1,
+            |payload| json!({ "amount": { "value": payload["amount_cents"], "currency": "USD" } }),
+        )
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .app_data(web::Data::new(upcasters))
+            .service(get_events)
+            .service(get_event_by_id),
+    )
+    .await;
+
+    let uri = format!("/events/{}", old.id);
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let returned: Event = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(returned.schema_version, 2);
+    assert_eq!(
+        returned.payload,
+        json!({ "amount": { "value": 1250, "currency": "USD" } })
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/events?event_type=purchase")
+        .to_request();
+    let returned: Vec<Event> = test::call_and_read_body_json(&app, req).await;
+    assert!(returned.iter().all(|e| e.schema_version == 2));
+    assert!(returned
+        .iter()
+        .all(|e| e.payload["amount"]["currency"] == "USD"));
+
+    //Upcasting happens at read time; the stored event keeps its original version
+    assert_eq!(store.get_by_id(DEFAULT_TENANT, old.id).unwrap(), Some(old));
+}
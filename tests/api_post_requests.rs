@@ -1,9 +1,14 @@
 use event_tracker::{
-    api::post_event,
+    api::{get_events, post_event},
+    model::Event,
+    redaction::{RedactionRule, Redactor, REDACTED_MARKER},
+    skew::{ClockSkewAction, ClockSkewPolicy},
     storage::{EventStore, InMemoryEventStore},
+    tenant::DEFAULT_TENANT,
 };
 
 use actix_web::{http::StatusCode, test, web, App};
+use chrono::{Duration, Utc};
 use std::sync::Arc;
 
 #[actix_rt::test]
@@ -104,3 +109,206 @@ async fn test_post_event_malformed_json() {
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
+
+#[actix_rt::test]
+async fn test_post_event_redacts_payload_before_storing() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let store_data: web::Data<Arc<dyn EventStore>> = web::Data::new(store.clone());
+    let mut rules = RedactionRule::builtins();
+    rules.push(RedactionRule::Path {
+        path: "user.ssn".into(),
+    });
+    let redactor = web::Data::new(Redactor::new(rules).unwrap());
+    let app = test::init_service(
+        App::new()
+            .app_data(store_data.clone())
+            .app_data(redactor)
+            .service(post_event),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .insert_header(("Content-Type", "application/json"))
+        .set_payload(
+            r#"{
+                "event_type": "checkout",
+                "timestamp": "2025-01-01T12:00:00Z",
+                "payload": {
+                    "email": "jo@example.com",
+                    "card": "4111-1111-1111-1111",
+                    "ip": "192.168.1.20",
+                    "user": { "ssn": "123-45-6789", "plan": "pro" }
+                }
+            }"#,
+        )
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let returned: Event = test::read_body_json(resp).await;
+
+    let expected = serde_json::json!({
+        "email": "[redacted:email]",
+        "card": "[redacted:card_number]",
+        "ip": "[redacted:ip_address]",
+        "user": { "ssn": REDACTED_MARKER, "plan": "pro" }
+    });
+    assert_eq!(returned.payload, expected);
+    let stored = store
+        .get_by_id(DEFAULT_TENANT, returned.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.payload, expected);
+}
+
+#[actix_rt::test]
+async fn test_post_event_schema_version() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let store_data: web::Data<Arc<dyn EventStore>> = web::Data::new(store.clone());
+    let app = test::init_service(App::new().app_data(store_data.clone()).service(post_event)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(serde_json::json!({
+            "event_type": "login",
+            "timestamp": "2025-01-01T12:00:00Z",
+            "payload": { "user_id": 1 },
+            "schema_version": 3
+        }))
+        .to_request();
+    let returned: Event = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(returned.schema_version, 3);
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(serde_json::json!({
+            "event_type": "login",
+            "timestamp": "2025-01-01T12:00:00Z",
+            "payload": { "user_id": 1 }
+        }))
+        .to_request();
+    let returned: Event = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(returned.schema_version, 1);
+}
+
+#[actix_rt::test]
+async fn test_post_event_metadata_is_stored_and_filterable() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let store_data: web::Data<Arc<dyn EventStore>> = web::Data::new(store.clone());
+    let app = test::init_service(
+        App::new()
+            .app_data(store_data.clone())
+            .service(post_event)
+            .service(get_events),
+    )
+    .await;
+
+    for (event_type, correlation_id) in [("order_placed", "flow-1"), ("order_placed", "flow-2")] {
+        let req = test::TestRequest::post()
+            .uri("/events")
+            .set_json(serde_json::json!({
+                "event_type": event_type,
+                "timestamp": "2025-01-01T12:00:00Z",
+                "payload": { "order_id": 7 },
+                "metadata": {
+                    "source": "checkout",
+                    "actor": "user-42",
+                    "correlation_id": correlation_id,
+                    "trace_id": "4bf92f3577b34da6"
+                }
+            }))
+            .to_request();
+        let returned: Event = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(returned.metadata.source.as_deref(), Some("checkout"));
+        assert_eq!(
+            returned.metadata.correlation_id.as_deref(),
+            Some(correlation_id)
+        );
+        assert!(returned.metadata.causation_id.is_none());
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/events?correlation_id=flow-1&source=checkout")
+        .to_request();
+    let returned: Vec<Event> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(returned.len(), 1);
+    assert_eq!(returned[0].metadata.actor.as_deref(), Some("user-42"));
+
+    let req = test::TestRequest::get()
+        .uri("/events?actor=someone-else")
+        .to_request();
+    let returned: Vec<Event> = test::call_and_read_body_json(&app, req).await;
+    assert!(returned.is_empty());
+}
+
+#[actix_rt::test]
+async fn test_post_event_timestamp_defaults_to_received_at() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let store_data: web::Data<Arc<dyn EventStore>> = web::Data::new(store.clone());
+    let app = test::init_service(App::new().app_data(store_data.clone()).service(post_event)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(serde_json::json!({
+            "event_type": "login",
+            "payload": { "user_id": 1 }
+        }))
+        .to_request();
+    let returned: Event = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(returned.timestamp, returned.received_at);
+    assert!(!returned.clock_skewed);
+}
+
+#[actix_rt::test]
+async fn test_post_event_clock_skew_policy() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let store_data: web::Data<Arc<dyn EventStore>> = web::Data::new(store.clone());
+    let policy = |action| ClockSkewPolicy {
+        max_future: Some(Duration::minutes(5)),
+        max_past: Some(Duration::days(30)),
+        action,
+    };
+    let far_future = (Utc::now() + Duration::days(365 * 3)).to_rfc3339();
+    let body = serde_json::json!({
+        "event_type": "login",
+        "timestamp": far_future,
+        "payload": { "user_id": 1 }
+    });
+
+    let app = test::init_service(
+        App::new()
+            .app_data(store_data.clone())
+            .app_data(web::Data::new(policy(ClockSkewAction::Reject)))
+            .service(post_event),
+    )
+    .await;
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(&body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(store.stats(None).unwrap().event_count, 0);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(store_data.clone())
+            .app_data(web::Data::new(policy(ClockSkewAction::Flag)))
+            .service(post_event),
+    )
+    .await;
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(&body)
+        .to_request();
+    let returned: Event = test::call_and_read_body_json(&app, req).await;
+    assert!(returned.clock_skewed);
+    assert!(
+        store
+            .get_by_id(DEFAULT_TENANT, returned.id)
+            .unwrap()
+            .unwrap()
+            .clock_skewed
+    );
+}
@@ -0,0 +1,121 @@
+use actix_web::http::StatusCode;
+use actix_web::middleware::from_fn;
+use actix_web::{test, web, App};
+use event_tracker::api::{delete_events, get_events, healthz, post_event};
+use event_tracker::auth::{authenticate, hash_key, ApiKeyConfig, ApiKeys, API_KEY_HEADER};
+use event_tracker::storage::{EventStore, InMemoryEventStore};
+use serde_json::json;
+use std::sync::Arc;
+
+fn api_keys() -> web::Data<ApiKeys> {
+    let config: ApiKeyConfig = serde_json::from_value(json!({
+        "keys": [
+            { "id": "ingest", "key_sha256": hash_key("ingest-secret"), "scopes": ["events:write"] },
+            { "id": "dashboard", "key_sha256": hash_key("read-secret"), "scopes": ["events:read"] },
+            { "id": "ops", "key_sha256": hash_key("ops-secret"), "scopes": ["admin"] }
+        ]
+    }))
+    .unwrap();
+    web::Data::new(ApiKeys::from_config(config))
+}
+
+fn login() -> serde_json::Value {
+    json!({ "event_type": "login", "timestamp": "2025-01-01T12:00:00Z", "payload": { "user_id": 1 } })
+}
+
+#[actix_rt::test]
+async fn test_scopes_are_enforced_per_route() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(authenticate))
+            .app_data(web::Data::new(store.clone()))
+            .app_data(api_keys())
+            .service(post_event)
+            .service(get_events)
+            .service(delete_events)
+            .service(healthz),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .insert_header(("Authorization", "Bearer ingest-secret"))
+        .set_json(login())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    //A write-only key cannot read
+    let req = test::TestRequest::get()
+        .uri("/events")
+        .insert_header(("Authorization", "Bearer ingest-secret"))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/events")
+        .insert_header((API_KEY_HEADER, "read-secret"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::delete()
+        .uri("/events?event_type=login")
+        .insert_header((API_KEY_HEADER, "read-secret"))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    //Admin satisfies every scope
+    let req = test::TestRequest::delete()
+        .uri("/events?event_type=login")
+        .insert_header((API_KEY_HEADER, "ops-secret"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    assert_eq!(store.stats(None).unwrap().event_count, 0);
+}
+
+#[actix_rt::test]
+async fn test_missing_or_invalid_keys_are_unauthorized() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(authenticate))
+            .app_data(web::Data::new(store.clone()))
+            .app_data(api_keys())
+            .service(post_event)
+            .service(healthz),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(login())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.headers().get("WWW-Authenticate").unwrap(), "Bearer");
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .insert_header(("Authorization", "Bearer guessed"))
+        .set_json(login())
+        .to_request();
+    let err = test::try_call_service(&app, req)
+        .await
+        .err()
+        .expect("invalid key should be rejected by the middleware");
+    assert_eq!(
+        err.as_response_error().status_code(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(store.stats(None).unwrap().event_count, 0);
+
+    //Probes do not require credentials
+    let req = test::TestRequest::get().uri("/healthz").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+}
@@ -0,0 +1,152 @@
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use event_tracker::api::{
+    delete_event_type, get_catalog, get_event_type, post_event, put_catalog, put_event_type,
+};
+use event_tracker::catalog::{CatalogConfig, EventTypeCatalog, EventTypeDefinition};
+use event_tracker::storage::{EventStore, InMemoryEventStore};
+use serde_json::json;
+use std::sync::Arc;
+
+fn event_body(event_type: &str) -> serde_json::Value {
+    json!({
+        "event_type": event_type,
+        "timestamp": "2025-01-01T12:00:00Z",
+        "payload": { "user_id": 1 }
+    })
+}
+
+#[actix_rt::test]
+async fn test_strict_catalog_rejects_unknown_event_types() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store))
+            .app_data(web::Data::new(EventTypeCatalog::new()))
+            .service(post_event)
+            .service(put_catalog)
+            .service(put_event_type),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(event_body("signup"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::put()
+        .uri("/admin/catalog")
+        .set_json(json!({
+            "strict": true,
+            "event_types": { "login": { "description": "User signed in", "owner": "identity" } }
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(event_body("signup"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["message"], "Unknown event type 'signup'");
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(event_body("login"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    //Teams can add types at runtime without a redeploy
+    let req = test::TestRequest::put()
+        .uri("/admin/event-types/signup")
+        .set_json(json!({ "owner": "growth" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(event_body("signup"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn test_disabled_event_type_rejected_in_lenient_mode() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let catalog = EventTypeCatalog::new();
+    catalog
+        .upsert(
+            "debug",
+            serde_json::from_value(json!({ "status": "disabled" })).unwrap(),
+        )
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store))
+            .app_data(web::Data::new(catalog))
+            .service(post_event),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(event_body("debug"))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[actix_rt::test]
+async fn test_event_type_admin_lifecycle() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store))
+            .app_data(web::Data::new(EventTypeCatalog::new()))
+            .service(get_catalog)
+            .service(get_event_type)
+            .service(put_event_type)
+            .service(delete_event_type),
+    )
+    .await;
+
+    let req = test::TestRequest::put()
+        .uri("/admin/event-types/login")
+        .set_json(
+            json!({ "description": "User signed in", "owner": "identity", "status": "deprecated" }),
+        )
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/admin/event-types/login")
+        .to_request();
+    let definition: EventTypeDefinition = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(definition.owner.as_deref(), Some("identity"));
+
+    let req = test::TestRequest::get().uri("/admin/catalog").to_request();
+    let catalog: CatalogConfig = test::call_and_read_body_json(&app, req).await;
+    assert!(!catalog.strict);
+    assert_eq!(catalog.event_types.len(), 1);
+
+    let req = test::TestRequest::delete()
+        .uri("/admin/event-types/login")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/admin/event-types/login")
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
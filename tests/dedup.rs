@@ -0,0 +1,130 @@
+use actix_web::{test, web, App};
+use event_tracker::api::post_event;
+use event_tracker::dedup::DedupWindow;
+use event_tracker::idempotency::IDEMPOTENT_REPLAYED_HEADER;
+use event_tracker::metrics::Metrics;
+use event_tracker::model::Event;
+use event_tracker::storage::{EventStore, InMemoryEventStore};
+use event_tracker::tenant::DEFAULT_TENANT;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[actix_rt::test]
+async fn test_replayed_batch_is_dropped_within_window() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let dedup = web::Data::new(DedupWindow::new(Duration::from_secs(60)));
+    let metrics = web::Data::new(Metrics::new().unwrap());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .app_data(dedup.clone())
+            .app_data(metrics.clone())
+            .service(post_event),
+    )
+    .await;
+    let batch = [
+        json!({ "event_type": "purchase", "timestamp": "2025-01-01T12:00:00Z", "payload": { "order_id": 7 } }),
+        json!({ "event_type": "purchase", "timestamp": "2025-01-01T12:00:01Z", "payload": { "order_id": 8 } }),
+    ];
+
+    let mut originals = Vec::new();
+    for body in &batch {
+        let req = test::TestRequest::post()
+            .uri("/events")
+            .set_json(body)
+            .to_request();
+        let event: Event = test::call_and_read_body_json(&app, req).await;
+        originals.push(event);
+    }
+
+    //The emitter replays the whole batch after a failure
+    for (body, original) in batch.iter().zip(&originals) {
+        let req = test::TestRequest::post()
+            .uri("/events")
+            .set_json(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.headers().get(IDEMPOTENT_REPLAYED_HEADER).unwrap(),
+            "true"
+        );
+        let replayed: Event = test::read_body_json(resp).await;
+        assert_eq!(&replayed, original);
+    }
+
+    assert_eq!(store.stats(None).unwrap().event_count, 2);
+    assert_eq!(dedup.dropped(), 2);
+    //Without a catalog every type is counted under "other"
+    assert!(metrics
+        .render()
+        .unwrap()
+        .contains("event_tracker_events_deduplicated_total{event_type=\"other\"} 2"));
+}
+
+#[actix_rt::test]
+async fn test_retries_without_timestamp_are_dropped() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let dedup = web::Data::new(DedupWindow::new(Duration::from_secs(60)));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .app_data(dedup.clone())
+            .service(post_event),
+    )
+    .await;
+    //The stored timestamp defaults to the arrival time, which differs between the two copies
+    let body = json!({ "event_type": "purchase", "payload": { "order_id": 7 } });
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(&body)
+        .to_request();
+    let first: Event = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(&body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(
+        resp.headers().get(IDEMPOTENT_REPLAYED_HEADER).unwrap(),
+        "true"
+    );
+    let replayed: Event = test::read_body_json(resp).await;
+    assert_eq!(replayed, first);
+    assert_eq!(store.stats(None).unwrap().event_count, 1);
+    assert_eq!(dedup.dropped(), 1);
+}
+
+#[actix_rt::test]
+async fn test_duplicate_of_deleted_event_is_stored() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let dedup = web::Data::new(DedupWindow::new(Duration::from_secs(60)));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .app_data(dedup.clone())
+            .service(post_event),
+    )
+    .await;
+    let body = json!({ "event_type": "purchase", "timestamp": "2025-01-01T12:00:00Z", "payload": { "order_id": 7 } });
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(&body)
+        .to_request();
+    let first: Event = test::call_and_read_body_json(&app, req).await;
+    store.delete(DEFAULT_TENANT, first.id).unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(&body)
+        .to_request();
+    let second: Event = test::call_and_read_body_json(&app, req).await;
+    assert_ne!(second.id, first.id);
+    assert!(store
+        .get_by_id(DEFAULT_TENANT, second.id)
+        .unwrap()
+        .is_some());
+    assert_eq!(dedup.dropped(), 0);
+}
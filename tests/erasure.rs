@@ -0,0 +1,159 @@
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use chrono::Utc;
+use event_tracker::api::{get_erasure_by_id, get_erasures, post_erasure};
+use event_tracker::erasure::{hash_subject, ErasureReceipt, ErasureService, ERASED_MARKER};
+use event_tracker::model::{Event, EventQuery};
+use event_tracker::projection::ProjectionRegistry;
+use event_tracker::storage::{EventStore, InMemoryEventStore};
+use event_tracker::tenant::DEFAULT_TENANT;
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+fn insert(store: &Arc<dyn EventStore>, payload: serde_json::Value) -> Uuid {
+    let event = Event {
+        id: Uuid::new_v4(),
+        event_type: "signup".into(),
+        timestamp: Utc::now(),
+        payload,
+        schema_version: 1,
+        metadata: Default::default(),
+        received_at: Utc::now(),
+        clock_skewed: false,
+        stream: None,
+    };
+    let id = event.id;
+    store.add_event(DEFAULT_TENANT, event).unwrap();
+    id
+}
+
+const SECRET: &[u8] = b"receipt-secret";
+
+#[actix_rt::test]
+async fn test_post_erasure_deletes_and_issues_receipt() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let erased = insert(&store, json!({ "email": "jo@example.com", "plan": "pro" }));
+    let kept = insert(&store, json!({ "email": "sam@example.com" }));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .app_data(web::Data::new(ErasureService::new(
+                vec!["user_id".into(), "email".into()],
+                SECRET,
+            )))
+            .service(post_erasure)
+            .service(get_erasures)
+            .service(get_erasure_by_id),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/erasures")
+        .set_json(json!({ "subject": "jo@example.com" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let receipt: ErasureReceipt = test::read_body_json(resp).await;
+
+    assert_eq!(receipt.events_affected, 1);
+    assert_eq!(receipt.event_ids, vec![erased]);
+    assert_eq!(receipt.subject_hash, hash_subject(SECRET, "jo@example.com"));
+    assert!(store.get_by_id(DEFAULT_TENANT, erased).unwrap().is_none());
+    assert!(store.get_by_id(DEFAULT_TENANT, kept).unwrap().is_some());
+
+    let uri = format!("/erasures/{}", receipt.id);
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let fetched: ErasureReceipt = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(fetched, receipt);
+
+    let req = test::TestRequest::get().uri("/erasures").to_request();
+    let all: Vec<ErasureReceipt> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(all, vec![receipt]);
+}
+
+#[actix_rt::test]
+async fn test_post_erasure_anonymizes_with_custom_paths() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    insert(&store, json!({ "customer": { "id": 99 }, "total": 10 }));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .app_data(web::Data::new(ErasureService::new(
+                vec!["user_id".into()],
+                SECRET,
+            )))
+            .service(post_erasure),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/erasures")
+        .set_json(json!({ "subject": "99", "mode": "anonymize", "paths": ["customer.id"] }))
+        .to_request();
+    let receipt: ErasureReceipt = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(receipt.events_affected, 1);
+
+    let events = store
+        .query_events(DEFAULT_TENANT, EventQuery::default())
+        .unwrap();
+    assert_eq!(
+        events[0].payload,
+        json!({ "customer": { "id": ERASED_MARKER }, "total": 10 })
+    );
+}
+
+#[actix_rt::test]
+async fn test_post_erasure_rebuilds_projections() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    insert(&store, json!({ "email": "jo@example.com" }));
+    insert(&store, json!({ "email": "sam@example.com" }));
+    let projections = web::Data::new(ProjectionRegistry::new());
+    projections
+        .register(
+            DEFAULT_TENANT,
+            "emails",
+            serde_json::from_value(json!({ "reducer": "set", "field": "email" })).unwrap(),
+            store.as_ref(),
+        )
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .app_data(web::Data::new(ErasureService::new(
+                vec!["email".into()],
+                SECRET,
+            )))
+            .app_data(projections.clone())
+            .service(post_erasure),
+    )
+    .await;
+    let req = test::TestRequest::post()
+        .uri("/erasures")
+        .set_json(json!({ "subject": "jo@example.com", "mode": "anonymize" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let state = projections.get(DEFAULT_TENANT, "emails").unwrap().unwrap();
+    assert_eq!(state.state, json!([ERASED_MARKER, "sam@example.com"]));
+}
+
+#[actix_rt::test]
+async fn test_get_erasure_receipt_not_found() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store))
+            .app_data(web::Data::new(ErasureService::new(vec![], SECRET)))
+            .service(get_erasure_by_id),
+    )
+    .await;
+
+    let uri = format!("/erasures/{}", Uuid::new_v4());
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
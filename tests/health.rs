@@ -0,0 +1,95 @@
+use actix_web::middleware::from_fn;
+use actix_web::{http::StatusCode, test, web, App};
+use chrono::Utc;
+use event_tracker::api::{get_events, healthz, readyz};
+use event_tracker::model::Event;
+use event_tracker::rate_limit::{limit_requests, RateLimitConfig, RequestLimiter, RouteLimit};
+use event_tracker::storage::{EventStore, InMemoryEventStore};
+use event_tracker::tenant::DEFAULT_TENANT;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_probes_bypass_rate_limit() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let limiter = RequestLimiter::new(&RateLimitConfig {
+        reads: RouteLimit {
+            period_ms: 60_000,
+            burst: 1,
+        },
+        ..Default::default()
+    })
+    .unwrap();
+
+    //Wired like main.rs: probes outside the rate-limited scope
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store))
+            .app_data(web::Data::new(limiter))
+            .service(healthz)
+            .service(readyz)
+            .service(
+                web::scope("")
+                    .wrap(from_fn(limit_requests))
+                    .service(get_events),
+            ),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/events").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get().uri("/events").to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    for uri in ["/healthz", "/readyz", "/healthz", "/readyz"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+        assert!(resp.headers().get("X-RateLimit-Limit").is_none(), "{uri}");
+    }
+}
+
+#[actix_rt::test]
+async fn test_readyz_unavailable_when_over_capacity() {
+    let event = Event {
+        id: Uuid::new_v4(),
+        event_type: "bulk".into(),
+        timestamp: Utc::now(),
+        payload: serde_json::json!({ "blob": "x".repeat(128) }),
+        schema_version: 1,
+        metadata: Default::default(),
+        received_at: Utc::now(),
+        clock_skewed: false,
+        stream: None,
+    };
+    //Size the budget so the event fills it exactly; writes beyond it are rejected
+    let sizing = InMemoryEventStore::new();
+    sizing.add_event(DEFAULT_TENANT, event.clone()).unwrap();
+    let store: Arc<dyn EventStore> =
+        Arc::new(InMemoryEventStore::new().with_capacity_bytes(sizing.memory_usage()));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .service(healthz)
+            .service(readyz),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/readyz").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    store.add_event(DEFAULT_TENANT, event).unwrap();
+
+    let req = test::TestRequest::get().uri("/readyz").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Service unavailable");
+
+    let req = test::TestRequest::get().uri("/healthz").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+}
@@ -0,0 +1,123 @@
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use event_tracker::api::post_event;
+use event_tracker::idempotency::{
+    IdempotencyCache, IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER,
+};
+use event_tracker::model::Event;
+use event_tracker::storage::{EventStore, InMemoryEventStore};
+use event_tracker::tenant::DEFAULT_TENANT;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_idempotency_key_replays_original_event() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .app_data(web::Data::new(IdempotencyCache::new(Duration::from_secs(
+                60,
+            ))))
+            .service(post_event),
+    )
+    .await;
+    let body = json!({
+        "event_type": "purchase",
+        "timestamp": "2025-01-01T12:00:00Z",
+        "payload": { "order_id": 7 }
+    });
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .insert_header((IDEMPOTENCY_KEY_HEADER, "order-7"))
+        .set_json(&body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get(IDEMPOTENT_REPLAYED_HEADER).is_none());
+    let original: Event = test::read_body_json(resp).await;
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .insert_header((IDEMPOTENCY_KEY_HEADER, "order-7"))
+        .set_json(&body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(IDEMPOTENT_REPLAYED_HEADER).unwrap(),
+        "true"
+    );
+    let replayed: Event = test::read_body_json(resp).await;
+
+    assert_eq!(replayed, original);
+    let stats = store.stats(None).unwrap();
+    assert_eq!(stats.event_count, 1);
+    assert_eq!(stats.events_received, 1);
+}
+
+#[actix_rt::test]
+async fn test_idempotency_key_reused_with_different_body_conflicts() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .app_data(web::Data::new(IdempotencyCache::new(Duration::from_secs(
+                60,
+            ))))
+            .service(post_event),
+    )
+    .await;
+
+    for (order_id, expected) in [(7, StatusCode::OK), (8, StatusCode::CONFLICT)] {
+        let req = test::TestRequest::post()
+            .uri("/events")
+            .insert_header((IDEMPOTENCY_KEY_HEADER, "order-7"))
+            .set_json(json!({
+                "event_type": "purchase",
+                "timestamp": "2025-01-01T12:00:00Z",
+                "payload": { "order_id": order_id }
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), expected);
+    }
+    assert_eq!(store.stats(None).unwrap().event_count, 1);
+}
+
+#[actix_rt::test]
+async fn test_client_supplied_id_is_kept_and_never_overwritten() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    //No cache registered: the stored id alone makes retries safe
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .service(post_event),
+    )
+    .await;
+    let id = Uuid::new_v4();
+
+    let mut returned = Vec::new();
+    for order_id in [7, 8] {
+        let req = test::TestRequest::post()
+            .uri("/events")
+            .set_json(json!({
+                "id": id,
+                "event_type": "purchase",
+                "timestamp": "2025-01-01T12:00:00Z",
+                "payload": { "order_id": order_id }
+            }))
+            .to_request();
+        let event: Event = test::call_and_read_body_json(&app, req).await;
+        returned.push(event);
+    }
+
+    assert_eq!(returned[0].id, id);
+    assert_eq!(returned[1], returned[0]);
+    let stored = store.get_by_id(DEFAULT_TENANT, id).unwrap().unwrap();
+    assert_eq!(stored.payload, json!({ "order_id": 7 }));
+    assert_eq!(store.stats(None).unwrap().events_received, 1);
+}
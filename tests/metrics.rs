@@ -0,0 +1,135 @@
+use actix_web::middleware::from_fn;
+use actix_web::{http::StatusCode, test, web, App};
+use event_tracker::api::{get_event_by_id, get_events, get_metrics, post_event};
+use event_tracker::catalog::EventTypeCatalog;
+use event_tracker::metrics::{track_requests, Metrics};
+use event_tracker::rate_limit::{limit_requests, RateLimitConfig, RequestLimiter, RouteLimit};
+use event_tracker::storage::{EventStore, InMemoryEventStore};
+use event_tracker::usage::UsageTracker;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_metrics_exposes_ingest_route_and_error_counters() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let metrics = web::Data::new(Metrics::new().unwrap());
+    let catalog = EventTypeCatalog::from_config(
+        serde_json::from_value(serde_json::json!({ "event_types": { "login": {} } })).unwrap(),
+    );
+
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(track_requests))
+            .app_data(web::Data::new(store))
+            .app_data(metrics.clone())
+            .app_data(web::Data::new(catalog))
+            .service(post_event)
+            .service(get_event_by_id)
+            .service(get_metrics),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .insert_header(("Content-Type", "application/json"))
+        .set_payload(
+            r#"{"event_type": "login", "timestamp": "2025-01-01T12:00:00Z", "payload": {"user_id": 1}}"#,
+        )
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    //Types missing from the catalog share one label
+    for event_type in ["made-up-1", "made-up-2"] {
+        let req = test::TestRequest::post()
+            .uri("/events")
+            .set_json(serde_json::json!({ "event_type": event_type, "payload": {} }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    let uri = format!("/events/{}", Uuid::new_v4());
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+
+    assert!(body.contains(r#"event_tracker_events_ingested_total{event_type="login"} 1"#));
+    assert!(body.contains(r#"event_tracker_events_ingested_total{event_type="other"} 2"#));
+    assert!(!body.contains("made-up"));
+    assert!(body.contains(
+        r#"event_tracker_http_requests_total{method="POST",route="/events",status="200"} 3"#
+    ));
+    assert!(body.contains(
+        r#"event_tracker_http_requests_total{method="GET",route="/events/{id}",status="404"} 1"#
+    ));
+    assert!(body.contains(
+        r#"event_tracker_http_request_duration_seconds_count{method="POST",route="/events"} 3"#
+    ));
+    assert!(body.contains(r#"event_tracker_errors_total{variant="NotFound"} 1"#));
+    assert!(body.contains("event_tracker_store_events 3"));
+}
+
+#[actix_rt::test]
+async fn test_metrics_counts_rate_limited_requests() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let metrics = web::Data::new(Metrics::new().unwrap());
+    let limiter = RequestLimiter::new(&RateLimitConfig {
+        reads: RouteLimit {
+            period_ms: 60_000,
+            burst: 1,
+        },
+        ..Default::default()
+    })
+    .unwrap();
+    //Every ingest is over the daily quota, which is also answered with a 429
+    let usage = UsageTracker::new(
+        serde_json::from_value(serde_json::json!({ "default": { "max_events": 0 } })).unwrap(),
+    );
+
+    //Wired like main.rs: track_requests outside, limit_requests on the API scope
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(track_requests))
+            .app_data(web::Data::new(store.clone()))
+            .app_data(metrics.clone())
+            .app_data(web::Data::new(limiter))
+            .app_data(web::Data::new(usage))
+            .service(
+                web::scope("")
+                    .wrap(from_fn(limit_requests))
+                    .service(get_events)
+                    .service(post_event),
+            ),
+    )
+    .await;
+
+    for _ in 0..3 {
+        let req = test::TestRequest::get().uri("/events").to_request();
+        test::call_service(&app, req).await;
+    }
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(serde_json::json!({ "event_type": "login", "payload": {} }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    metrics.set_store_stats(&store.stats(None).unwrap());
+    let body = metrics.render().unwrap();
+
+    assert!(body.contains("event_tracker_rate_limited_total 2"));
+    assert!(body.contains(
+        r#"event_tracker_http_requests_total{method="GET",route="/events",status="429"} 2"#
+    ));
+    assert!(body.contains(
+        r#"event_tracker_http_requests_total{method="POST",route="/events",status="429"} 1"#
+    ));
+    assert!(body.contains(r#"event_tracker_errors_total{variant="QuotaExceeded"} 1"#));
+}
@@ -0,0 +1,122 @@
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use event_tracker::api::{
+    append_stream_events, delete_projection, get_projection, get_projections, post_event,
+    put_projection,
+};
+use event_tracker::model::Event;
+use event_tracker::projection::{ProjectionRegistry, ProjectionState};
+use event_tracker::storage::{EventStore, InMemoryEventStore};
+use serde_json::json;
+use std::sync::Arc;
+
+fn purchase(user: &str, amount: u64) -> serde_json::Value {
+    json!({
+        "event_type": "purchase",
+        "timestamp": "2025-01-01T12:00:00Z",
+        "payload": { "user_id": user, "amount": amount }
+    })
+}
+
+#[actix_rt::test]
+async fn test_projection_backfills_and_follows_ingest() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .app_data(web::Data::new(ProjectionRegistry::new()))
+            .service(post_event)
+            .service(append_stream_events)
+            .service(put_projection)
+            .service(get_projection)
+            .service(get_projections),
+    )
+    .await;
+
+    //Stored before the projection exists, picked up by the backfill
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(purchase("jo", 10))
+        .to_request();
+    let _: Event = test::call_and_read_body_json(&app, req).await;
+
+    let req = test::TestRequest::put()
+        .uri("/projections/spend_per_user")
+        .set_json(json!({
+            "event_type": "purchase",
+            "group_by": "user_id",
+            "reducer": "sum",
+            "field": "amount"
+        }))
+        .to_request();
+    let state: ProjectionState = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(state.state, json!({ "jo": 10 }));
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(purchase("jo", 5))
+        .to_request();
+    let _: Event = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::post()
+        .uri("/streams/cart-1/events")
+        .set_json(json!({ "events": [purchase("sam", 7)] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/projections/spend_per_user")
+        .to_request();
+    let state: ProjectionState = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(state.state, json!({ "jo": 15, "sam": 7 }));
+    assert_eq!(state.events_applied, 3);
+
+    let req = test::TestRequest::get().uri("/projections").to_request();
+    let listed: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(listed["spend_per_user"]["reducer"], "sum");
+}
+
+#[actix_rt::test]
+async fn test_projection_not_found_and_invalid_definition() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .app_data(web::Data::new(ProjectionRegistry::new()))
+            .service(put_projection)
+            .service(get_projection)
+            .service(delete_projection),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/projections/missing")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::put()
+        .uri("/projections/bad")
+        .set_json(json!({ "reducer": "median", "field": "amount" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::put()
+        .uri("/projections/all")
+        .set_json(json!({ "reducer": "count" }))
+        .to_request();
+    let state: ProjectionState = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(state.state, json!(0));
+
+    let req = test::TestRequest::delete()
+        .uri("/projections/all")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let req = test::TestRequest::delete()
+        .uri("/projections/all")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
@@ -1,10 +1,16 @@
 use actix_governor::{Governor, GovernorConfigBuilder, KeyExtractor};
-use actix_web::dev::ServiceRequest;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::from_fn;
 use actix_web::{error::HttpError, http::StatusCode, test, web, App};
 use chrono::{TimeZone, Utc};
-use event_tracker::api::get_events;
+use event_tracker::api::{get_event_by_id, get_events, post_event};
+use event_tracker::auth::{authenticate, hash_key, ApiKeyConfig, ApiKeys};
 use event_tracker::model::Event;
+use event_tracker::rate_limit::{
+    limit_requests, RateLimitConfig, RateLimitKey, RequestLimiter, RouteLimit,
+};
 use event_tracker::storage::{EventStore, InMemoryEventStore};
+use event_tracker::tenant::DEFAULT_TENANT;
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -29,8 +35,13 @@ async fn test_rate_limit_triggers() {
         event_type: "login".into(),
         timestamp: Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap(),
         payload: serde_json::json!({ "user_id": 1 }),
+        schema_version: 1,
+        metadata: Default::default(),
+        received_at: Utc::now(),
+        clock_skewed: false,
+        stream: None,
     };
-    store.add_event(event.clone()).unwrap();
+    store.add_event(DEFAULT_TENANT, event.clone()).unwrap();
 
     let governor_conf = GovernorConfigBuilder::default()
         .seconds_per_request(2)
@@ -59,3 +70,247 @@ async fn test_rate_limit_triggers() {
     let resp3 = test::call_service(&app, req3).await;
     assert_eq!(resp3.status(), StatusCode::TOO_MANY_REQUESTS);
 }
+
+fn header<B>(resp: &ServiceResponse<B>, name: &str) -> String {
+    resp.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[actix_rt::test]
+async fn test_rate_limits_per_key_and_method_class() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let keys: ApiKeyConfig = serde_json::from_value(serde_json::json!({
+        "keys": [
+            { "id": "a", "key_sha256": hash_key("a-secret"), "scopes": ["admin"], "tenant": "shared" },
+            { "id": "b", "key_sha256": hash_key("b-secret"), "scopes": ["admin"], "tenant": "shared" }
+        ]
+    }))
+    .unwrap();
+    let limiter = RequestLimiter::new(&RateLimitConfig {
+        key: RateLimitKey::Principal,
+        reads: RouteLimit {
+            period_ms: 60_000,
+            burst: 3,
+        },
+        writes: RouteLimit {
+            period_ms: 60_000,
+            burst: 1,
+        },
+        ..Default::default()
+    })
+    .unwrap();
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(authenticate))
+            .app_data(web::Data::new(store))
+            .app_data(web::Data::new(ApiKeys::from_config(keys)))
+            .app_data(web::Data::new(limiter))
+            .service(
+                web::scope("")
+                    .wrap(from_fn(limit_requests))
+                    .service(post_event)
+                    .service(get_events),
+            ),
+    )
+    .await;
+    let post = |key: &str| {
+        test::TestRequest::post()
+            .uri("/events")
+            .insert_header(("Authorization", format!("Bearer {key}")))
+            .set_json(serde_json::json!({ "event_type": "login", "payload": {} }))
+            .to_request()
+    };
+    let get = |key: &str| {
+        test::TestRequest::get()
+            .uri("/events")
+            .insert_header(("Authorization", format!("Bearer {key}")))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, post("a-secret")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(header(&resp, "X-RateLimit-Limit"), "1");
+    assert_eq!(header(&resp, "X-RateLimit-Remaining"), "0");
+    assert_eq!(header(&resp, "X-RateLimit-Reset"), "60");
+
+    let resp = test::call_service(&app, post("a-secret")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&resp, "X-RateLimit-Remaining"), "0");
+    let retry_after: u64 = header(&resp, "Retry-After").parse().unwrap();
+    assert!((1..=60).contains(&retry_after));
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Too many requests");
+    assert!(body["message"].as_str().unwrap().contains("retry in"));
+
+    //Another key in the same tenant has its own bucket
+    assert_eq!(
+        test::call_service(&app, post("b-secret")).await.status(),
+        StatusCode::OK
+    );
+    //Reads are budgeted separately from writes
+    for remaining in ["2", "1", "0"] {
+        let resp = test::call_service(&app, get("a-secret")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(header(&resp, "X-RateLimit-Limit"), "3");
+        assert_eq!(header(&resp, "X-RateLimit-Remaining"), remaining);
+    }
+    assert_eq!(
+        test::call_service(&app, get("a-secret")).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+#[actix_rt::test]
+async fn test_route_rules_and_tenant_overrides() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let keys: ApiKeyConfig = serde_json::from_value(serde_json::json!({
+        "keys": [
+            { "id": "a", "key_sha256": hash_key("a-secret"), "scopes": ["admin"], "tenant": "big" },
+            { "id": "b", "key_sha256": hash_key("b-secret"), "scopes": ["admin"], "tenant": "small" }
+        ]
+    }))
+    .unwrap();
+    let limiter = RequestLimiter::new(
+        &serde_json::from_value(serde_json::json!({
+            "reads": { "period_ms": 60000, "burst": 5 },
+            "routes": [{
+                "path": "/events/{id}",
+                "method": "GET",
+                "limit": { "period_ms": 60000, "burst": 1 }
+            }],
+            "overrides": {
+                "tenant:big": { "reads": { "period_ms": 60000, "burst": 50 } }
+            }
+        }))
+        .unwrap(),
+    )
+    .unwrap();
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(authenticate))
+            .app_data(web::Data::new(store))
+            .app_data(web::Data::new(ApiKeys::from_config(keys)))
+            .app_data(web::Data::new(limiter))
+            .service(
+                web::scope("")
+                    .wrap(from_fn(limit_requests))
+                    .service(get_events)
+                    .service(get_event_by_id),
+            ),
+    )
+    .await;
+    let get = |uri: String, key: &str| {
+        test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(("Authorization", format!("Bearer {key}")))
+            .to_request()
+    };
+
+    //The tenant override raises the read limit for every key of the tenant
+    let resp = test::call_service(&app, get("/events".into(), "a-secret")).await;
+    assert_eq!(header(&resp, "X-RateLimit-Limit"), "50");
+    let resp = test::call_service(&app, get("/events".into(), "b-secret")).await;
+    assert_eq!(header(&resp, "X-RateLimit-Limit"), "5");
+
+    //The route rule has its own bucket and wins over the override
+    let by_id = || format!("/events/{}", Uuid::new_v4());
+    let resp = test::call_service(&app, get(by_id(), "a-secret")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(header(&resp, "X-RateLimit-Limit"), "1");
+    assert_eq!(
+        test::call_service(&app, get(by_id(), "a-secret"))
+            .await
+            .status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+    let resp = test::call_service(&app, get("/events".into(), "a-secret")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(header(&resp, "X-RateLimit-Remaining"), "48");
+}
+
+//Two replicas pointed at the same server draw from one budget.  Needs a Redis-compatible
+//server, e.g. `REDIS_URL=redis://127.0.0.1:6379 cargo test --features redis -- --ignored`.
+#[cfg(feature = "redis")]
+#[actix_rt::test]
+#[ignore = "needs a Redis-compatible server at REDIS_URL"]
+async fn test_redis_buckets_are_shared_between_replicas() {
+    use actix_web::HttpMessage;
+    use event_tracker::auth::{Credential, Principal};
+
+    let redis_url = std::env::var("REDIS_URL").expect("REDIS_URL must point at a server");
+    let config = RateLimitConfig {
+        key: RateLimitKey::Principal,
+        writes: RouteLimit {
+            period_ms: 60_000,
+            burst: 2,
+        },
+        redis_url: Some(redis_url),
+        ..Default::default()
+    };
+    let replicas = [
+        RequestLimiter::new(&config).unwrap(),
+        RequestLimiter::new(&config).unwrap(),
+    ];
+    let subject = Uuid::new_v4().to_string();
+    let request = || {
+        let req = test::TestRequest::post().uri("/events").to_srv_request();
+        req.extensions_mut().insert(Principal::new(
+            Credential::ApiKey,
+            &subject,
+            None,
+            Default::default(),
+        ));
+        req
+    };
+
+    let first = replicas[0].check(&request()).await.unwrap();
+    assert_eq!((first.remaining, first.retry_after_secs), (1, None));
+    let second = replicas[1].check(&request()).await.unwrap();
+    assert_eq!((second.remaining, second.retry_after_secs), (0, None));
+    let third = replicas[0].check(&request()).await.unwrap();
+    assert!(third.retry_after_secs.is_some());
+}
+
+#[cfg(feature = "redis")]
+#[actix_rt::test]
+async fn test_unreachable_redis_falls_back_to_local_buckets() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let limiter = RequestLimiter::new(&RateLimitConfig {
+        key: RateLimitKey::Tenant,
+        writes: RouteLimit {
+            period_ms: 60_000,
+            burst: 1,
+        },
+        redis_url: Some("redis://127.0.0.1:1".to_string()),
+        ..Default::default()
+    })
+    .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store))
+            .app_data(web::Data::new(limiter))
+            .service(
+                web::scope("")
+                    .wrap(from_fn(limit_requests))
+                    .service(post_event),
+            ),
+    )
+    .await;
+    let post = || {
+        test::TestRequest::post()
+            .uri("/events")
+            .set_json(serde_json::json!({ "event_type": "login", "payload": {} }))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, post()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(header(&resp, "X-RateLimit-Remaining"), "0");
+    assert_eq!(
+        test::call_service(&app, post()).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
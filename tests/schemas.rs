@@ -0,0 +1,128 @@
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use event_tracker::api::{delete_schema, get_schema, get_schemas, post_event, put_schema};
+use event_tracker::model::EventQuery;
+use event_tracker::schema::SchemaRegistry;
+use event_tracker::storage::{EventStore, InMemoryEventStore};
+use event_tracker::tenant::DEFAULT_TENANT;
+use serde_json::json;
+use std::sync::Arc;
+
+#[actix_rt::test]
+async fn test_registered_schema_rejects_nonconforming_payloads() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .app_data(web::Data::new(SchemaRegistry::new()))
+            .service(post_event)
+            .service(put_schema)
+            .service(get_schema),
+    )
+    .await;
+
+    let schema = json!({
+        "type": "object",
+        "required": ["user_id", "method"],
+        "properties": {
+            "user_id": { "type": "integer" },
+            "method": { "enum": ["password", "sso"] }
+        }
+    });
+    let req = test::TestRequest::put()
+        .uri("/admin/schemas/login")
+        .set_json(&schema)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/admin/schemas/login")
+        .to_request();
+    let fetched: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(fetched, schema);
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(json!({
+            "event_type": "login",
+            "timestamp": "2025-01-01T12:00:00Z",
+            "payload": { "user_id": "abc", "method": "magic" }
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let message = body["message"].as_str().unwrap();
+    assert!(message.contains("'/user_id'"), "{message}");
+    assert!(message.contains("'/method'"), "{message}");
+    assert!(store
+        .query_events(DEFAULT_TENANT, EventQuery::default())
+        .unwrap()
+        .is_empty());
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .set_json(json!({
+            "event_type": "login",
+            "timestamp": "2025-01-01T12:00:00Z",
+            "payload": { "user_id": 1, "method": "sso" }
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        store
+            .query_events(DEFAULT_TENANT, EventQuery::default())
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[actix_rt::test]
+async fn test_schema_admin_lifecycle() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store))
+            .app_data(web::Data::new(SchemaRegistry::new()))
+            .service(get_schemas)
+            .service(get_schema)
+            .service(put_schema)
+            .service(delete_schema),
+    )
+    .await;
+
+    let req = test::TestRequest::put()
+        .uri("/admin/schemas/login")
+        .set_json(json!({ "type": "not-a-type" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::put()
+        .uri("/admin/schemas/login")
+        .set_json(json!({ "type": "object" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().uri("/admin/schemas").to_request();
+    let all: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(all, json!({ "login": { "type": "object" } }));
+
+    let req = test::TestRequest::delete()
+        .uri("/admin/schemas/login")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/admin/schemas/login")
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
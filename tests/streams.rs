@@ -0,0 +1,137 @@
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use event_tracker::api::{append_stream_events, get_events, get_stream};
+use event_tracker::catalog::{CatalogConfig, EventTypeCatalog};
+use event_tracker::model::{Event, StreamEvents};
+use event_tracker::storage::{EventStore, InMemoryEventStore};
+use serde_json::json;
+use std::sync::Arc;
+
+fn deposit(amount: u64) -> serde_json::Value {
+    json!({
+        "event_type": "deposited",
+        "timestamp": "2025-01-01T12:00:00Z",
+        "payload": { "amount": amount }
+    })
+}
+
+#[actix_rt::test]
+async fn test_append_and_read_stream() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .service(append_stream_events)
+            .service(get_stream)
+            .service(get_events),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/streams/account-1/events")
+        .set_json(json!({ "expected_version": 0, "events": [deposit(10), deposit(20)] }))
+        .to_request();
+    let appended: StreamEvents = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(appended.version, 2);
+
+    let req = test::TestRequest::post()
+        .uri("/streams/account-1/events")
+        .set_json(json!({ "expected_version": 2, "events": [deposit(30)] }))
+        .to_request();
+    let appended: StreamEvents = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(appended.version, 3);
+    assert_eq!(appended.events[0].stream.as_ref().unwrap().version, 3);
+
+    let req = test::TestRequest::get()
+        .uri("/streams/account-1")
+        .to_request();
+    let stream: StreamEvents = test::call_and_read_body_json(&app, req).await;
+    let amounts: Vec<u64> = stream
+        .events
+        .iter()
+        .map(|e| e.payload["amount"].as_u64().unwrap())
+        .collect();
+    assert_eq!(amounts, vec![10, 20, 30]);
+
+    let req = test::TestRequest::get()
+        .uri("/streams/account-1?from_version=2")
+        .to_request();
+    let stream: StreamEvents = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(stream.events.len(), 2);
+    assert_eq!(stream.version, 3);
+
+    //Stream events are ordinary events for the rest of the API
+    let req = test::TestRequest::get()
+        .uri("/events?event_type=deposited")
+        .to_request();
+    let events: Vec<Event> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(events.len(), 3);
+}
+
+#[actix_rt::test]
+async fn test_append_with_stale_expected_version_returns_409() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .service(append_stream_events),
+    )
+    .await;
+
+    for (expected, status) in [(0, StatusCode::OK), (0, StatusCode::CONFLICT)] {
+        let req = test::TestRequest::post()
+            .uri("/streams/account-1/events")
+            .set_json(json!({ "expected_version": expected, "events": [deposit(10)] }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status);
+    }
+
+    //Without expected_version the append is unconditional
+    let req = test::TestRequest::post()
+        .uri("/streams/account-1/events")
+        .set_json(json!({ "events": [deposit(10)] }))
+        .to_request();
+    let appended: StreamEvents = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(appended.version, 2);
+}
+
+#[actix_rt::test]
+async fn test_append_batch_is_all_or_nothing() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let catalog: CatalogConfig = serde_json::from_value(json!({
+        "strict": true,
+        "event_types": { "deposited": {} }
+    }))
+    .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .app_data(web::Data::new(EventTypeCatalog::from_config(catalog)))
+            .service(append_stream_events)
+            .service(get_stream),
+    )
+    .await;
+
+    let unknown = json!({ "event_type": "teleported", "payload": {} });
+    let req = test::TestRequest::post()
+        .uri("/streams/account-1/events")
+        .set_json(json!({ "events": [deposit(10), unknown] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(store.stats(None).unwrap().event_count, 0);
+
+    let req = test::TestRequest::get()
+        .uri("/streams/account-1")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::post()
+        .uri("/streams/account-1/events")
+        .set_json(json!({ "events": [] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
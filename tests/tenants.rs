@@ -0,0 +1,167 @@
+use actix_web::http::StatusCode;
+use actix_web::middleware::from_fn;
+use actix_web::{test, web, App};
+use event_tracker::api::{get_event_by_id, get_events, get_stats, post_event};
+use event_tracker::auth::{authenticate, hash_key, ApiKeyConfig, ApiKeys};
+use event_tracker::model::Event;
+use event_tracker::storage::{EventStore, InMemoryEventStore};
+use event_tracker::tenant::{TenantConfig, DEFAULT_TENANT, TENANT_HEADER};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+fn login() -> Value {
+    json!({ "event_type": "login", "timestamp": "2025-01-01T12:00:00Z", "payload": { "user_id": 1 } })
+}
+
+#[actix_rt::test]
+async fn test_tenant_header_isolates_events() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(store.clone()))
+            .service(post_event)
+            .service(get_events)
+            .service(get_event_by_id),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .insert_header((TENANT_HEADER, "team-a"))
+        .set_json(login())
+        .to_request();
+    let stored: Event = test::call_and_read_body_json(&app, req).await;
+
+    let req = test::TestRequest::get()
+        .uri("/events")
+        .insert_header((TENANT_HEADER, "team-b"))
+        .to_request();
+    let events: Vec<Event> = test::call_and_read_body_json(&app, req).await;
+    assert!(events.is_empty());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/events/{}", stored.id))
+        .insert_header((TENANT_HEADER, "team-b"))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    //Requests without the header use the default tenant
+    let req = test::TestRequest::get().uri("/events").to_request();
+    let events: Vec<Event> = test::call_and_read_body_json(&app, req).await;
+    assert!(events.is_empty());
+
+    let req = test::TestRequest::get()
+        .uri("/events")
+        .insert_header((TENANT_HEADER, "team-a"))
+        .to_request();
+    let events: Vec<Event> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(events, vec![stored.clone()]);
+    assert!(store.get_by_id("team-a", stored.id).unwrap().is_some());
+    assert!(store
+        .get_by_id(DEFAULT_TENANT, stored.id)
+        .unwrap()
+        .is_none());
+
+    let req = test::TestRequest::get()
+        .uri("/events")
+        .insert_header((TENANT_HEADER, "team/a"))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[actix_rt::test]
+async fn test_tenant_bound_keys_and_quotas() {
+    let limits: TenantConfig = serde_json::from_value(json!({
+        "tenants": { "team-a": { "max_events": 1 } }
+    }))
+    .unwrap();
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new().with_tenant_limits(limits));
+    let keys: ApiKeyConfig = serde_json::from_value(json!({
+        "keys": [{
+            "id": "team-a-ingest",
+            "key_sha256": hash_key("team-a-secret"),
+            "scopes": ["events:write", "events:read"],
+            "tenant": "team-a"
+        }, {
+            "id": "unbound-ingest",
+            "key_sha256": hash_key("unbound-secret"),
+            "scopes": ["events:write", "events:read"]
+        }, {
+            "id": "ops",
+            "key_sha256": hash_key("ops-secret"),
+            "scopes": ["admin"]
+        }]
+    }))
+    .unwrap();
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(authenticate))
+            .app_data(web::Data::new(store.clone()))
+            .app_data(web::Data::new(ApiKeys::from_config(keys)))
+            .service(post_event)
+            .service(get_stats),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .insert_header(("Authorization", "Bearer team-a-secret"))
+        .set_json(login())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    //A key bound to a tenant cannot select another one
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .insert_header(("Authorization", "Bearer team-a-secret"))
+        .insert_header((TENANT_HEADER, "team-b"))
+        .set_json(login())
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    //Keys without a tenant stay in the default tenant unless they are admins
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .insert_header(("Authorization", "Bearer unbound-secret"))
+        .insert_header((TENANT_HEADER, "team-a"))
+        .set_json(login())
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+    let req = test::TestRequest::get()
+        .uri("/stats")
+        .insert_header(("Authorization", "Bearer ops-secret"))
+        .insert_header((TENANT_HEADER, "team-a"))
+        .to_request();
+    let stats: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(stats["event_count"], 1);
+
+    let req = test::TestRequest::post()
+        .uri("/events")
+        .insert_header(("Authorization", "Bearer team-a-secret"))
+        .set_json(login())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::INSUFFICIENT_STORAGE);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Insufficient storage");
+
+    let req = test::TestRequest::get()
+        .uri("/stats")
+        .insert_header(("Authorization", "Bearer team-a-secret"))
+        .to_request();
+    let stats: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(stats["event_count"], 1);
+    assert_eq!(store.stats(Some("team-a")).unwrap().event_count, 1);
+}
@@ -0,0 +1,98 @@
+use actix_web::http::StatusCode;
+use actix_web::middleware::from_fn;
+use actix_web::{test, web, App};
+use event_tracker::api::{append_stream_events, get_usage, post_event};
+use event_tracker::auth::{authenticate, hash_key, ApiKeyConfig, ApiKeys};
+use event_tracker::storage::{EventStore, InMemoryEventStore};
+use event_tracker::usage::{UsageConfig, UsageReport, UsageTracker};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+fn event(size: usize) -> Value {
+    json!({ "event_type": "upload", "timestamp": "2025-01-01T12:00:00Z", "payload": { "blob": "x".repeat(size) } })
+}
+
+#[actix_rt::test]
+async fn test_daily_quotas_by_events_and_bytes() {
+    let keys: ApiKeyConfig = serde_json::from_value(json!({
+        "keys": [
+            { "id": "small", "key_sha256": hash_key("small-secret"), "scopes": ["events:write"] },
+            { "id": "bulk", "key_sha256": hash_key("bulk-secret"), "scopes": ["events:write"] },
+            { "id": "ops", "key_sha256": hash_key("ops-secret"), "scopes": ["admin"] }
+        ]
+    }))
+    .unwrap();
+    let quotas: UsageConfig = serde_json::from_value(json!({
+        "default": { "max_events": 2 },
+        "keys": { "key:bulk": { "max_bytes": 3000 } }
+    }))
+    .unwrap();
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(authenticate))
+            .app_data(web::Data::new(store))
+            .app_data(web::Data::new(ApiKeys::from_config(keys)))
+            .app_data(web::Data::new(UsageTracker::new(quotas)))
+            .service(post_event)
+            .service(append_stream_events)
+            .service(get_usage),
+    )
+    .await;
+    let post = |key: &str, size: usize| {
+        test::TestRequest::post()
+            .uri("/events")
+            .insert_header(("Authorization", format!("Bearer {key}")))
+            .set_json(event(size))
+            .to_request()
+    };
+
+    for _ in 0..2 {
+        let resp = test::call_service(&app, post("small-secret", 10)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    let resp = test::call_service(&app, post("small-secret", 10)).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(resp.headers().contains_key("Retry-After"));
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Quota exceeded");
+
+    //A single large event uses up most of the byte budget; a stream batch over it is rejected whole
+    let resp = test::call_service(&app, post("bulk-secret", 2000)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let req = test::TestRequest::post()
+        .uri("/streams/uploads/events")
+        .insert_header(("Authorization", "Bearer bulk-secret"))
+        .set_json(json!({ "events": [event(500), event(500)] }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/usage")
+        .insert_header(("Authorization", "Bearer bulk-secret"))
+        .to_request();
+    let usage: UsageReport = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(usage.key, "key:bulk");
+    assert_eq!(usage.events, 1);
+    assert!(usage.bytes > 2000 && usage.bytes < 3000);
+    assert_eq!(usage.quota.max_bytes, Some(3000));
+
+    //Only admins can look at another key's usage
+    let req = test::TestRequest::get()
+        .uri("/usage?key=key:small")
+        .insert_header(("Authorization", "Bearer bulk-secret"))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+    let req = test::TestRequest::get()
+        .uri("/usage?key=key:small")
+        .insert_header(("Authorization", "Bearer ops-secret"))
+        .to_request();
+    let usage: UsageReport = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((usage.key.as_str(), usage.events), ("key:small", 2));
+}